@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE products
+DROP COLUMN currency;
+
+ALTER TABLE products
+ALTER COLUMN cost TYPE FLOAT
+USING cost::float8;
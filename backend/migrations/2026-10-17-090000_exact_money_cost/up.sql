@@ -0,0 +1,8 @@
+-- Store prices as exact decimals together with their ISO-4217 currency
+ALTER TABLE products
+ALTER COLUMN cost TYPE NUMERIC(19, 4)
+USING round(cost::numeric, 2);
+
+ALTER TABLE products
+ADD COLUMN currency VARCHAR(3) NOT NULL DEFAULT 'USD'
+CHECK (currency ~ '^[A-Z]{3}$');
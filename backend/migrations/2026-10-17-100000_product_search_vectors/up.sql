@@ -0,0 +1,12 @@
+-- Full-text search documents for product names and variant values
+ALTER TABLE products
+ADD COLUMN search_vector TSVECTOR
+GENERATED ALWAYS AS (to_tsvector('simple'::regconfig, name)) STORED;
+
+CREATE INDEX idx_products_search_vector ON products USING GIN (search_vector);
+
+ALTER TABLE product_variants
+ADD COLUMN search_vector TSVECTOR
+GENERATED ALWAYS AS (to_tsvector('simple'::regconfig, coalesce(value, ''))) STORED;
+
+CREATE INDEX idx_product_variants_search_vector ON product_variants USING GIN (search_vector);
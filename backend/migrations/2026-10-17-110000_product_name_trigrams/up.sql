@@ -0,0 +1,4 @@
+-- Trigram index for typo-tolerant name matching and autocomplete
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX idx_products_name_trgm ON products USING GIN (name gin_trgm_ops);
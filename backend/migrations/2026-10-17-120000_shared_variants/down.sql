@@ -0,0 +1,3 @@
+-- Merged duplicates are not split back out
+ALTER TABLE variants DROP COLUMN allowed_values;
+ALTER TABLE variants DROP CONSTRAINT variants_name_key;
@@ -0,0 +1,26 @@
+-- Variant definitions are shared between products, so there must only be one per name
+UPDATE variants SET name = btrim(name);
+
+-- Keep one row per name and point every product value at it
+CREATE TEMPORARY TABLE variant_merges AS
+SELECT v.id AS duplicate_id, keeper.id AS keeper_id
+FROM variants v
+JOIN (SELECT DISTINCT ON (name) id, name FROM variants ORDER BY name, id) keeper
+    ON keeper.name = v.name
+WHERE v.id <> keeper.id;
+
+UPDATE product_variants pv
+SET variant_id = m.keeper_id
+FROM variant_merges m
+WHERE pv.variant_id = m.duplicate_id;
+
+DELETE FROM variants v
+USING variant_merges m
+WHERE v.id = m.duplicate_id;
+
+DROP TABLE variant_merges;
+
+ALTER TABLE variants ADD CONSTRAINT variants_name_key UNIQUE (name);
+
+-- NULL means any value is accepted
+ALTER TABLE variants ADD COLUMN allowed_values TEXT[];
@@ -0,0 +1,2 @@
+-- Bumped on every write, exposed to clients as the product's ETag
+ALTER TABLE products ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
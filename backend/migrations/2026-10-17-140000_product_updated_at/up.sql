@@ -0,0 +1,2 @@
+-- Moves together with version, drives the Last-Modified header
+ALTER TABLE products ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
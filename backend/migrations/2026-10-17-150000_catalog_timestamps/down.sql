@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS idx_products_updated_at;
+DROP INDEX IF EXISTS idx_products_created_at;
+
+ALTER TABLE product_variants DROP COLUMN updated_at, DROP COLUMN created_at;
+ALTER TABLE variants DROP COLUMN updated_at, DROP COLUMN created_at;
+ALTER TABLE products DROP COLUMN created_at;
@@ -0,0 +1,14 @@
+-- Existing rows get the migration time, there is no better record of when they were added
+ALTER TABLE products ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+ALTER TABLE variants
+ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+ALTER TABLE product_variants
+ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- Incremental syncs and newest-first listings scan by these
+CREATE INDEX idx_products_created_at ON products (created_at);
+CREATE INDEX idx_products_updated_at ON products (updated_at);
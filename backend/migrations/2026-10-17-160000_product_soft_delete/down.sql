@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_products_deleted_at;
+
+-- Tombstoned rows would reappear as live products
+DELETE FROM products WHERE deleted_at IS NOT NULL;
+ALTER TABLE products DROP COLUMN deleted_at;
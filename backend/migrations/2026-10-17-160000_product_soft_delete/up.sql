@@ -0,0 +1,4 @@
+-- Tombstone set by DELETE /products/{id}; purged for good once past the retention period
+ALTER TABLE products ADD COLUMN deleted_at TIMESTAMPTZ;
+
+CREATE INDEX idx_products_deleted_at ON products (deleted_at) WHERE deleted_at IS NOT NULL;
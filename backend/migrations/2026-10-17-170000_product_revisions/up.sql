@@ -0,0 +1,21 @@
+-- One row per recorded write, holding the product's state right after it. Diffs come
+-- from comparing neighbouring revisions, and a revert copies a snapshot back.
+CREATE TABLE product_revisions (
+    product_id UUID NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    -- The product version the write produced
+    revision INTEGER NOT NULL,
+    action VARCHAR(16) NOT NULL,
+    changed_by VARCHAR,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    name VARCHAR NOT NULL,
+    cost NUMERIC NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    active BOOLEAN NOT NULL,
+    deleted BOOLEAN NOT NULL DEFAULT false,
+    PRIMARY KEY (product_id, revision)
+);
+
+-- Products written before history existed start from their current state
+INSERT INTO product_revisions (product_id, revision, action, changed_at, name, cost, currency, active, deleted)
+SELECT id, version, 'baseline', updated_at, name, cost, currency, active, deleted_at IS NOT NULL
+FROM products;
@@ -0,0 +1,14 @@
+-- Responses of POST requests sent with an Idempotency-Key, replayed on retries
+CREATE TABLE idempotency_keys (
+    key VARCHAR(255) PRIMARY KEY,
+    -- SHA-256 of method, path and body, so a reused key with another request is caught
+    request_hash VARCHAR(64) NOT NULL,
+    -- NULL while the first request is still being handled
+    status_code SMALLINT,
+    response_headers JSONB,
+    response_body BYTEA,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_idempotency_keys_expires_at ON idempotency_keys (expires_at);
@@ -0,0 +1,3 @@
+-- Merged duplicates are not split back out
+DROP INDEX variants_name_lower_key;
+ALTER TABLE variants ADD CONSTRAINT variants_name_key UNIQUE (name);
@@ -0,0 +1,49 @@
+-- Variant names are unique regardless of case, the way attribute filters and facets match them
+CREATE TEMPORARY TABLE variant_merges AS
+SELECT v.id AS duplicate_id, keeper.id AS keeper_id
+FROM variants v
+JOIN (
+    SELECT DISTINCT ON (lower(btrim(name))) id, name
+    FROM variants
+    ORDER BY lower(btrim(name)), created_at, id
+) keeper ON lower(btrim(keeper.name)) = lower(btrim(v.name))
+WHERE v.id <> keeper.id;
+
+-- The kept definition accepts what any of the merged ones accepted, and anything if one of them did
+UPDATE variants k
+SET allowed_values = CASE WHEN merged.unrestricted THEN NULL ELSE merged.allowed_values END,
+    updated_at = now()
+FROM (
+    SELECT m.keeper_id,
+           bool_or(v.allowed_values IS NULL) AS unrestricted,
+           array_agg(DISTINCT value) FILTER (WHERE value IS NOT NULL) AS allowed_values
+    FROM (SELECT keeper_id, keeper_id AS variant_id FROM variant_merges
+          UNION SELECT keeper_id, duplicate_id FROM variant_merges) m
+    JOIN variants v ON v.id = m.variant_id
+    LEFT JOIN LATERAL unnest(v.allowed_values) AS value ON true
+    GROUP BY m.keeper_id
+) merged
+WHERE k.id = merged.keeper_id;
+
+-- Products whose values move to another definition change
+UPDATE products p
+SET version = version + 1, updated_at = now()
+WHERE EXISTS (
+    SELECT 1 FROM product_variants pv
+    JOIN variant_merges m ON pv.variant_id = m.duplicate_id
+    WHERE pv.product_id = p.id
+);
+
+UPDATE product_variants pv
+SET variant_id = m.keeper_id
+FROM variant_merges m
+WHERE pv.variant_id = m.duplicate_id;
+
+DELETE FROM variants v
+USING variant_merges m
+WHERE v.id = m.duplicate_id;
+
+DROP TABLE variant_merges;
+
+ALTER TABLE variants DROP CONSTRAINT variants_name_key;
+CREATE UNIQUE INDEX variants_name_lower_key ON variants (lower(btrim(name)));
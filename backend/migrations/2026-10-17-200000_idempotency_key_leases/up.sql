@@ -0,0 +1,3 @@
+-- How long the request that claimed a key holds it. A key still without a response
+-- after that was left behind by a crashed server, and a retry may take it over.
+ALTER TABLE idempotency_keys ADD COLUMN locked_until TIMESTAMPTZ NOT NULL DEFAULT now();
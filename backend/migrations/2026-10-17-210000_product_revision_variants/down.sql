@@ -0,0 +1 @@
+ALTER TABLE product_revisions DROP COLUMN variants;
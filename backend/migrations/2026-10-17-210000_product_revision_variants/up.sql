@@ -0,0 +1,17 @@
+-- The product's variants and their values, as [{variant_id, name, values}] ordered by name
+ALTER TABLE product_revisions ADD COLUMN variants JSONB;
+
+-- Revisions recorded before variants were tracked take the product's current variants
+UPDATE product_revisions r
+SET variants = COALESCE((
+    SELECT jsonb_agg(jsonb_build_object('variant_id', g.id, 'name', g.name, 'values', g.vals) ORDER BY g.name)
+    FROM (
+        SELECT v.id, v.name, jsonb_agg(pv.value ORDER BY pv.value, pv.id) AS vals
+        FROM product_variants pv
+        JOIN variants v ON v.id = pv.variant_id
+        WHERE pv.product_id = r.product_id
+        GROUP BY v.id, v.name
+    ) g
+), '[]'::jsonb);
+
+ALTER TABLE product_revisions ALTER COLUMN variants SET NOT NULL;
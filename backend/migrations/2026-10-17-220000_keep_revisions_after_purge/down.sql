@@ -0,0 +1,6 @@
+DELETE FROM product_revisions r
+WHERE NOT EXISTS (SELECT 1 FROM products p WHERE p.id = r.product_id);
+
+ALTER TABLE product_revisions
+    ADD CONSTRAINT product_revisions_product_id_fkey
+    FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE;
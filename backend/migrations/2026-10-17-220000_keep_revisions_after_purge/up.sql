@@ -0,0 +1,3 @@
+-- Revisions are the audit trail and outlive a purge of their product, which records
+-- a last 'purge' revision before the row goes
+ALTER TABLE product_revisions DROP CONSTRAINT product_revisions_product_id_fkey;
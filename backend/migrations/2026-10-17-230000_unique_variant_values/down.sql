@@ -0,0 +1 @@
+DROP INDEX product_variants_value_key;
@@ -0,0 +1,9 @@
+-- A product lists each value of a variant once, keeping the earliest of any duplicates
+DELETE FROM product_variants pv
+USING product_variants keeper
+WHERE keeper.product_id = pv.product_id
+  AND keeper.variant_id = pv.variant_id
+  AND keeper.value = pv.value
+  AND (keeper.created_at, keeper.id) < (pv.created_at, pv.id);
+
+CREATE UNIQUE INDEX product_variants_value_key ON product_variants (product_id, variant_id, value);
@@ -0,0 +1 @@
+DROP INDEX idx_product_revisions_changed_at;
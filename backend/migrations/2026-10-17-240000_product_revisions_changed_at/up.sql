@@ -0,0 +1,2 @@
+-- Product lists are last modified by the newest revision, purges included
+CREATE INDEX idx_product_revisions_changed_at ON product_revisions (changed_at);
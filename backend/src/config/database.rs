@@ -1,18 +1,120 @@
-use diesel::r2d2::{ Pool, ConnectionManager, PooledConnection };
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Result};
+use diesel::connection::SimpleConnection;
+use diesel::r2d2::{ Pool, ConnectionManager, CustomizeConnection };
 use diesel::pg::PgConnection;
+use tokio::sync::Semaphore;
+use tokio::task::spawn_blocking;
+use tokio::time::timeout;
+use tracing::{debug, error, warn, Span};
 use crate::config::Settings;
+use crate::traits::responses::AppError;
 
 pub type DbPool = Pool<ConnectionManager<PgConnection>>;
-pub type DbConnection = PooledConnection<ConnectionManager<PgConnection>>;
 
+// Makes Postgres cancel any statement running longer than the configured time
+#[derive(Debug)]
+struct StatementTimeout(u64);
+
+impl CustomizeConnection<PgConnection, diesel::r2d2::Error> for StatementTimeout {
+    fn on_acquire(&self, conn: &mut PgConnection) -> Result<(), diesel::r2d2::Error> {
+        conn.batch_execute(&format!("SET statement_timeout = {}", self.0))
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
 
 pub fn create_pool(settings: &Settings) -> DbPool {
     let manager = ConnectionManager::<PgConnection>::new(&settings.database_url);
-    
+
     Pool::builder()
         .max_size(settings.max_pool_size)
         .min_idle(Some(settings.min_idle_size))
         .test_on_check_out(true)
+        .connection_timeout(Duration::from_millis(settings.db_acquire_timeout_ms))
+        .connection_customizer(Box::new(StatementTimeout(settings.db_statement_timeout_ms)))
         .build(manager)
         .expect("Failed to create connection pool")
-}
\ No newline at end of file
+}
+
+// Set when the request waiting for a job goes away, e.g. because the client disconnected
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+// Runs Diesel's blocking calls on tokio's blocking threads so they never stall an
+// actix worker. At most one job per pooled connection runs at a time; the others
+// wait for a slot, and give up with a 503 once the pool's connection timeout has
+// passed. A job whose request was dropped before it started is skipped, while one
+// already running finishes its transaction, bounded by the statement timeout.
+#[derive(Clone)]
+pub struct Database {
+    pool: DbPool,
+    slots: Arc<Semaphore>
+}
+
+impl Database {
+    pub fn new(pool: DbPool) -> Self {
+        let slots = Arc::new(Semaphore::new(pool.max_size() as usize));
+        Self { pool, slots }
+    }
+
+    pub async fn run<T, F>(&self, job: F) -> Result<T>
+    where
+        F: FnOnce(&mut PgConnection) -> Result<T> + Send + 'static,
+        T: Send + 'static
+    {
+        let started = Instant::now();
+        let wait = self.pool.connection_timeout();
+        let slot = match timeout(wait, self.slots.clone().acquire_owned()).await {
+            Ok(Ok(slot)) => slot,
+            Ok(Err(e)) => return Err(AppError::Unavailable(e.into()).into()),
+            Err(_) => {
+                warn!(waited_ms = wait.as_millis() as u64, "Timed out waiting for a database slot");
+                return Err(AppError::Unavailable(anyhow!("Timed out waiting for a database connection")).into());
+            }
+        };
+
+        let cancel = CancelOnDrop(Arc::new(AtomicBool::new(false)));
+        let cancelled = cancel.0.clone();
+        let pool = self.pool.clone();
+        let span = Span::current();
+        let task = spawn_blocking(move || {
+            // The slot is held until the thread is done, even if nobody waits for it anymore
+            let _slot = slot;
+            let _entered = span.enter();
+            if cancelled.load(Ordering::Relaxed) {
+                debug!("Request went away before its database work started");
+                return Err(anyhow!("Request cancelled"));
+            }
+
+            debug!("Acquiring database connection from pool");
+            let remaining = wait.saturating_sub(started.elapsed());
+            let mut conn = match pool.get_timeout(remaining) {
+                Ok(conn) => conn,
+                Err(e) => {
+                    error!(error = %e, "Failed to acquire database connection");
+                    return Err(e.into());
+                }
+            };
+            if cancelled.load(Ordering::Relaxed) {
+                debug!("Request went away while waiting for a connection");
+                return Err(anyhow!("Request cancelled"));
+            }
+            job(&mut conn)
+        });
+
+        match task.await {
+            Ok(result) => result,
+            Err(e) => {
+                error!(error = %e, "Database task did not complete");
+                Err(AppError::Internal(anyhow!("Database task failed: {}", e)).into())
+            }
+        }
+    }
+}
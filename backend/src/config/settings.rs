@@ -6,13 +6,50 @@ pub struct Settings {
     #[serde(default = "default_max_pool_size")]
     pub max_pool_size: u32,
     #[serde(default = "default_min_idle_size")]
-    pub min_idle_size: u32
+    pub min_idle_size: u32,
+    // How long a request waits for a database connection before it's answered with a 503
+    #[serde(default = "default_db_acquire_timeout_ms")]
+    pub db_acquire_timeout_ms: u64,
+    // Postgres cancels statements running longer than this
+    #[serde(default = "default_db_statement_timeout_ms")]
+    pub db_statement_timeout_ms: u64,
+    // Cache-Control sent with product reads. The default lets caches keep a copy
+    // but revalidate it with the ETag on every use.
+    #[serde(default = "default_product_cache_control")]
+    pub product_cache_control: String,
+    // How long deleted products can still be restored before a purge removes them
+    #[serde(default = "default_deleted_product_retention_days")]
+    pub deleted_product_retention_days: i64,
+    // How long a response is replayed for retries carrying the same Idempotency-Key
+    #[serde(default = "default_idempotency_key_ttl_hours")]
+    pub idempotency_key_ttl_hours: i64,
+    // How long a request holds its Idempotency-Key. Should a server die mid-request,
+    // retries are refused until then and may take the key over afterwards.
+    #[serde(default = "default_idempotency_lease_secs")]
+    pub idempotency_lease_secs: i64,
+    // Most products a single bulk update or delete may touch
+    #[serde(default = "default_bulk_change_limit")]
+    pub bulk_change_limit: i64
 }
 
 fn default_max_pool_size() -> u32 {10}
 
 fn default_min_idle_size() -> u32 {2}
 
+fn default_db_acquire_timeout_ms() -> u64 {5000}
+
+fn default_db_statement_timeout_ms() -> u64 {30000}
+
+fn default_product_cache_control() -> String {"public, no-cache".to_string()}
+
+fn default_deleted_product_retention_days() -> i64 {30}
+
+fn default_idempotency_key_ttl_hours() -> i64 {24}
+
+fn default_idempotency_lease_secs() -> i64 {300}
+
+fn default_bulk_change_limit() -> i64 {1000}
+
 
 pub fn get_settings() -> Result<Settings, serde_env::Error> {
     dotenvy::dotenv().ok();
@@ -0,0 +1,83 @@
+use actix_web::error::{InternalError, JsonPayloadError, PathError, QueryPayloadError};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{HttpRequest, HttpResponse, Resource, Route};
+use crate::prelude::*;
+use crate::traits::responses::Problem;
+
+fn rejected<E: std::fmt::Debug + std::fmt::Display + 'static>(err: E, response: HttpResponse) -> actix_web::Error {
+    InternalError::from_response(err, response).into()
+}
+
+fn json_error(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let response = match &err {
+        JsonPayloadError::OverflowKnownLength { limit, .. } | JsonPayloadError::Overflow { limit } => {
+            Problem::from_status(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("The JSON body is larger than {} bytes", limit)
+            ).into_response()
+        }
+        JsonPayloadError::ContentType => {
+            Problem::from_status(StatusCode::UNSUPPORTED_MEDIA_TYPE, "The body must be sent as application/json")
+                .into_response()
+        }
+        JsonPayloadError::Deserialize(e) => AppError::Validation(format!("Invalid JSON body: {}", e)).error_response(),
+        other => Problem::from_status(StatusCode::BAD_REQUEST, other.to_string()).into_response()
+    };
+    rejected(err, response)
+}
+
+fn path_error(err: PathError, req: &HttpRequest) -> actix_web::Error {
+    let response = match &err {
+        PathError::Deserialize(e) => AppError::Validation(format!("Invalid path '{}': {}", req.path(), e)).error_response(),
+        other => Problem::from_status(StatusCode::BAD_REQUEST, other.to_string()).into_response()
+    };
+    rejected(err, response)
+}
+
+fn query_error(err: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let response = match &err {
+        QueryPayloadError::Deserialize(e) => AppError::Validation(format!("Invalid query string: {}", e)).error_response(),
+        other => Problem::from_status(StatusCode::BAD_REQUEST, other.to_string()).into_response()
+    };
+    rejected(err, response)
+}
+
+async fn route_not_found(req: HttpRequest) -> HttpResponse {
+    AppError::NotFound(format!("No route matches {} {}", req.method(), req.path())).error_response()
+}
+
+fn method_not_allowed(allowed: Vec<Method>) -> Route {
+    let allow = allowed.iter().map(Method::as_str).collect::<Vec<_>>().join(", ");
+    web::to(move |req: HttpRequest| {
+        let allow = allow.clone();
+        async move {
+            let mut response = Problem::from_status(
+                StatusCode::METHOD_NOT_ALLOWED,
+                format!("{} is not supported on {}, use {}", req.method(), req.path(), allow)
+            ).into_response();
+            if let Ok(value) = HeaderValue::from_str(&allow) {
+                response.headers_mut().insert(header::ALLOW, value);
+            }
+            response
+        }
+    })
+}
+
+// A resource serving one route per method; any other method gets a 405 naming the allowed ones
+// instead of falling through to the app's 404
+pub fn resource(path: &str, routes: Vec<(Method, Route)>) -> Resource {
+    let allowed = routes.iter().map(|(method, _)| method.clone()).collect();
+    routes
+        .into_iter()
+        .fold(web::resource(path), |resource, (method, route)| resource.route(route.method(method)))
+        .default_service(method_not_allowed(allowed))
+}
+
+// Extractor failures and unknown routes answer with the same problem body as the handlers
+pub fn create_error_controller(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::JsonConfig::default().error_handler(json_error))
+        .app_data(web::PathConfig::default().error_handler(path_error))
+        .app_data(web::QueryConfig::default().error_handler(query_error))
+        .default_service(web::to(route_not_found));
+}
@@ -0,0 +1,59 @@
+use std::ops::Deref;
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+use crate::models::Validate;
+use crate::prelude::*;
+use tracing::warn;
+
+// Who to record in the product history. There is no authentication yet, so
+// callers name themselves in this header.
+const ACTOR_HEADER: &str = "X-Actor";
+
+pub fn actor(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(ACTOR_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+// A JSON body that passed its validation rules. Bodies that don't are answered
+// with a 422 listing every failing field, before the handler runs.
+#[derive(Debug)]
+pub struct ValidJson<T>(pub T);
+
+impl<T> ValidJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidJson<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let json = web::Json::<T>::from_request(req, payload);
+        let path = req.path().to_string();
+        Box::pin(async move {
+            let body = json.await?.into_inner();
+            match body.validated() {
+                Ok(()) => Ok(ValidJson(body)),
+                Err(errors) => {
+                    warn!(path = %path, invalid_fields = errors.len(), "Rejected invalid request body");
+                    Err(AppError::InvalidFields(errors).into())
+                }
+            }
+        })
+    }
+}
@@ -1,2 +1,8 @@
+pub mod errors;
+pub mod extractors;
 pub mod products;
-pub use products::*;
\ No newline at end of file
+pub mod variants;
+pub use errors::*;
+pub use extractors::*;
+pub use products::*;
+pub use variants::*;
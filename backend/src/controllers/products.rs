@@ -1,12 +1,29 @@
 use crate::prelude::*;
-use crate::models::{NewCompleteProduct, ProductFilters, ProductUpdates};
+use crate::controllers::{actor, resource, ValidJson};
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::HttpRequest;
+use crate::models::{
+    BulkChangeQuery, BulkQuery, BulkUpdate, ExportQuery, ImportQuery,
+    MAX_BULK_PRODUCTS, MAX_IMPORT_BYTES, includes_variants, parse_attribute_filters, IfMatch, IncludeQuery, NewCompleteProduct, ProductFilters,
+    PatchFormat, ProductDocument, ProductPatch, SuggestQuery, JSON_PATCH_CONTENT_TYPE, MERGE_PATCH_CONTENT_TYPE
+};
+use crate::config::Settings;
 use crate::services::ProductService;
+use crate::traits::responses::Problem;
 use uuid::Uuid;
+use futures_util::stream::{self, StreamExt};
 use tracing::{info, warn, error, instrument};
 
+// An unreadable If-Match header can't name the current version, so it never matches
+fn if_match(req: &HttpRequest) -> Option<IfMatch> {
+    req.headers()
+        .get(header::IF_MATCH)
+        .map(|value| IfMatch::parse(value.to_str().unwrap_or_default()))
+}
+
 #[instrument(
     name = "create_product_handler",
-    skip(service, payload),
+    skip(service, payload, req),
     fields(
         product_name = %payload.product.name,
         product_cost = %payload.product.cost,
@@ -15,7 +32,8 @@ use tracing::{info, warn, error, instrument};
 )]
 pub async fn create_product(
     service: web::Data<ProductService>,
-    payload: web::Json<NewCompleteProduct>,
+    payload: ValidJson<NewCompleteProduct>,
+    req: HttpRequest
 ) -> ActixResult<HttpResponse> {
     let product_data = payload.into_inner();
     
@@ -26,7 +44,7 @@ pub async fn create_product(
         "🆕 Creating new product"
     );
 
-    let result = service.create_product(product_data).to_response();
+    let result = service.create_product(product_data, actor(&req)).await.to_response();
     
     match &result {
         Ok(response) if response.status().is_success() => {
@@ -52,25 +70,208 @@ pub async fn create_product(
     result
 }
 
+#[instrument(
+    name = "create_products_bulk_handler",
+    skip(service, payload, req),
+    fields(
+        item_count = payload.len(),
+        mode = ?query.mode
+    )
+)]
+pub async fn create_products_bulk(
+    service: web::Data<ProductService>,
+    payload: web::Json<Vec<NewCompleteProduct>>,
+    query: web::Query<BulkQuery>,
+    req: HttpRequest
+) -> ActixResult<HttpResponse> {
+    let items = payload.into_inner();
+
+    info!(
+        item_count = items.len(),
+        mode = ?query.mode,
+        "🆕 Creating products in bulk"
+    );
+
+    if items.len() > MAX_BULK_PRODUCTS {
+        warn!(item_count = items.len(), "Rejecting oversized bulk request");
+        return Ok(AppError::Validation(format!(
+            "At most {} products can be created per request", MAX_BULK_PRODUCTS
+        )).error_response());
+    }
+
+    let result = service.create_products(items, query.mode, actor(&req)).await.to_response();
+
+    match &result {
+        Ok(response) if response.status().is_success() => {
+            info!(
+                status = response.status().as_u16(),
+                "Bulk product creation completed"
+            );
+        }
+        Ok(response) => {
+            warn!(
+                status = response.status().as_u16(),
+                "Bulk product creation rejected"
+            );
+        }
+        Err(e) => {
+            error!(
+                error = %e,
+                "Bulk product creation failed with server error"
+            );
+        }
+    }
+
+    result
+}
+
+#[instrument(
+    name = "import_products_handler",
+    skip(service, body, req),
+    fields(bytes = body.len(), dry_run = ?query.dry_run)
+)]
+pub async fn import_products(
+    service: web::Data<ProductService>,
+    body: web::Bytes,
+    query: web::Query<ImportQuery>,
+    req: HttpRequest
+) -> ActixResult<HttpResponse> {
+    let dry_run = query.dry_run.unwrap_or(false);
+
+    info!(
+        bytes = body.len(),
+        dry_run = dry_run,
+        "📥 Importing products from CSV"
+    );
+
+    let result = service.import_products(&body, dry_run, actor(&req)).await.to_response();
+
+    match &result {
+        Ok(response) if response.status().is_success() => {
+            info!(
+                status = response.status().as_u16(),
+                "Product import completed"
+            );
+        }
+        Ok(response) => {
+            warn!(
+                status = response.status().as_u16(),
+                "Product import rejected"
+            );
+        }
+        Err(e) => {
+            error!(
+                error = %e,
+                "Product import failed with server error"
+            );
+        }
+    }
+
+    result
+}
+
+#[instrument(
+    name = "export_products_handler",
+    skip(service, filters, req),
+    fields(format = ?query.format)
+)]
+pub async fn export_products(
+    service: web::Data<ProductService>,
+    filters: web::Query<ProductFilters>,
+    query: web::Query<ExportQuery>,
+    req: HttpRequest
+) -> ActixResult<HttpResponse> {
+    let format = query.format;
+    let mut filters = filters.into_inner();
+    filters.attributes = match parse_attribute_filters(req.query_string()) {
+        Ok(attributes) => attributes,
+        Err(message) => {
+            warn!(error = %message, "Rejecting invalid attribute filters");
+            return Ok(AppError::Validation(message).error_response());
+        }
+    };
+
+    info!(
+        format = ?format,
+        has_filters = !filters.is_empty(),
+        "📤 Exporting products"
+    );
+
+    if let Err(message) = filters.validate_export() {
+        warn!(error = %message, "Rejecting invalid export query");
+        return Ok(AppError::Validation(message).error_response());
+    }
+
+    // The first chunk is the header, or the error that stopped the export before any
+    // of it was sent, which still gets a proper error response
+    let mut chunks = service.export_products(filters, format);
+    let header = match chunks.recv().await {
+        Some(Ok(header)) => header,
+        Some(Err(e)) => {
+            error!(error = %e, "Export failed with server error");
+            return error_response(e);
+        }
+        None => {
+            error!("Export ended before sending its header");
+            return error_response(anyhow::anyhow!("Export ended before sending its header"));
+        }
+    };
+
+    let rows = stream::unfold(chunks, |mut chunks| async move {
+        let chunk = chunks.recv().await?.map(web::Bytes::from).map_err(|e| {
+            error!(error = %e, "Export stopped by a server error");
+            actix_web::error::ErrorInternalServerError(e)
+        });
+        Some((chunk, chunks))
+    });
+    // An empty chunk would end the chunked body, so an empty header is left out
+    let header = (!header.is_empty()).then(|| Ok(web::Bytes::from(header)));
+    let body = stream::iter(header).chain(rows);
+
+    info!(format = ?format, "Product export started");
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(header::ContentDisposition::attachment(format.file_name()))
+        .streaming(body))
+}
+
 #[instrument(
     name = "get_product_by_id_handler",
-    skip(service),
+    skip(service, query, settings, req),
     fields(
-        product_id = %id.as_ref()
+        product_id = %id.as_ref(),
+        include = query.include.as_deref().unwrap_or("none")
     )
 )]
 pub async fn get_product_by_id(
     service: web::Data<ProductService>,
     id: web::Path<Uuid>,
+    query: web::Query<IncludeQuery>,
+    settings: web::Data<Settings>,
+    req: HttpRequest
 ) -> ActixResult<HttpResponse> {
     let product_id = id.into_inner();
     
     info!(
         product_id = %product_id,
+        include = query.include.as_deref().unwrap_or("none"),
         "Fetching product by ID"
     );
 
-    let result = service.get_product_by_id(product_id).to_response();
+    let with_variants = match includes_variants(query.include.as_deref()) {
+        Ok(with_variants) => with_variants,
+        Err(message) => {
+            warn!(error = %message, "Rejecting invalid product query");
+            return Ok(AppError::Validation(message).error_response());
+        }
+    };
+
+    let result = if with_variants {
+        service.get_complete_product_by_id(product_id).await.to_cached_response(&req, &settings.product_cache_control)
+    } else {
+        service.get_product_by_id(product_id).await.to_cached_response(&req, &settings.product_cache_control)
+    };
     
     match &result {
         Ok(response) if response.status().is_success() => {
@@ -80,6 +281,13 @@ pub async fn get_product_by_id(
                 "Product retrieved successfully"
             );
         }
+        Ok(response) if response.status() == 304 => {
+            info!(
+                product_id = %product_id,
+                status = response.status().as_u16(),
+                "Product not modified"
+            );
+        }
         Ok(response) if response.status() == 404 => {
             info!(
                 product_id = %product_id,
@@ -108,28 +316,51 @@ pub async fn get_product_by_id(
 
 #[instrument(
     name = "get_products_handler",
-    skip(service, filters),
+    skip(service, filters, settings, req),
     fields(
         has_filters = !filters.is_empty(),
         filter_name = filters.name.as_deref().unwrap_or("none"),
-        filter_active = filters.is_active
+        filter_active = filters.is_active,
+        search = filters.q.as_deref().unwrap_or("none")
     )
 )]
 pub async fn get_products(
     service: web::Data<ProductService>,
-    filters: web::Query<ProductFilters>
+    filters: web::Query<ProductFilters>,
+    settings: web::Data<Settings>,
+    req: HttpRequest
 ) -> ActixResult<HttpResponse> {
+    let mut filters = filters.into_inner();
+    filters.attributes = match parse_attribute_filters(req.query_string()) {
+        Ok(attributes) => attributes,
+        Err(message) => {
+            warn!(error = %message, "Rejecting invalid attribute filters");
+            return Ok(AppError::Validation(message).error_response());
+        }
+    };
     let has_filters = !filters.is_empty();
     
     info!(
         has_filters = has_filters,
         filter_name = filters.name.as_deref().unwrap_or("none"),
         filter_active = filters.is_active,
+        search = filters.q.as_deref().unwrap_or("none"),
+        attribute_filters = filters.attributes.len(),
         "🔍 Fetching products with filters"
     );
 
-    let filters = if filters.is_empty() { None } else { Some(filters.into_inner()) };
-    let result = service.get_products(filters).to_response();
+    if let Err(message) = filters.validate() {
+        warn!(error = %message, "Rejecting invalid product query");
+        return Ok(AppError::Validation(message).error_response());
+    }
+
+    let with_variants = includes_variants(filters.include.as_deref()).unwrap_or(false);
+    let filters = if filters.is_empty() { None } else { Some(filters) };
+    let result = if with_variants {
+        service.get_complete_products(filters).await.to_cached_response(&req, &settings.product_cache_control)
+    } else {
+        service.get_products(filters).await.to_cached_response(&req, &settings.product_cache_control)
+    };
     
     match &result {
         Ok(response) if response.status().is_success() => {
@@ -138,6 +369,12 @@ pub async fn get_products(
                 "📦 Products retrieved successfully"
             );
         }
+        Ok(response) if response.status() == 304 => {
+            info!(
+                status = response.status().as_u16(),
+                "Products not modified"
+            );
+        }
         Ok(response) => {
             warn!(
                 status = response.status().as_u16(),
@@ -156,60 +393,47 @@ pub async fn get_products(
 }
 
 #[instrument(
-    name = "update_product_handler",
-    skip(service, updates),
+    name = "suggest_products_handler",
+    skip(service, query),
     fields(
-        product_id = %id.as_ref(),
-        update_name = updates.name.is_some(),
-        update_cost = updates.cost.is_some(),
-        update_active = updates.active.is_some()
+        prefix = %query.prefix,
+        limit = query.limit
     )
 )]
-pub async fn update_product(
+pub async fn suggest_products(
     service: web::Data<ProductService>,
-    id: web::Path<Uuid>,
-    updates: web::Json<ProductUpdates> 
+    query: web::Query<SuggestQuery>
 ) -> ActixResult<HttpResponse> {
-    let product_id = id.into_inner();
-    let update_data = updates.into_inner();
-    
     info!(
-        product_id = %product_id,
-        update_name = update_data.name.is_some(),
-        update_cost = update_data.cost.is_some(),
-        update_active = update_data.active.is_some(),
-        "Updating product"
+        prefix = %query.prefix,
+        limit = query.limit,
+        "🔎 Fetching product suggestions"
     );
 
-    let result = service.update_product(product_id, update_data).to_response();
+    if let Err(message) = query.validate() {
+        warn!(error = %message, "Rejecting invalid suggestion query");
+        return Ok(AppError::Validation(message).error_response());
+    }
+
+    let result = service.suggest_products(query.into_inner()).await.to_response();
     
     match &result {
         Ok(response) if response.status().is_success() => {
             info!(
-                product_id = %product_id,
-                status = response.status().as_u16(),
-                "Product updated successfully"
-            );
-        }
-        Ok(response) if response.status() == 404 => {
-            info!(
-                product_id = %product_id,
                 status = response.status().as_u16(),
-                "Product not found for update"
+                "Product suggestions retrieved successfully"
             );
         }
         Ok(response) => {
             warn!(
-                product_id = %product_id,
                 status = response.status().as_u16(),
-                "Product update failed"
+                "Product suggestions retrieval failed"
             );
         }
         Err(e) => {
             error!(
-                product_id = %product_id,
                 error = %e,
-                "Product update failed with server error"
+                "Product suggestions retrieval failed with server error"
             );
         }
     }
@@ -217,16 +441,115 @@ pub async fn update_product(
     result
 }
 
+// Logs the outcome of a write to a single product
+fn log_product_write(result: &ActixResult<HttpResponse>, product_id: Uuid, action: &str) {
+    match result {
+        Ok(response) if response.status().is_success() => {
+            info!(product_id = %product_id, status = response.status().as_u16(), "Product {} succeeded", action);
+        }
+        Ok(response) if response.status() == 404 => {
+            info!(product_id = %product_id, status = response.status().as_u16(), "Product not found for {}", action);
+        }
+        Ok(response) if response.status() == 412 => {
+            info!(product_id = %product_id, status = response.status().as_u16(), "Product {} rejected, version is stale", action);
+        }
+        Ok(response) => {
+            warn!(product_id = %product_id, status = response.status().as_u16(), "Product {} failed", action);
+        }
+        Err(e) => {
+            error!(product_id = %product_id, error = %e, "Product {} failed with server error", action);
+        }
+    }
+}
+
+#[instrument(
+    name = "replace_product_handler",
+    skip(service, document, req),
+    fields(
+        product_id = %id.as_ref(),
+        variant_count = document.variants.len()
+    )
+)]
+pub async fn replace_product(
+    service: web::Data<ProductService>,
+    id: web::Path<Uuid>,
+    document: ValidJson<ProductDocument>,
+    req: HttpRequest
+) -> ActixResult<HttpResponse> {
+    let product_id = id.into_inner();
+    let document = document.into_inner();
+
+    info!(
+        product_id = %product_id,
+        variant_count = document.variants.len(),
+        "Replacing product"
+    );
+
+    let result = service.replace_product(product_id, document, if_match(&req), actor(&req)).await.to_response();
+    log_product_write(&result, product_id, "replacement");
+    result
+}
+
+#[instrument(
+    name = "patch_product_handler",
+    skip(service, body, req),
+    fields(
+        product_id = %id.as_ref(),
+        bytes = body.len()
+    )
+)]
+pub async fn patch_product(
+    service: web::Data<ProductService>,
+    id: web::Path<Uuid>,
+    body: web::Bytes,
+    req: HttpRequest
+) -> ActixResult<HttpResponse> {
+    let product_id = id.into_inner();
+
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let Some(format) = PatchFormat::from_content_type(content_type) else {
+        warn!(content_type = %content_type, "Rejecting patch with unsupported content type");
+        let mut response = Problem::from_status(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            format!("A patch must be sent as {} or {}", MERGE_PATCH_CONTENT_TYPE, JSON_PATCH_CONTENT_TYPE)
+        ).into_response();
+        response.headers_mut().insert(
+            header::HeaderName::from_static("accept-patch"),
+            header::HeaderValue::from_str(&format!("{}, {}", MERGE_PATCH_CONTENT_TYPE, JSON_PATCH_CONTENT_TYPE))
+                .expect("content types are valid header values")
+        );
+        return Ok(response);
+    };
+    let patch = match ProductPatch::parse(format, &body) {
+        Ok(patch) => patch,
+        Err(message) => {
+            warn!(error = %message, "Rejecting malformed patch");
+            return Ok(AppError::Validation(message).error_response());
+        }
+    };
+
+    info!(product_id = %product_id, format = ?format, "Patching product");
+
+    let result = service.patch_product(product_id, patch, if_match(&req), actor(&req)).await.to_response();
+    log_product_write(&result, product_id, "patch");
+    result
+}
+
 #[instrument(
     name = "delete_product_handler",
-    skip(service),
+    skip(service, req),
     fields(
         product_id = %id.as_ref()
     )
 )]
 pub async fn delete_product(
     service: web::Data<ProductService>,
-    id: web::Path<Uuid>
+    id: web::Path<Uuid>,
+    req: HttpRequest
 ) -> ActixResult<HttpResponse> {
     let product_id = id.into_inner();
     
@@ -235,7 +558,7 @@ pub async fn delete_product(
         "Deleting product"
     );
 
-    match service.delete_product(product_id) {
+    match service.delete_product(product_id, if_match(&req), actor(&req)).await {
         Ok(true) => {
             info!(
                 product_id = %product_id,
@@ -248,31 +571,363 @@ pub async fn delete_product(
                 product_id = %product_id,
                 "Product not found for deletion"
             );
-            Ok(HttpResponse::NotFound().json(ErrorResponse {
-                error: "Product not found".to_string()
-            }))
+            Ok(AppError::NotFound("Product not found".to_string()).error_response())
         },
+        Err(err) if matches!(err.downcast_ref::<AppError>(), Some(AppError::PreconditionFailed)) => {
+            info!(
+                product_id = %product_id,
+                "Product deletion rejected, version is stale"
+            );
+            error_response(err)
+        }
         Err(err) => {
             error!(
                 product_id = %product_id,
                 error = %err,
                 "Product deletion failed with server error"
             );
-            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: err.to_string()
-            }))
+            error_response(err)
         }
     }
 }
 
+#[instrument(
+    name = "restore_product_handler",
+    skip(service, req),
+    fields(
+        product_id = %id.as_ref()
+    )
+)]
+pub async fn restore_product(
+    service: web::Data<ProductService>,
+    id: web::Path<Uuid>,
+    req: HttpRequest
+) -> ActixResult<HttpResponse> {
+    let product_id = id.into_inner();
+
+    info!(
+        product_id = %product_id,
+        "Restoring product"
+    );
+
+    let result = service.restore_product(product_id, actor(&req)).await.to_response();
+
+    match &result {
+        Ok(response) if response.status().is_success() => {
+            info!(
+                product_id = %product_id,
+                "Product restored successfully"
+            );
+        }
+        Ok(response) => {
+            info!(
+                product_id = %product_id,
+                status = response.status().as_u16(),
+                "Product not found for restore"
+            );
+        }
+        Err(e) => {
+            error!(
+                product_id = %product_id,
+                error = %e,
+                "Product restore failed with server error"
+            );
+        }
+    }
+
+    result
+}
+
+#[instrument(
+    name = "get_product_history_handler",
+    skip(service),
+    fields(
+        product_id = %id.as_ref()
+    )
+)]
+pub async fn get_product_history(
+    service: web::Data<ProductService>,
+    id: web::Path<Uuid>
+) -> ActixResult<HttpResponse> {
+    let product_id = id.into_inner();
+
+    info!(
+        product_id = %product_id,
+        "Fetching product history"
+    );
+
+    let result = service.product_history(product_id).await.to_response();
+
+    match &result {
+        Ok(response) if response.status().is_success() => {
+            info!(
+                product_id = %product_id,
+                "Product history retrieved successfully"
+            );
+        }
+        Ok(response) => {
+            info!(
+                product_id = %product_id,
+                status = response.status().as_u16(),
+                "Product history retrieval failed"
+            );
+        }
+        Err(e) => {
+            error!(
+                product_id = %product_id,
+                error = %e,
+                "Product history retrieval failed with server error"
+            );
+        }
+    }
+
+    result
+}
+
+#[instrument(
+    name = "revert_product_handler",
+    skip(service, req),
+    fields(
+        product_id = %path.0,
+        revision = path.1
+    )
+)]
+pub async fn revert_product(
+    service: web::Data<ProductService>,
+    path: web::Path<(Uuid, i32)>,
+    req: HttpRequest
+) -> ActixResult<HttpResponse> {
+    let (product_id, revision) = path.into_inner();
+
+    info!(
+        product_id = %product_id,
+        revision = revision,
+        "Reverting product"
+    );
+
+    let result = service.revert_product(product_id, revision, if_match(&req), actor(&req)).await.to_response();
+
+    match &result {
+        Ok(response) if response.status().is_success() => {
+            info!(
+                product_id = %product_id,
+                revision = revision,
+                "Product reverted successfully"
+            );
+        }
+        Ok(response) => {
+            warn!(
+                product_id = %product_id,
+                status = response.status().as_u16(),
+                "Product revert failed"
+            );
+        }
+        Err(e) => {
+            error!(
+                product_id = %product_id,
+                error = %e,
+                "Product revert failed with server error"
+            );
+        }
+    }
+
+    result
+}
+
+#[instrument(name = "purge_deleted_products_handler", skip(service, settings))]
+pub async fn purge_deleted_products(
+    service: web::Data<ProductService>,
+    settings: web::Data<Settings>
+) -> ActixResult<HttpResponse> {
+    let retention_days = settings.deleted_product_retention_days;
+
+    info!(
+        retention_days = retention_days,
+        "Purging deleted products"
+    );
+
+    let result = service
+        .purge_deleted_products(chrono::Duration::days(retention_days))
+        .await
+        .to_response();
+
+    match &result {
+        Ok(response) if response.status().is_success() => {
+            info!(
+                status = response.status().as_u16(),
+                "Deleted products purged"
+            );
+        }
+        Ok(response) => {
+            warn!(
+                status = response.status().as_u16(),
+                "Purging deleted products failed"
+            );
+        }
+        Err(e) => {
+            error!(
+                error = %e,
+                "Purging deleted products failed with server error"
+            );
+        }
+    }
+
+    result
+}
+
+// Reads the filters picking the products of a bulk change, including `attr.*` ones
+fn bulk_selector(filters: ProductFilters, req: &HttpRequest) -> Result<ProductFilters, String> {
+    let mut filters = filters;
+    filters.attributes = parse_attribute_filters(req.query_string())?;
+    filters.validate_selector()?;
+    Ok(filters)
+}
+
+#[instrument(
+    name = "bulk_update_products_handler",
+    skip(service, filters, payload, settings, req),
+    fields(dry_run = ?query.dry_run)
+)]
+pub async fn bulk_update_products(
+    service: web::Data<ProductService>,
+    filters: web::Query<ProductFilters>,
+    query: web::Query<BulkChangeQuery>,
+    payload: ValidJson<BulkUpdate>,
+    settings: web::Data<Settings>,
+    req: HttpRequest
+) -> ActixResult<HttpResponse> {
+    let update = payload.into_inner();
+    let dry_run = query.dry_run.unwrap_or(false);
+
+    info!(
+        dry_run = dry_run,
+        adjust_cost = update.cost_adjustment.is_some(),
+        "Updating products in bulk"
+    );
+
+    let filters = match bulk_selector(filters.into_inner(), &req) {
+        Ok(filters) => filters,
+        Err(message) => {
+            warn!(error = %message, "Rejecting invalid bulk selector");
+            return Ok(AppError::Validation(message).error_response());
+        }
+    };
+    if let Err(message) = update.validate(&filters) {
+        warn!(error = %message, "Rejecting invalid bulk update");
+        return Ok(AppError::Validation(message).error_response());
+    }
+
+    let result = service
+        .bulk_update_products(filters, update, dry_run, settings.bulk_change_limit, actor(&req))
+        .await
+        .to_response();
+
+    match &result {
+        Ok(response) if response.status().is_success() => {
+            info!(
+                status = response.status().as_u16(),
+                "Bulk update completed"
+            );
+        }
+        Ok(response) => {
+            warn!(
+                status = response.status().as_u16(),
+                "Bulk update rejected"
+            );
+        }
+        Err(e) => {
+            error!(
+                error = %e,
+                "Bulk update failed with server error"
+            );
+        }
+    }
+
+    result
+}
+
+#[instrument(
+    name = "bulk_delete_products_handler",
+    skip(service, filters, settings, req),
+    fields(dry_run = ?query.dry_run)
+)]
+pub async fn bulk_delete_products(
+    service: web::Data<ProductService>,
+    filters: web::Query<ProductFilters>,
+    query: web::Query<BulkChangeQuery>,
+    settings: web::Data<Settings>,
+    req: HttpRequest
+) -> ActixResult<HttpResponse> {
+    let dry_run = query.dry_run.unwrap_or(false);
+
+    info!(dry_run = dry_run, "Deleting products in bulk");
+
+    let filters = match bulk_selector(filters.into_inner(), &req) {
+        Ok(filters) => filters,
+        Err(message) => {
+            warn!(error = %message, "Rejecting invalid bulk selector");
+            return Ok(AppError::Validation(message).error_response());
+        }
+    };
+
+    let result = service
+        .bulk_delete_products(filters, dry_run, settings.bulk_change_limit, actor(&req))
+        .await
+        .to_response();
+
+    match &result {
+        Ok(response) if response.status().is_success() => {
+            info!(
+                status = response.status().as_u16(),
+                "Bulk delete completed"
+            );
+        }
+        Ok(response) => {
+            warn!(
+                status = response.status().as_u16(),
+                "Bulk delete rejected"
+            );
+        }
+        Err(e) => {
+            error!(
+                error = %e,
+                "Bulk delete failed with server error"
+            );
+        }
+    }
+
+    result
+}
+
 // Orchestrate the posts controller
 pub fn create_product_controller(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/products")
-        .route("", web::post().to(create_product))
-        .route("", web::get().to(get_products))
-        .route("/{id}", web::put().to(update_product))
-        .route("/{id}", web::delete().to(delete_product))
-        .route("/{id}", web::get().to(get_product_by_id))
+        .service(resource("", vec![
+            (Method::POST, web::to(create_product)),
+            (Method::GET, web::to(get_products)),
+            (Method::PATCH, web::to(bulk_update_products)),
+            (Method::DELETE, web::to(bulk_delete_products)),
+        ]))
+        .service(resource("/suggest", vec![(Method::GET, web::to(suggest_products))]))
+        .service(resource("/bulk", vec![(Method::POST, web::to(create_products_bulk))]))
+        .service(resource("/export", vec![(Method::GET, web::to(export_products))]))
+        .service(
+            resource("/import", vec![(Method::POST, web::to(import_products))])
+                .app_data(web::PayloadConfig::new(MAX_IMPORT_BYTES))
+        )
+        .service(resource("/{id}", vec![
+            (Method::PUT, web::to(replace_product)),
+            (Method::PATCH, web::to(patch_product)),
+            (Method::DELETE, web::to(delete_product)),
+            (Method::GET, web::to(get_product_by_id)),
+        ]))
+        .service(resource("/{id}/restore", vec![(Method::POST, web::to(restore_product))]))
+        .service(resource("/{id}/history", vec![(Method::GET, web::to(get_product_history))]))
+        .service(resource("/{id}/revert/{revision}", vec![(Method::POST, web::to(revert_product))]))
     );
-}
\ No newline at end of file
+    cfg.service(
+        web::scope("/admin/products")
+        .service(resource("/purge", vec![(Method::POST, web::to(purge_deleted_products))]))
+    );
+}
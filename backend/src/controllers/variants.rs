@@ -0,0 +1,218 @@
+use crate::prelude::*;
+use crate::controllers::{actor, resource, ValidJson};
+use actix_web::HttpRequest;
+use actix_web::http::Method;
+use crate::models::{NewProductVariantValue, NewVariant, NewVariantValue, ProductVariantUpdates, VariantUpdate};
+use crate::services::VariantService;
+use uuid::Uuid;
+use tracing::{info, warn, error, instrument};
+
+// Logs the outcome of a variant handler the same way for every route
+fn log_outcome(result: &ActixResult<HttpResponse>, action: &str) {
+    match result {
+        Ok(response) if response.status().is_success() => {
+            info!(status = response.status().as_u16(), "{} succeeded", action);
+        }
+        Ok(response) if response.status() == 404 => {
+            info!(status = response.status().as_u16(), "{} target not found", action);
+        }
+        Ok(response) => {
+            warn!(status = response.status().as_u16(), "{} failed", action);
+        }
+        Err(e) => {
+            error!(error = %e, "{} failed with server error", action);
+        }
+    }
+}
+
+#[instrument(name = "list_product_variants_handler", skip(service), fields(product_id = %product_id.as_ref()))]
+pub async fn list_product_variants(
+    service: web::Data<VariantService>,
+    product_id: web::Path<Uuid>,
+) -> ActixResult<HttpResponse> {
+    let product_id = product_id.into_inner();
+    info!(product_id = %product_id, "Listing product variants");
+
+    let result = service.list_product_variants(product_id).await.to_response();
+    log_outcome(&result, "Listing product variants");
+    result
+}
+
+#[instrument(
+    name = "add_product_variant_handler",
+    skip(service, payload, req),
+    fields(product_id = %product_id.as_ref(), variant = %payload.variant)
+)]
+pub async fn add_product_variant(
+    service: web::Data<VariantService>,
+    product_id: web::Path<Uuid>,
+    payload: ValidJson<NewVariantValue>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let product_id = product_id.into_inner();
+    info!(product_id = %product_id, values_count = payload.values.len(), "🆕 Adding variant to product");
+
+    let result = service.add_product_variant(product_id, payload.into_inner(), actor(&req)).await.to_response();
+    log_outcome(&result, "Adding product variant");
+    result
+}
+
+#[instrument(name = "remove_product_variant_handler", skip(service, path, req))]
+pub async fn remove_product_variant(
+    service: web::Data<VariantService>,
+    path: web::Path<(Uuid, Uuid)>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let (product_id, variant_id) = path.into_inner();
+    info!(product_id = %product_id, variant_id = %variant_id, "Removing variant from product");
+
+    let result = service.remove_product_variant(product_id, variant_id, actor(&req)).await.to_response();
+    log_outcome(&result, "Removing product variant");
+    result
+}
+
+#[instrument(name = "add_variant_value_handler", skip(service, path, payload, req))]
+pub async fn add_variant_value(
+    service: web::Data<VariantService>,
+    path: web::Path<(Uuid, Uuid)>,
+    payload: ValidJson<NewProductVariantValue>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let (product_id, variant_id) = path.into_inner();
+    info!(product_id = %product_id, variant_id = %variant_id, "🆕 Adding variant value");
+
+    let result = service
+        .add_variant_value(product_id, variant_id, payload.into_inner(), actor(&req))
+        .await
+        .to_response();
+    log_outcome(&result, "Adding variant value");
+    result
+}
+
+#[instrument(name = "update_variant_value_handler", skip(service, path, updates, req))]
+pub async fn update_variant_value(
+    service: web::Data<VariantService>,
+    path: web::Path<(Uuid, Uuid, Uuid)>,
+    updates: ValidJson<ProductVariantUpdates>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let (product_id, variant_id, value_id) = path.into_inner();
+    info!(product_id = %product_id, variant_id = %variant_id, value_id = %value_id, "Updating variant value");
+
+    let result = service
+        .update_variant_value(product_id, variant_id, value_id, updates.into_inner(), actor(&req))
+        .await
+        .to_response();
+    log_outcome(&result, "Updating variant value");
+    result
+}
+
+#[instrument(name = "remove_variant_value_handler", skip(service, path, req))]
+pub async fn remove_variant_value(
+    service: web::Data<VariantService>,
+    path: web::Path<(Uuid, Uuid, Uuid)>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let (product_id, variant_id, value_id) = path.into_inner();
+    info!(product_id = %product_id, variant_id = %variant_id, value_id = %value_id, "Removing variant value");
+
+    let result = service
+        .remove_variant_value(product_id, variant_id, value_id, actor(&req))
+        .await
+        .to_response();
+    log_outcome(&result, "Removing variant value");
+    result
+}
+
+#[instrument(name = "list_variants_handler", skip(service))]
+pub async fn list_variants(service: web::Data<VariantService>) -> ActixResult<HttpResponse> {
+    info!("Listing variant definitions");
+
+    let result = service.list_variants().await.to_response();
+    log_outcome(&result, "Listing variants");
+    result
+}
+
+#[instrument(name = "create_variant_handler", skip(service, payload), fields(variant_name = %payload.name))]
+pub async fn create_variant(
+    service: web::Data<VariantService>,
+    payload: ValidJson<NewVariant>,
+) -> ActixResult<HttpResponse> {
+    info!("🆕 Creating variant definition");
+
+    let result = service.create_variant(payload.into_inner()).await.to_response();
+    log_outcome(&result, "Creating variant");
+    result
+}
+
+#[instrument(name = "get_variant_handler", skip(service), fields(variant_id = %id.as_ref()))]
+pub async fn get_variant(
+    service: web::Data<VariantService>,
+    id: web::Path<Uuid>,
+) -> ActixResult<HttpResponse> {
+    let variant_id = id.into_inner();
+    info!(variant_id = %variant_id, "Fetching variant by ID");
+
+    let result = service.get_variant(variant_id).await.to_response();
+    log_outcome(&result, "Fetching variant");
+    result
+}
+
+#[instrument(name = "update_variant_handler", skip(service, updates, req), fields(variant_id = %id.as_ref()))]
+pub async fn update_variant(
+    service: web::Data<VariantService>,
+    id: web::Path<Uuid>,
+    updates: ValidJson<VariantUpdate>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let variant_id = id.into_inner();
+    info!(variant_id = %variant_id, "Updating variant");
+
+    let result = service.update_variant(variant_id, updates.into_inner(), actor(&req)).await.to_response();
+    log_outcome(&result, "Updating variant");
+    result
+}
+
+#[instrument(name = "delete_variant_handler", skip(service, req), fields(variant_id = %id.as_ref()))]
+pub async fn delete_variant(
+    service: web::Data<VariantService>,
+    id: web::Path<Uuid>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let variant_id = id.into_inner();
+    info!(variant_id = %variant_id, "Deleting variant");
+
+    let result = service.delete_variant(variant_id, actor(&req)).await.to_response();
+    log_outcome(&result, "Deleting variant");
+    result
+}
+
+// Orchestrate the variants controller. Must be configured before the products
+// controller, otherwise the `/products` scope swallows the nested variant routes.
+pub fn create_variant_controller(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/products/{product_id}/variants")
+        .service(resource("", vec![
+            (Method::GET, web::to(list_product_variants)),
+            (Method::POST, web::to(add_product_variant)),
+        ]))
+        .service(resource("/{variant_id}", vec![(Method::DELETE, web::to(remove_product_variant))]))
+        .service(resource("/{variant_id}/values", vec![(Method::POST, web::to(add_variant_value))]))
+        .service(resource("/{variant_id}/values/{value_id}", vec![
+            (Method::PUT, web::to(update_variant_value)),
+            (Method::DELETE, web::to(remove_variant_value)),
+        ]))
+    )
+    .service(
+        web::scope("/variants")
+        .service(resource("", vec![
+            (Method::GET, web::to(list_variants)),
+            (Method::POST, web::to(create_variant)),
+        ]))
+        .service(resource("/{id}", vec![
+            (Method::GET, web::to(get_variant)),
+            (Method::PUT, web::to(update_variant)),
+            (Method::DELETE, web::to(delete_variant)),
+        ]))
+    );
+}
@@ -11,11 +11,11 @@ mod middleware;
 use actix_web::middleware::from_fn;
 use actix_web::{App, web, HttpServer};
 use tracing::{info, error};
-use crate::config::{create_pool, get_settings};
-use crate::controllers::create_product_controller;
-use crate::services::ProductService;
+use crate::config::{create_pool, get_settings, Database};
+use crate::controllers::{create_error_controller, create_product_controller, create_variant_controller};
+use crate::services::{IdempotencyService, ProductService, VariantService};
 use crate::core::init_tracing;
-use crate::middleware::{cors_middleware, request_logging};
+use crate::middleware::{cors_middleware, idempotency, problem_details, request_logging};
 
 
 #[actix_web::main]
@@ -32,26 +32,45 @@ async fn main() -> std::io::Result<()> {
         }
         Err(e) => {
             error!("❌ Failed to load configuration: {}", e);
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, e));
+            return Err(std::io::Error::other(e));
         }
     };
 
-    let pool = create_pool(&settings);
+    let db = Database::new(create_pool(&settings));
+    let settings = web::Data::new(settings);
     info!("🗄️  Database connection pool created");
 
-    let products_service = web::Data::new(ProductService::new(pool.clone()));
+    let products_service = web::Data::new(ProductService::new(db.clone()));
     info!("🛍️  Product service initialized");
 
+    let variants_service = web::Data::new(VariantService::new(db.clone()));
+    info!("🏷️  Variant service initialized");
+
+    let idempotency_service = web::Data::new(IdempotencyService::new(
+        db,
+        chrono::Duration::hours(settings.idempotency_key_ttl_hours),
+        chrono::Duration::seconds(settings.idempotency_lease_secs)
+    ));
+    info!("🔁 Idempotency service initialized");
+
     let bind_address = "0.0.0.0:8000";
     info!("🌐 Starting HTTP server on {}", bind_address);
 
     HttpServer::new(move || {
         App::new()
+            .wrap(from_fn(problem_details))
+            .wrap(from_fn(idempotency))
             .wrap(tracing_actix_web::TracingLogger::default())
             .wrap(from_fn(request_logging))
             .wrap(cors_middleware())
+            .app_data(settings.clone())
             .app_data(products_service.clone())
+            .app_data(variants_service.clone())
+            .app_data(idempotency_service.clone())
+            // Nested product variant routes have to be registered before the /products scope
+            .configure(create_variant_controller)
             .configure(create_product_controller)
+            .configure(create_error_controller)
     })
     .bind(bind_address)?
     .run()
@@ -0,0 +1,194 @@
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    error::PayloadError,
+    http::{Method, StatusCode},
+    ResponseError,
+    middleware::Next,
+    web, Error, HttpResponse,
+};
+use sha2::{Digest, Sha256};
+use crate::middleware::complete_problem;
+use crate::models::{IdempotencyClaim, StoredResponse, MAX_IDEMPOTENCY_KEY_LENGTH, MAX_IMPORT_BYTES};
+use crate::services::IdempotencyService;
+use crate::traits::responses::AppError;
+use tracing::{info, warn, error};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+// Set on responses served from the store instead of the handler
+pub const IDEMPOTENT_REPLAY_HEADER: &str = "Idempotent-Replayed";
+// The largest body any route accepts, the handler's extractor still applies its own limit
+const MAX_HASHED_BODY_BYTES: usize = MAX_IMPORT_BYTES;
+
+// A claimed key the request hasn't stored a response for yet. Should the request
+// never get there, e.g. because the client went away or the request timed out and
+// its future was dropped, the key is released so a retry doesn't wait for the lease.
+struct Claim {
+    service: web::Data<IdempotencyService>,
+    key: Option<String>
+}
+
+impl Claim {
+    async fn release(mut self) {
+        if let Some(key) = self.key.take() {
+            let _ = self.service.release(&key).await;
+        }
+    }
+
+    fn keep(mut self) {
+        self.key = None;
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+        warn!(idempotency_key = %key, "Request dropped before storing its response, releasing key");
+        let service = self.service.clone();
+        // Without a runtime to release it on, the key is taken over once its lease ends
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                let _ = service.release(&key).await;
+            });
+        }
+    }
+}
+
+fn request_hash(req: &ServiceRequest, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(req.method().as_str());
+    hasher.update(b" ");
+    hasher.update(req.uri().to_string());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Rejections are made outside the problem middleware, so they complete their problem here
+fn rejection(req: ServiceRequest, err: AppError) -> ServiceResponse<BoxBody> {
+    complete_problem(req.into_response(err.error_response()))
+}
+
+fn replay(req: ServiceRequest, stored: StoredResponse) -> ServiceResponse<BoxBody> {
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let mut response = HttpResponse::build(status);
+    for (name, value) in &stored.headers {
+        response.append_header((name.as_str(), value.as_str()));
+    }
+    response.insert_header((IDEMPOTENT_REPLAY_HEADER, "true"));
+    req.into_response(response.body(stored.body))
+}
+
+// Makes POST requests carrying an Idempotency-Key safe to retry: the first response
+// is stored and replayed for later requests with the same key and body.
+pub async fn idempotency(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let key = req
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .map(|value| value.to_str().unwrap_or_default().trim().to_string());
+    let service = req.app_data::<web::Data<IdempotencyService>>().cloned();
+
+    let (Some(key), Some(service)) = (key, service) else {
+        return next.call(req).await.map(ServiceResponse::map_into_boxed_body);
+    };
+    if req.method() != Method::POST {
+        return next.call(req).await.map(ServiceResponse::map_into_boxed_body);
+    }
+
+    if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+        return Ok(rejection(req, AppError::Validation(format!(
+            "{} must be between 1 and {} characters", IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LENGTH
+        ))));
+    }
+
+    // The body is read here to hash it, then handed back for the handler to parse
+    let payload = match req.extract::<web::Payload>().await {
+        Ok(payload) => payload,
+        Err(e) => return Ok(complete_problem(req.error_response(e)))
+    };
+    let body = match payload.to_bytes_limited(MAX_HASHED_BODY_BYTES).await {
+        Ok(Ok(body)) => body,
+        Ok(Err(e)) => return Ok(complete_problem(req.error_response(e))),
+        Err(_) => return Ok(complete_problem(req.error_response(PayloadError::Overflow)))
+    };
+    let hash = request_hash(&req, &body);
+    req.set_payload(Payload::from(body));
+
+    match service.claim(&key, &hash).await {
+        Ok(IdempotencyClaim::Started) => {}
+        Ok(IdempotencyClaim::Replay(stored)) => {
+            info!(idempotency_key = %key, status = stored.status, "Replaying idempotent response");
+            return Ok(replay(req, stored));
+        }
+        Ok(IdempotencyClaim::InProgress) => {
+            return Ok(rejection(req, AppError::Conflict(format!(
+                "A request with this {} is still being processed", IDEMPOTENCY_KEY_HEADER
+            ))));
+        }
+        Ok(IdempotencyClaim::Mismatch) => {
+            return Ok(rejection(req, AppError::Unprocessable(format!(
+                "{} was already used for a different request", IDEMPOTENCY_KEY_HEADER
+            ))));
+        }
+        Err(e) => {
+            error!(idempotency_key = %key, error = %e, "Idempotency check failed");
+            return Ok(rejection(req, AppError::from(e)));
+        }
+    }
+
+    let claim = Claim { service: service.clone(), key: Some(key.clone()) };
+    let res = match next.call(req).await {
+        Ok(res) => res,
+        Err(e) => {
+            claim.release().await;
+            return Err(e);
+        }
+    };
+
+    // Server errors aren't stored, so a retry gets another chance
+    if res.status().is_server_error() {
+        warn!(idempotency_key = %key, status = res.status().as_u16(), "Not storing failed idempotent request");
+        claim.release().await;
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (res, response_body) = res.into_parts();
+    let bytes = match body::to_bytes(response_body).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            claim.release().await;
+            return Ok(rejection(
+                ServiceRequest::from_request(req),
+                AppError::Internal(anyhow::anyhow!("Reading the response body failed"))
+            ));
+        }
+    };
+
+    let stored = StoredResponse {
+        status: res.status().as_u16(),
+        headers: res
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body: bytes.to_vec()
+    };
+    if service.complete(&key, &stored).await.is_err() {
+        // The client still gets its response, a retry just won't be deduplicated
+        claim.release().await;
+    } else {
+        claim.keep();
+    }
+
+    Ok(ServiceResponse::new(req, res.set_body(BoxBody::new(bytes))))
+}
@@ -1,17 +1,25 @@
 use actix_web::{
     body::MessageBody,
     dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
     middleware::Next,
-    Error,
+    Error, HttpMessage,
 };
 use tracing::info;
 
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// Id of the request being handled, shared with the inner middleware through the request extensions
+#[derive(Debug, Clone, Copy)]
+pub struct RequestId(pub uuid::Uuid);
+
 pub async fn request_logging(
     req: ServiceRequest,
     next: Next<impl MessageBody>,
 ) -> Result<ServiceResponse<impl MessageBody>, Error> {
     let start_time = std::time::Instant::now();
     let request_id = uuid::Uuid::new_v4();
+    req.extensions_mut().insert(RequestId(request_id));
     let method = req.method().to_string();
     let path = req.uri().path().to_string();
     let remote_addr = req
@@ -34,7 +42,10 @@ pub async fn request_logging(
         "🔥 Request started"
     );
 
-    let res = next.call(req).await?;
+    let mut res = next.call(req).await?;
+    if let Ok(value) = HeaderValue::from_str(&request_id.to_string()) {
+        res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
     let duration = start_time.elapsed();
 
     info!(
@@ -1,4 +1,8 @@
+pub mod idempotency;
 pub mod logging;
 pub mod cors;
+pub mod problems;
+pub use idempotency::*;
 pub use logging::*;
-pub use cors::*;
\ No newline at end of file
+pub use cors::*;
+pub use problems::*;
\ No newline at end of file
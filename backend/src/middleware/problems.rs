@@ -0,0 +1,54 @@
+use actix_web::{
+    body::{BodySize, BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    Error, HttpMessage,
+};
+use crate::middleware::RequestId;
+use crate::traits::responses::Problem;
+
+// Error responses actix builds itself carry the error that caused them, or no body at all
+fn untyped_problem(res: &ServiceResponse<BoxBody>) -> Option<Problem> {
+    let status = res.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return None;
+    }
+    match res.response().error() {
+        Some(_) if status.is_server_error() => Some(Problem::from_status(status, "Internal server error")),
+        Some(err) => Some(Problem::from_status(status, err.to_string())),
+        None if matches!(res.response().body().size(), BodySize::None | BodySize::Sized(0)) => {
+            Some(Problem::from_status(status, status.canonical_reason().unwrap_or("Error")))
+        }
+        None => None
+    }
+}
+
+// Adds the path and request id to a problem response, turning actix's own error
+// responses into problems on the way
+pub fn complete_problem(res: ServiceResponse<BoxBody>) -> ServiceResponse<BoxBody> {
+    let problem = res.response().extensions().get::<Problem>().cloned();
+    let Some(problem) = problem.or_else(|| untyped_problem(&res)) else {
+        return res;
+    };
+
+    let instance = res.request().path().to_string();
+    let request_id = res.request().extensions().get::<RequestId>().map(|id| id.0.to_string());
+    let (req, original) = res.into_parts();
+    let mut response = problem.with_request(instance, request_id).into_response();
+    // Headers such as Allow or ETag still apply to the problem
+    for (name, value) in original.headers() {
+        if !response.headers().contains_key(name) {
+            response.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+    ServiceResponse::new(req, response)
+}
+
+// Makes every error response an RFC 7807 problem
+pub async fn problem_details(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let res = next.call(req).await?;
+    Ok(complete_problem(res.map_into_boxed_body()))
+}
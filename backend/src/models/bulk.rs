@@ -0,0 +1,140 @@
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::models::{FieldError, ProductFilters, ProductUpdates};
+use crate::traits::responses::AppError;
+
+// Most products accepted by a single bulk request
+pub const MAX_BULK_PRODUCTS: usize = 500;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMode {
+    // Every item is created or none is
+    #[default]
+    Atomic,
+    // Items are created independently, failed ones are reported and skipped
+    PerItem
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BulkQuery {
+    #[serde(default)]
+    pub mode: BulkMode
+}
+
+// Outcome of one item, at the same position as in the request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkItemResult {
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // Every failing field of an item that didn't validate, under the item's index
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>
+}
+
+impl BulkItemResult {
+    pub fn created(index: usize, id: Uuid) -> Self {
+        Self { index, id: Some(id), error: None, errors: Vec::new() }
+    }
+
+    pub fn failed(index: usize, error: AppError) -> Self {
+        let errors = match &error {
+            AppError::InvalidFields(errors) => errors.iter().cloned().map(|e| e.at_index(index)).collect(),
+            _ => Vec::new()
+        };
+        Self { index, id: None, error: Some(error.to_string()), errors }
+    }
+
+    // A valid item left out because the rest of an atomic batch failed
+    pub fn skipped(index: usize) -> Self {
+        Self { index, id: None, error: Some("Not created because other items failed".to_string()), errors: Vec::new() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkCreateResult {
+    pub created: usize,
+    pub failed: usize,
+    pub items: Vec<BulkItemResult>
+}
+
+impl BulkCreateResult {
+    pub fn new(items: Vec<BulkItemResult>) -> Self {
+        let created = items.iter().filter(|item| item.id.is_some()).count();
+        Self { created, failed: items.len() - created, items }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BulkChangeQuery {
+    // Only count the matching products, without changing them
+    pub dry_run: Option<bool>
+}
+
+// Arithmetic on the current cost of every selected product, e.g.
+// `{"op": "percent", "by": "8"}` to raise prices by 8%
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", content = "by", rename_all = "snake_case")]
+pub enum CostAdjustment {
+    // Amount in the currency of each product
+    Add(BigDecimal),
+    Multiply(BigDecimal),
+    Percent(BigDecimal)
+}
+
+impl CostAdjustment {
+    // The adjusted cost is `cost * factor + offset`
+    pub fn factor_and_offset(&self) -> (BigDecimal, BigDecimal) {
+        match self {
+            Self::Add(amount) => (BigDecimal::from(1), amount.clone()),
+            Self::Multiply(factor) => (factor.clone(), BigDecimal::from(0)),
+            Self::Percent(percent) => ((BigDecimal::from(100) + percent) / BigDecimal::from(100), BigDecimal::from(0))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkUpdate {
+    #[serde(flatten)]
+    pub updates: ProductUpdates,
+    pub cost_adjustment: Option<CostAdjustment>
+}
+
+impl BulkUpdate {
+    pub fn validate(&self, filters: &ProductFilters) -> Result<(), String> {
+        let ProductUpdates { name, cost, active } = &self.updates;
+        if name.is_none() && cost.is_none() && active.is_none() && self.cost_adjustment.is_none() {
+            return Err("A bulk update needs at least one field to change".to_string());
+        }
+
+        match &self.cost_adjustment {
+            Some(_) if cost.is_some() => {
+                Err("cost and cost_adjustment can't be combined".to_string())
+            }
+            Some(CostAdjustment::Add(_)) if filters.currency.is_none() => {
+                Err("Adding to costs needs a currency filter, since the amount is in each product's currency".to_string())
+            }
+            Some(CostAdjustment::Multiply(factor)) if *factor < 0 => {
+                Err("Costs can't be multiplied by a negative factor".to_string())
+            }
+            Some(CostAdjustment::Percent(percent)) if *percent < -100 => {
+                Err("Costs can't be lowered by more than 100 percent".to_string())
+            }
+            _ => Ok(())
+        }
+    }
+}
+
+// Outcome of a bulk update or delete. When more products match than `limit`,
+// nothing is changed and `ids` stays empty.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkChangeResult {
+    pub dry_run: bool,
+    pub matched: i64,
+    pub limit: i64,
+    pub ids: Vec<Uuid>
+}
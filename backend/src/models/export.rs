@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use crate::models::{CompleteProduct, VARIANT_COLUMN_PREFIX, VARIANT_VALUE_SEPARATOR};
+
+// Products loaded per query while an export streams
+pub const EXPORT_BATCH_SIZE: i64 = 500;
+
+// Encoded batches an export reads ahead of a slow client
+pub const EXPORT_BUFFERED_BATCHES: usize = 2;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    // The columns the import reads, so an export can be edited and imported again
+    #[default]
+    Csv,
+    // One complete product per line
+    Ndjson
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Ndjson => "application/x-ndjson"
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Self::Csv => "products.csv",
+            Self::Ndjson => "products.ndjson"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat
+}
+
+fn csv_bytes(writer: csv::Writer<Vec<u8>>) -> Result<Vec<u8>, csv::Error> {
+    writer.into_inner().map_err(|e| e.into_error().into())
+}
+
+// Header of a CSV export with one column per variant name
+pub fn csv_header(variant_names: &[String]) -> Result<Vec<u8>, csv::Error> {
+    let mut cells: Vec<String> = ["id", "name", "cost", "currency", "active"].map(String::from).to_vec();
+    cells.extend(variant_names.iter().map(|name| format!("{}{}", VARIANT_COLUMN_PREFIX, name)));
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&cells)?;
+    csv_bytes(writer)
+}
+
+pub fn csv_rows(products: &[CompleteProduct], variant_names: &[String]) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for complete in products {
+        let product = &complete.product;
+        let mut cells = vec![
+            product.id.to_string(),
+            product.name.clone(),
+            product.cost.amount_string(),
+            product.cost.currency.clone(),
+            product.active.to_string()
+        ];
+        cells.extend(variant_names.iter().map(|name| {
+            complete.variants
+                .iter()
+                .filter(|group| &group.variant.name == name)
+                .flat_map(|group| group.values.iter().filter_map(|v| v.value.as_deref()))
+                .collect::<Vec<&str>>()
+                .join(&VARIANT_VALUE_SEPARATOR.to_string())
+        }));
+        writer.write_record(&cells)?;
+    }
+    csv_bytes(writer)
+}
+
+pub fn ndjson_rows(products: &[CompleteProduct]) -> serde_json::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for product in products {
+        serde_json::to_writer(&mut out, product)?;
+        out.push(b'\n');
+    }
+    Ok(out)
+}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+use crate::models::Money;
+
+// Roughly how many equal-width buckets the cost range is split into
+pub const COST_BUCKETS: i64 = 5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValueCount {
+    pub value: String,
+    pub count: i64
+}
+
+// Products per value of one variant, e.g. how many come in size 42
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttributeFacet {
+    pub name: String,
+    pub values: Vec<ValueCount>
+}
+
+// Products costing at least `min` and less than `max`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostBucket {
+    pub min: Money,
+    pub max: Money,
+    pub count: i64
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ActiveCounts {
+    pub active: i64,
+    pub inactive: i64
+}
+
+// Sidebar counts for a product query. Every facet ignores its own filter, so the
+// alternatives to the current selection stay visible.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Facets {
+    pub attributes: Vec<AttributeFacet>,
+    pub cost: Vec<CostBucket>,
+    pub active: ActiveCounts
+}
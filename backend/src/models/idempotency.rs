@@ -0,0 +1,77 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde_json::Value;
+use crate::schema::*;
+
+// Longest Idempotency-Key accepted, matching the column
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = idempotency_keys)]
+pub struct NewIdempotencyKey {
+    pub key: String,
+    pub request_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub locked_until: DateTime<Utc>
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = idempotency_keys)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct IdempotencyRecord {
+    pub request_hash: String,
+    pub status_code: Option<i16>,
+    pub response_headers: Option<Value>,
+    pub response_body: Option<Vec<u8>>
+}
+
+// A finished response as it is replayed to retries
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>
+}
+
+impl StoredResponse {
+    pub fn headers_json(&self) -> Value {
+        Value::Array(
+            self.headers
+                .iter()
+                .map(|(name, value)| Value::Array(vec![Value::from(name.clone()), Value::from(value.clone())]))
+                .collect()
+        )
+    }
+
+    // Rows written before the response was complete have nothing to replay
+    pub fn from_record(record: IdempotencyRecord) -> Option<Self> {
+        let headers = record.response_headers
+            .as_ref()
+            .and_then(Value::as_array)
+            .map(|pairs| {
+                pairs
+                    .iter()
+                    .filter_map(|pair| Some((pair.get(0)?.as_str()?.to_string(), pair.get(1)?.as_str()?.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self {
+            status: u16::try_from(record.status_code?).ok()?,
+            headers,
+            body: record.response_body.unwrap_or_default()
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdempotencyClaim {
+    // First use of the key, or one whose earlier request never finished and whose
+    // lease ran out, so the request should run and its response be stored
+    Started,
+    Replay(StoredResponse),
+    // The first request with this key hasn't finished yet
+    InProgress,
+    // The key was used before for a different request
+    Mismatch
+}
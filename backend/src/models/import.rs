@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::models::{Money, DEFAULT_CURRENCY};
+
+// Largest import file accepted by the upload endpoint, and the most data rows in one
+pub const MAX_IMPORT_BYTES: usize = 10 * 1024 * 1024;
+pub const MAX_IMPORT_ROWS: usize = 10_000;
+
+// Columns such as `variant:Size` hold the values of that variant
+pub const VARIANT_COLUMN_PREFIX: &str = "variant:";
+// Separates several values of one variant in a single cell, e.g. `41|42|43`
+pub const VARIANT_VALUE_SEPARATOR: char = '|';
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportQuery {
+    // Validate and match every row, then roll everything back
+    pub dry_run: Option<bool>
+}
+
+// A data row of an import file. Products are matched by `id` when the row has one,
+// otherwise by their exact name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    pub id: Option<Uuid>,
+    pub name: String,
+    pub cost: Money,
+    // Left as it is on existing products when the cell is empty
+    pub active: Option<bool>,
+    // Only the variant cells that aren't empty, which replace the product's values
+    pub variants: Vec<(String, Vec<String>)>
+}
+
+impl ImportRow {
+    // What identifies the product within one file
+    pub fn key(&self) -> String {
+        match self.id {
+            Some(id) => id.to_string(),
+            None => self.name.clone()
+        }
+    }
+}
+
+// A row of the file with the line it starts on, counting the header as line 1
+pub type ParsedRow = (u64, Result<ImportRow, String>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportAction {
+    Created,
+    Updated,
+    // The product already matched the row
+    Unchanged
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportRowResult {
+    pub line: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<ImportAction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>
+}
+
+impl ImportRowResult {
+    pub fn done(line: u64, id: Uuid, action: ImportAction) -> Self {
+        Self { line, id: Some(id), action: Some(action), error: None }
+    }
+
+    pub fn failed(line: u64, error: impl ToString) -> Self {
+        Self { line, id: None, action: None, error: Some(error.to_string()) }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+    pub rows: Vec<ImportRowResult>
+}
+
+impl ImportReport {
+    pub fn new(dry_run: bool, rows: Vec<ImportRowResult>) -> Self {
+        let count = |action: ImportAction| rows.iter().filter(|row| row.action == Some(action)).count();
+        Self {
+            dry_run,
+            created: count(ImportAction::Created),
+            updated: count(ImportAction::Updated),
+            unchanged: count(ImportAction::Unchanged),
+            failed: rows.iter().filter(|row| row.error.is_some()).count(),
+            rows
+        }
+    }
+}
+
+enum Column {
+    Id,
+    Name,
+    Cost,
+    Currency,
+    Active,
+    Variant(String)
+}
+
+fn parse_header(header: &csv::StringRecord) -> Result<Vec<Column>, String> {
+    let mut columns = Vec::with_capacity(header.len());
+    for cell in header.iter().map(str::trim) {
+        let column = match cell.to_lowercase().as_str() {
+            "id" => Column::Id,
+            "name" => Column::Name,
+            "cost" => Column::Cost,
+            "currency" => Column::Currency,
+            "active" => Column::Active,
+            lower if lower.starts_with(VARIANT_COLUMN_PREFIX) => {
+                let name = cell[VARIANT_COLUMN_PREFIX.len()..].trim();
+                if name.is_empty() {
+                    return Err(format!("Column '{}' is missing a variant name", cell));
+                }
+                Column::Variant(name.to_string())
+            }
+            _ => return Err(format!("Unknown column '{}'", cell))
+        };
+        columns.push(column);
+    }
+
+    let mut seen: Vec<String> = Vec::new();
+    for cell in header.iter().map(|cell| cell.trim().to_lowercase()) {
+        if seen.contains(&cell) {
+            return Err(format!("Column '{}' appears more than once", cell));
+        }
+        seen.push(cell);
+    }
+    for required in ["name", "cost"] {
+        if !seen.iter().any(|cell| cell == required) {
+            return Err(format!("Missing required column '{}'", required));
+        }
+    }
+
+    Ok(columns)
+}
+
+fn parse_active(cell: &str) -> Result<Option<bool>, String> {
+    match cell.to_lowercase().as_str() {
+        "" => Ok(None),
+        "true" | "yes" | "1" => Ok(Some(true)),
+        "false" | "no" | "0" => Ok(Some(false)),
+        _ => Err(format!("'{}' is not a valid value for active, use true or false", cell))
+    }
+}
+
+fn parse_row(columns: &[Column], record: &csv::StringRecord) -> Result<ImportRow, String> {
+    let mut id = None;
+    let mut name = "";
+    let mut cost = "";
+    let mut currency = "";
+    let mut active = None;
+    let mut variants = Vec::new();
+
+    for (column, cell) in columns.iter().zip(record.iter().map(str::trim)) {
+        match column {
+            Column::Id if !cell.is_empty() => {
+                id = Some(Uuid::parse_str(cell).map_err(|_| format!("'{}' is not a valid product id", cell))?);
+            }
+            Column::Id => {}
+            Column::Name => name = cell,
+            Column::Cost => cost = cell,
+            Column::Currency => currency = cell,
+            Column::Active => active = parse_active(cell)?,
+            Column::Variant(variant) => {
+                let mut values: Vec<String> = Vec::new();
+                for value in cell.split(VARIANT_VALUE_SEPARATOR).map(str::trim).filter(|v| !v.is_empty()) {
+                    if !values.iter().any(|v| v == value) {
+                        values.push(value.to_string());
+                    }
+                }
+                if !values.is_empty() {
+                    variants.push((variant.clone(), values));
+                }
+            }
+        }
+    }
+
+    if name.is_empty() {
+        return Err("name must not be empty".to_string());
+    }
+    if cost.is_empty() {
+        return Err("cost must not be empty".to_string());
+    }
+    let currency = if currency.is_empty() { DEFAULT_CURRENCY } else { currency };
+
+    Ok(ImportRow {
+        id,
+        name: name.to_string(),
+        cost: Money::new(cost, currency)?,
+        active,
+        variants
+    })
+}
+
+// Reads an import file. A bad header rejects the whole file, a bad row is
+// returned as an error for that row so the others can still be imported.
+pub fn parse_import_csv(data: &[u8]) -> Result<Vec<ParsedRow>, String> {
+    let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(data);
+    let header = reader
+        .headers()
+        .map_err(|e| format!("Invalid CSV header: {}", e))?
+        .clone();
+    let columns = parse_header(&header)?;
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        if rows.len() == MAX_IMPORT_ROWS {
+            return Err(format!("An import can have at most {} rows", MAX_IMPORT_ROWS));
+        }
+        let row = match record {
+            Ok(record) => {
+                let line = record.position().map_or(0, |position| position.line());
+                (line, parse_row(&columns, &record))
+            }
+            Err(e) => {
+                let line = e.position().map_or(0, |position| position.line());
+                let message = match e.kind() {
+                    csv::ErrorKind::UnequalLengths { len, .. } => {
+                        format!("Expected {} cells but found {}", columns.len(), len)
+                    }
+                    _ => e.to_string()
+                };
+                (line, Err(message))
+            }
+        };
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
@@ -1,6 +1,30 @@
+pub mod bulk;
+pub mod export;
+pub mod facets;
+pub mod idempotency;
+pub mod import;
+pub mod money;
+pub mod pagination;
+pub mod patch;
+pub mod preconditions;
 pub mod products;
+pub mod revisions;
+pub mod sorting;
 pub mod utils;
+pub mod validation;
 pub mod variants;
+pub use bulk::*;
+pub use export::*;
+pub use facets::*;
+pub use idempotency::*;
+pub use import::*;
+pub use money::*;
+pub use pagination::*;
+pub use patch::*;
+pub use preconditions::*;
 pub use products::*;
+pub use revisions::*;
+pub use sorting::*;
 pub use utils::*;
+pub use validation::*;
 pub use variants::*;
\ No newline at end of file
@@ -0,0 +1,112 @@
+use std::fmt;
+use std::str::FromStr;
+use bigdecimal::BigDecimal;
+use diesel::prelude::*;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use crate::schema::*;
+
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+// ISO-4217 codes that don't use the usual two decimal places
+pub const ZERO_DECIMAL_CURRENCIES: &[&str] = &[
+    "BIF", "CLP", "DJF", "GNF", "ISK", "JPY", "KMF", "KRW", "PYG",
+    "RWF", "UGX", "UYI", "VND", "VUV", "XAF", "XOF", "XPF"
+];
+pub const THREE_DECIMAL_CURRENCIES: &[&str] = &["BHD", "IQD", "JOD", "KWD", "LYD", "OMR", "TND"];
+
+// Digits after the decimal point for an ISO-4217 currency code
+pub fn minor_units(currency: &str) -> i64 {
+    if ZERO_DECIMAL_CURRENCIES.contains(&currency) {
+        0
+    } else if THREE_DECIMAL_CURRENCIES.contains(&currency) {
+        3
+    } else {
+        2
+    }
+}
+
+// An exact amount in a given currency, stored as NUMERIC plus an ISO-4217 code
+#[derive(
+    Queryable,
+    Selectable,
+    Insertable,
+    Debug,
+    Clone,
+    PartialEq
+)]
+#[diesel(table_name = products)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Money {
+    #[diesel(column_name = cost)]
+    pub amount: BigDecimal,
+    pub currency: String
+}
+
+impl Money {
+    pub fn new(amount: &str, currency: &str) -> Result<Self, String> {
+        let amount = BigDecimal::from_str(amount.trim())
+            .map_err(|_| format!("'{}' is not a valid decimal amount", amount))?;
+        Self::from_parts(amount, currency)
+    }
+
+    pub fn from_parts(amount: BigDecimal, currency: &str) -> Result<Self, String> {
+        let is_code = currency.len() == 3 && currency.chars().all(|c| c.is_ascii_uppercase());
+        if !is_code {
+            return Err(format!("'{}' is not an ISO-4217 currency code", currency));
+        }
+
+        let units = minor_units(currency);
+        if amount.normalized().fractional_digit_count() > units {
+            return Err(format!(
+                "{} amounts allow at most {} decimal places", currency, units
+            ));
+        }
+
+        Ok(Self { amount: amount.with_scale(units), currency: currency.to_string() })
+    }
+
+    // Amount rendered with exactly the currency's number of decimal places
+    pub fn amount_string(&self) -> String {
+        self.amount.with_scale(minor_units(&self.currency)).to_plain_string()
+    }
+}
+
+impl Default for Money {
+    fn default() -> Self {
+        Self { amount: BigDecimal::from(0), currency: DEFAULT_CURRENCY.to_string() }
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount_string(), self.currency)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct MoneyRepr {
+    amount: String,
+    #[serde(default = "default_currency")]
+    currency: String
+}
+
+fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_string()
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MoneyRepr {
+            amount: self.amount_string(),
+            currency: self.currency.clone()
+        }.serialize(serializer)
+    }
+}
+
+// Amounts are only accepted as decimal strings so no precision is lost in transit
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = MoneyRepr::deserialize(deserializer)?;
+        Money::new(&repr.amount, &repr.currency).map_err(de::Error::custom)
+    }
+}
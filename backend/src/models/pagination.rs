@@ -0,0 +1,45 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::models::{Facets, SortValue};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+// Position of the last row of a page. Clients only ever see the encoded form.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cursor {
+    pub id: Uuid,
+    // Values of the requested sort fields, in the same order as `sort`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<SortValue>
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("cursor is always serializable");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(raw: &str) -> Result<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(raw)
+            .map_err(|_| anyhow!("Invalid cursor"))?;
+        serde_json::from_slice(&bytes).map_err(|_| anyhow!("Invalid cursor"))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facets: Option<Facets>,
+    // When anything in the catalog last changed, sent as Last-Modified
+    #[serde(skip)]
+    pub last_modified: Option<DateTime<Utc>>
+}
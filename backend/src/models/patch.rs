@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+use crate::models::{Money, NewCompleteProduct, NewProduct, NewVariant, NewVariantValue, Product, VariantRef, VariantWithValues};
+
+pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+pub const JSON_PATCH_CONTENT_TYPE: &str = "application/json-patch+json";
+
+// The editable state of a product: the body of a PUT and the document a PATCH
+// applies to. Variants are keyed by name so their values can be patched in place,
+// e.g. `{"variants": {"Size": ["41", "42"], "Color": null}}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProductDocument {
+    pub name: String,
+    pub cost: Money,
+    pub active: bool,
+    // Left out, the product has no variants
+    #[serde(default)]
+    pub variants: BTreeMap<String, Vec<String>>
+}
+
+impl ProductDocument {
+    pub fn new(product: &Product, groups: &[VariantWithValues]) -> Self {
+        let mut variants: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for group in groups {
+            let values: Vec<String> = group.values.iter().filter_map(|v| v.value.clone()).collect();
+            if !values.is_empty() {
+                variants.entry(group.variant.name.clone()).or_default().extend(values);
+            }
+        }
+        Self { name: product.name.clone(), cost: product.cost.clone(), active: product.active, variants }
+    }
+
+    // The same product in the shape it's created from
+    pub fn into_new_product(self, id: Uuid) -> NewCompleteProduct {
+        NewCompleteProduct {
+            product: NewProduct { id: Some(id), name: self.name, cost: self.cost, active: self.active },
+            variants: self.variants
+                .into_iter()
+                .map(|(name, values)| NewVariantValue {
+                    variant: VariantRef::Name(NewVariant { name, allowed_values: None }),
+                    values: values.into_iter().map(Some).collect()
+                })
+                .collect()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    // RFC 7396: the body mirrors the document, null removes a member
+    Merge,
+    // RFC 6902: a list of operations on JSON pointers
+    Json
+}
+
+impl PatchFormat {
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        if essence.eq_ignore_ascii_case(MERGE_PATCH_CONTENT_TYPE) {
+            Some(Self::Merge)
+        } else if essence.eq_ignore_ascii_case(JSON_PATCH_CONTENT_TYPE) {
+            Some(Self::Json)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ProductPatch {
+    Merge(Value),
+    Json(json_patch::Patch)
+}
+
+impl ProductPatch {
+    pub fn parse(format: PatchFormat, body: &[u8]) -> Result<Self, String> {
+        match format {
+            PatchFormat::Merge => serde_json::from_slice(body)
+                .map(Self::Merge)
+                .map_err(|e| format!("Invalid merge patch: {}", e)),
+            PatchFormat::Json => serde_json::from_slice(body)
+                .map(Self::Json)
+                .map_err(|e| format!("Invalid JSON patch: {}", e))
+        }
+    }
+
+    // Applies the patch to the document as JSON. The result still has to be read
+    // back into a `ProductDocument`, which catches removed or unknown fields.
+    pub fn apply(&self, document: &ProductDocument) -> Result<Value, json_patch::PatchError> {
+        let mut value = serde_json::to_value(document).expect("product documents are always serializable");
+        match self {
+            Self::Merge(patch) => json_patch::merge(&mut value, patch),
+            Self::Json(patch) => json_patch::patch(&mut value, patch)?
+        }
+        Ok(value)
+    }
+}
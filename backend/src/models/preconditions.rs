@@ -0,0 +1,51 @@
+// Strong entity tag of a product version, e.g. `"3"`. Representations that embed
+// related data append a suffix, e.g. `"3-variants"`.
+pub fn version_etag(version: i32, representation: Option<&str>) -> String {
+    match representation {
+        Some(suffix) => format!("\"{}-{}\"", version, suffix),
+        None => format!("\"{}\"", version)
+    }
+}
+
+// Product version an entity tag was issued for. Weak tags never qualify since
+// If-Match requires strong comparison.
+fn tag_version(tag: &str) -> Option<i32> {
+    let opaque = tag.strip_prefix('"')?.strip_suffix('"')?;
+    opaque.split('-').next()?.parse().ok()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IfMatch {
+    Any,
+    Versions(Vec<i32>)
+}
+
+impl IfMatch {
+    // Tags that don't look like ours are kept out of the list, so they simply never match
+    pub fn parse(header: &str) -> Self {
+        if header.trim() == "*" {
+            return Self::Any;
+        }
+        Self::Versions(header.split(',').filter_map(|tag| tag_version(tag.trim())).collect())
+    }
+
+    pub fn matches(&self, version: i32) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Versions(versions) => versions.contains(&version)
+        }
+    }
+}
+
+fn opaque_tag(tag: &str) -> &str {
+    tag.trim().trim_start_matches("W/")
+}
+
+// True when an If-None-Match header names the tag. The comparison is weak, so
+// `W/"3"` and `"3"` count as the same version.
+pub fn names_tag(header: &str, etag: &str) -> bool {
+    if header.trim() == "*" {
+        return true;
+    }
+    header.split(',').any(|tag| opaque_tag(tag) == opaque_tag(etag))
+}
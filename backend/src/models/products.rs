@@ -2,6 +2,9 @@
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use crate::models::Money;
 use crate::schema::*;
 
 
@@ -17,20 +20,47 @@ use crate::schema::*;
 pub struct NewProduct {
     pub id: Option<Uuid>,
     pub name: String,
-    pub cost: f64,
+    #[diesel(embed)]
+    pub cost: Money,
     pub active: bool,
 }
 
 
-#[derive(AsChangeset, Debug, Clone, Serialize, Deserialize)]
-#[diesel(table_name = products)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProductUpdates {
     pub name: Option<String>,
-    pub cost: Option<f64>,
+    pub cost: Option<Money>,
     pub active: Option<bool>
 }
 
 
+// Column-level form of `ProductUpdates`, since a `Money` spans two columns
+#[derive(AsChangeset, Debug, Clone)]
+#[diesel(table_name = products)]
+pub struct ProductChangeset {
+    pub name: Option<String>,
+    pub cost: Option<BigDecimal>,
+    pub currency: Option<String>,
+    pub active: Option<bool>
+}
+
+impl From<ProductUpdates> for ProductChangeset {
+    fn from(updates: ProductUpdates) -> Self {
+        let (cost, currency) = match updates.cost {
+            Some(money) => (Some(money.amount), Some(money.currency)),
+            None => (None, None)
+        };
+
+        Self {
+            name: updates.name,
+            cost,
+            currency,
+            active: updates.active
+        }
+    }
+}
+
+
 #[derive(
     Identifiable, 
     Queryable,
@@ -45,6 +75,29 @@ pub struct ProductUpdates {
 pub struct Product {
     pub id: Uuid,
     pub name: String,
-    pub cost: f64,
+    #[diesel(embed)]
+    pub cost: Money,
     pub active: bool,
+    pub version: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // Set when the product is soft-deleted, so only tombstones read with `include_deleted` carry it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+
+// Outcome of purging tombstoned products
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurgeSummary {
+    pub purged: usize,
+    pub deleted_before: DateTime<Utc>
+}
+
+
+#[derive(Queryable, Debug, Clone, Serialize, Deserialize)]
+pub struct ProductSuggestion {
+    pub id: Uuid,
+    pub name: String,
+    pub score: f32
 }
\ No newline at end of file
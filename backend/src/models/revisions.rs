@@ -0,0 +1,152 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+use crate::models::{Money, Product, VariantWithValues};
+use crate::schema::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevisionAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+    Revert,
+    Purge
+}
+
+impl RevisionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+            Self::Restore => "restore",
+            Self::Revert => "revert",
+            Self::Purge => "purge"
+        }
+    }
+}
+
+// A product's state right after one of its writes
+#[derive(Queryable, Selectable, Insertable, Debug, Clone)]
+#[diesel(table_name = product_revisions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct RevisionRecord {
+    pub product_id: Uuid,
+    pub revision: i32,
+    pub action: String,
+    pub changed_by: Option<String>,
+    pub changed_at: DateTime<Utc>,
+    pub name: String,
+    pub cost: BigDecimal,
+    pub currency: String,
+    pub active: bool,
+    pub deleted: bool,
+    pub variants: Value
+}
+
+// A variant as a revision records it, with the values the product had for it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariantSnapshot {
+    pub variant_id: Uuid,
+    pub name: String,
+    pub values: Vec<Option<String>>
+}
+
+impl From<&VariantWithValues> for VariantSnapshot {
+    fn from(group: &VariantWithValues) -> Self {
+        Self {
+            variant_id: group.variant.id,
+            name: group.variant.name.clone(),
+            values: group.values.iter().map(|value| value.value.clone()).collect()
+        }
+    }
+}
+
+impl RevisionRecord {
+    pub fn of(
+        product: &Product,
+        variants: &[VariantWithValues],
+        action: RevisionAction,
+        changed_by: Option<&str>
+    ) -> Self {
+        let variants: Vec<VariantSnapshot> = variants.iter().map(VariantSnapshot::from).collect();
+        Self {
+            product_id: product.id,
+            revision: product.version,
+            action: action.as_str().to_string(),
+            changed_by: changed_by.map(str::to_string),
+            changed_at: product.updated_at,
+            name: product.name.clone(),
+            cost: product.cost.amount.clone(),
+            currency: product.cost.currency.clone(),
+            active: product.active,
+            deleted: product.deleted_at.is_some(),
+            variants: serde_json::to_value(variants).unwrap_or_default()
+        }
+    }
+
+    pub fn variant_snapshots(&self) -> serde_json::Result<Vec<VariantSnapshot>> {
+        serde_json::from_value(self.variants.clone())
+    }
+
+    pub fn cost(&self) -> Money {
+        Money { amount: self.cost.clone(), currency: self.currency.clone() }
+    }
+
+    // Tracked fields as they appear in product responses, with variants as snapshotted
+    fn fields(&self) -> [(&'static str, Value); 5] {
+        [
+            ("name", Value::from(self.name.clone())),
+            ("cost", serde_json::to_value(self.cost()).unwrap_or_default()),
+            ("active", Value::from(self.active)),
+            ("deleted", Value::from(self.deleted)),
+            ("variants", self.variants.clone())
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    // Absent for the revision that created the product
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<Value>,
+    pub to: Value
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductRevision {
+    pub revision: i32,
+    pub action: String,
+    pub changed_by: Option<String>,
+    pub changed_at: DateTime<Utc>,
+    pub changes: Vec<FieldChange>
+}
+
+impl ProductRevision {
+    // Fields that differ from the previous revision, or all of them for the first one
+    pub fn between(previous: Option<&RevisionRecord>, current: RevisionRecord) -> Self {
+        let before = previous.map(RevisionRecord::fields);
+        let changes = current
+            .fields()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, (field, to))| {
+                let from = before.as_ref().map(|fields| fields[index].1.clone());
+                (from.as_ref() != Some(&to)).then(|| FieldChange { field: field.to_string(), from, to })
+            })
+            .collect();
+
+        Self {
+            revision: current.revision,
+            action: current.action,
+            changed_by: current.changed_by,
+            changed_at: current.changed_at,
+            changes
+        }
+    }
+}
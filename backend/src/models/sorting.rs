@@ -0,0 +1,110 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::models::Product;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    Name,
+    Cost,
+    Active,
+    CreatedAt,
+    UpdatedAt,
+    // Full-text search rank, only available together with `q`
+    Relevance
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDirection {
+    Asc,
+    Desc
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortOrder {
+    pub field: SortField,
+    pub direction: SortDirection
+}
+
+// Sort key of a single row, stored in the pagination cursor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortValue {
+    Bool(bool),
+    Decimal(BigDecimal),
+    Rank(f32),
+    Text(String),
+    Timestamp(DateTime<Utc>)
+}
+
+impl SortField {
+    pub const ALLOWED: &'static str = "name, cost, active, created_at, updated_at, relevance";
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "name" => Some(Self::Name),
+            "cost" => Some(Self::Cost),
+            "active" => Some(Self::Active),
+            "created_at" => Some(Self::CreatedAt),
+            "updated_at" => Some(Self::UpdatedAt),
+            "relevance" => Some(Self::Relevance),
+            _ => None
+        }
+    }
+
+    pub fn value_of(&self, product: &Product, rank: f32) -> SortValue {
+        match self {
+            Self::Name => SortValue::Text(product.name.clone()),
+            Self::Cost => SortValue::Decimal(product.cost.amount.clone()),
+            Self::Active => SortValue::Bool(product.active),
+            Self::CreatedAt => SortValue::Timestamp(product.created_at),
+            Self::UpdatedAt => SortValue::Timestamp(product.updated_at),
+            Self::Relevance => SortValue::Rank(rank)
+        }
+    }
+
+    pub fn accepts(&self, value: &SortValue) -> bool {
+        matches!(
+            (self, value),
+            (Self::Name, SortValue::Text(_)) |
+            (Self::Cost, SortValue::Decimal(_)) |
+            (Self::Active, SortValue::Bool(_)) |
+            (Self::CreatedAt | Self::UpdatedAt, SortValue::Timestamp(_)) |
+            (Self::Relevance, SortValue::Rank(_))
+        )
+    }
+}
+
+impl SortOrder {
+    // Parses `sort=cost:desc,name` into a list of orderings, defaulting to ascending
+    pub fn parse_list(raw: &str) -> Result<Vec<Self>, String> {
+        let mut orders: Vec<Self> = Vec::new();
+
+        for part in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (field_name, direction) = match part.split_once(':') {
+                Some((field, direction)) => (field.trim(), direction.trim()),
+                None => (part, "asc")
+            };
+
+            let field = SortField::parse(field_name).ok_or_else(|| format!(
+                "Unknown sort field '{}', expected one of: {}", field_name, SortField::ALLOWED
+            ))?;
+
+            let direction = match direction {
+                "asc" => SortDirection::Asc,
+                "desc" => SortDirection::Desc,
+                other => return Err(format!(
+                    "Unknown sort direction '{}', expected 'asc' or 'desc'", other
+                ))
+            };
+
+            if orders.iter().any(|o| o.field == field) {
+                return Err(format!("Sort field '{}' is listed more than once", field_name));
+            }
+
+            orders.push(Self { field, direction });
+        }
+
+        Ok(orders)
+    }
+}
@@ -1,27 +1,293 @@
+use std::collections::BTreeMap;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use crate::models::{NewProduct, NewVariant};
+use uuid::Uuid;
+use crate::models::{
+    Cursor, NewProduct, Product, Variant, VariantRef, SortDirection, SortField, SortOrder, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE
+};
 
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct ProductFilters {
     pub name: Option<String>,
-    pub cost_ge: Option<f64>,
-    pub cost_le: Option<f64>,
-    pub is_active: Option<bool>
+    pub cost_ge: Option<BigDecimal>,
+    pub cost_le: Option<BigDecimal>,
+    pub currency: Option<String>,
+    pub is_active: Option<bool>,
+    // RFC 3339 instants; only rows created or changed strictly later match
+    pub created_after: Option<DateTime<Utc>>,
+    pub updated_after: Option<DateTime<Utc>>,
+    pub q: Option<String>,
+    pub search_variants: Option<bool>,
+    pub fuzzy: Option<bool>,
+    pub sort: Option<String>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    pub include_total: Option<bool>,
+    pub include_facets: Option<bool>,
+    pub include: Option<String>,
+    // Lists tombstoned products alongside the live ones
+    pub include_deleted: Option<bool>,
+    // Lowercased variant name -> accepted values, read from `attr.*` parameters
+    #[serde(skip)]
+    pub attributes: BTreeMap<String, Vec<String>>
 }
 
 impl ProductFilters {
     pub fn is_empty(&self) -> bool {
-        self.name.is_none() && 
-        self.cost_ge.is_none() && 
-        self.cost_le.is_none() && 
-        self.is_active.is_none()
+        self.name.is_none() &&
+        self.cost_ge.is_none() &&
+        self.cost_le.is_none() &&
+        self.currency.is_none() &&
+        self.is_active.is_none() &&
+        self.created_after.is_none() &&
+        self.updated_after.is_none() &&
+        self.q.is_none() &&
+        self.search_variants.is_none() &&
+        self.fuzzy.is_none() &&
+        self.sort.is_none() &&
+        self.limit.is_none() &&
+        self.cursor.is_none() &&
+        self.include_total.is_none() &&
+        self.include_facets.is_none() &&
+        self.include.is_none() &&
+        self.include_deleted.is_none() &&
+        self.attributes.is_empty()
+    }
+
+    // Whether anything narrows down which products match. Search modifiers such as
+    // `fuzzy` or `search_variants` change how `q` matches, but select nothing on their own.
+    pub fn has_predicate(&self) -> bool {
+        self.name.as_deref().is_some_and(|name| !name.is_empty()) ||
+        self.cost_ge.is_some() ||
+        self.cost_le.is_some() ||
+        self.currency.is_some() ||
+        self.is_active.is_some() ||
+        self.created_after.is_some() ||
+        self.updated_after.is_some() ||
+        self.search_query().is_some() ||
+        self.fuzzy_query().is_some() ||
+        !self.attributes.is_empty()
+    }
+
+    // ILIKE pattern for products whose name contains the `name` filter. The filter matches
+    // literally, so a `%` or `_` can't widen it to every product.
+    pub fn name_pattern(&self) -> Option<String> {
+        self.name.as_deref().map(|name| format!("%{}%", escape_like(name)))
+    }
+
+    pub fn page_size(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE)
+    }
+
+    // Words of the `q` parameter, stripped of anything tsquery would treat as syntax
+    pub fn search_terms(&self) -> Vec<String> {
+        self.q
+            .as_deref()
+            .unwrap_or_default()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|term| !term.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    }
+
+    // Prefix-matching tsquery requiring every search term, e.g. `red:* & 42:*`
+    pub fn search_query(&self) -> Option<String> {
+        let terms = self.search_terms();
+        if terms.is_empty() {
+            return None;
+        }
+        Some(terms.iter().map(|term| prefix_term(term)).collect::<Vec<_>>().join(" & "))
+    }
+
+    // Trigram matching on the raw `q` instead of full-text search
+    pub fn fuzzy_query(&self) -> Option<String> {
+        if !self.fuzzy.unwrap_or(false) {
+            return None;
+        }
+        self.q.as_deref().map(str::trim).filter(|q| !q.is_empty()).map(str::to_lowercase)
+    }
+
+    // Search results default to best match first
+    pub fn sort_order(&self) -> Result<Vec<SortOrder>, String> {
+        match &self.sort {
+            Some(sort) => SortOrder::parse_list(sort),
+            None if self.q.is_some() => Ok(vec![SortOrder {
+                field: SortField::Relevance,
+                direction: SortDirection::Desc
+            }]),
+            None => Ok(Vec::new())
+        }
+    }
+
+    // Checks the parameters that can't be expressed in the query string types
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(limit) = self.limit
+            && !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(format!("limit must be between 1 and {}", MAX_PAGE_SIZE));
+        }
+
+        if self.q.is_some() && self.search_terms().is_empty() {
+            return Err("q must contain at least one letter or digit".to_string());
+        }
+
+        // Amounts of different currencies don't compare, so a cost range is always within one
+        if (self.cost_ge.is_some() || self.cost_le.is_some()) && self.currency.is_none() {
+            return Err("cost_ge and cost_le need a currency to compare costs in".to_string());
+        }
+
+        includes_variants(self.include.as_deref())?;
+
+        let sort_order = self.sort_order()?;
+
+        if self.q.is_none() && sort_order.iter().any(|o| o.field == SortField::Relevance) {
+            return Err("Sorting by relevance requires a q search parameter".to_string());
+        }
+
+        if let Some(cursor) = &self.cursor {
+            let cursor = Cursor::decode(cursor).map_err(|e| e.to_string())?;
+            let matches_sort = cursor.keys.len() == sort_order.len() &&
+                sort_order.iter().zip(&cursor.keys).all(|(order, key)| order.field.accepts(key));
+            if !matches_sort {
+                return Err("Cursor does not match the requested sort order".to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    // The first paging or sorting parameter given, none of which apply when
+    // every matching product is processed
+    fn paging_parameter(&self) -> Option<&'static str> {
+        [
+            ("sort", self.sort.is_some()),
+            ("limit", self.limit.is_some()),
+            ("cursor", self.cursor.is_some()),
+            ("include_total", self.include_total.is_some()),
+            ("include_facets", self.include_facets.is_some())
+        ]
+        .into_iter()
+        .find_map(|(parameter, given)| given.then_some(parameter))
+    }
+
+    // Filters picking the products of a bulk change. Only live products can be
+    // changed, and without any filter the whole catalog would match.
+    pub fn validate_selector(&self) -> Result<(), String> {
+        self.validate()?;
+
+        let unsupported = self.paging_parameter()
+            .or(self.include.is_some().then_some("include"))
+            .or(self.include_deleted.is_some().then_some("include_deleted"));
+        if let Some(parameter) = unsupported {
+            return Err(format!("{} can't be used to select products for a bulk change", parameter));
+        }
+
+        if !self.has_predicate() {
+            return Err("A bulk change needs at least one filter".to_string());
+        }
+
+        Ok(())
+    }
+
+    // Filters of an export, which always covers every matching product in id order
+    pub fn validate_export(&self) -> Result<(), String> {
+        self.validate()?;
+
+        if let Some(parameter) = self.paging_parameter() {
+            return Err(format!("{} can't be used with an export", parameter));
+        }
+
+        Ok(())
+    }
+}
+
+pub const ATTRIBUTE_PREFIX: &str = "attr.";
+
+// Variant attribute filters such as `attr.size=42&attr.size=43&attr.color=black,red`.
+// Values of one attribute are alternatives, different attributes all have to match.
+pub fn parse_attribute_filters(query: &str) -> Result<BTreeMap<String, Vec<String>>, String> {
+    let pairs: Vec<(String, String)> = serde_urlencoded::from_str(query)
+        .map_err(|e| format!("Invalid query string: {}", e))?;
+
+    let mut attributes: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (key, raw_values) in pairs {
+        let Some(name) = key.strip_prefix(ATTRIBUTE_PREFIX) else {
+            continue;
+        };
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            return Err(format!("Attribute filter '{}' is missing a variant name", key));
+        }
+
+        let values = attributes.entry(name).or_default();
+        for value in raw_values.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+            let value = value.to_lowercase();
+            if !values.contains(&value) {
+                values.push(value);
+            }
+        }
+        if values.is_empty() {
+            return Err(format!("Attribute filter '{}' needs at least one value", key));
+        }
+    }
+
+    Ok(attributes)
+}
+
+// Related data a product read can embed, e.g. `?include=variants`
+pub fn includes_variants(include: Option<&str>) -> Result<bool, String> {
+    let mut variants = false;
+    for part in include.unwrap_or_default().split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part {
+            "variants" => variants = true,
+            other => return Err(format!("Unknown include '{}', expected: variants", other))
+        }
+    }
+    Ok(variants)
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct IncludeQuery {
+    pub include: Option<String>
+}
+
+pub fn prefix_term(term: &str) -> String {
+    format!("{}:*", term)
+}
+
+// Text matched literally by LIKE/ILIKE, with its wildcards and the escape character escaped
+pub fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+pub const DEFAULT_SUGGESTIONS: i64 = 10;
+pub const MAX_SUGGESTIONS: i64 = 50;
+
+#[derive(Serialize, Deserialize)]
+pub struct SuggestQuery {
+    pub prefix: String,
+    pub limit: Option<i64>
+}
+
+impl SuggestQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.prefix.trim().is_empty() {
+            return Err("prefix must not be empty".to_string());
+        }
+
+        if let Some(limit) = self.limit
+            && !(1..=MAX_SUGGESTIONS).contains(&limit) {
+            return Err(format!("limit must be between 1 and {}", MAX_SUGGESTIONS));
+        }
+
+        Ok(())
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NewVariantValue {
-    pub variant: NewVariant,
+    pub variant: VariantRef,
     pub values: Vec<Option<String>>
 }
 
@@ -29,4 +295,24 @@ pub struct NewVariantValue {
 pub struct NewCompleteProduct {
     pub product: NewProduct,
     pub variants: Vec<NewVariantValue>
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VariantValue {
+    pub id: Uuid,
+    pub value: Option<String>
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VariantWithValues {
+    #[serde(flatten)]
+    pub variant: Variant,
+    pub values: Vec<VariantValue>
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CompleteProduct {
+    #[serde(flatten)]
+    pub product: Product,
+    pub variants: Vec<VariantWithValues>
+}
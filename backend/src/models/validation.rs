@@ -0,0 +1,297 @@
+use std::collections::HashSet;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::models::{
+    BulkUpdate, ImportRow, Money, NewCompleteProduct, NewProduct, NewProductVariantValue, NewVariant,
+    NewVariantValue, ProductDocument, ProductUpdates, ProductVariantUpdates, VariantRef, VariantUpdate, VARIANT_COLUMN_PREFIX
+};
+
+pub const MAX_PRODUCT_NAME_LENGTH: usize = 200;
+pub const MAX_VARIANT_NAME_LENGTH: usize = 100;
+pub const MAX_VARIANT_VALUE_LENGTH: usize = 100;
+pub const MAX_VARIANTS_PER_PRODUCT: usize = 50;
+pub const MAX_VALUES_PER_VARIANT: usize = 200;
+// Highest cost accepted, in the major unit of any currency
+pub const MAX_COST: i64 = 1_000_000_000;
+
+// A rule a request body broke, at a path such as `variants[2].values[0]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+impl FieldError {
+    // The same failure for the item at `index` of a batch, e.g. `[2].variants[0].values[1]`
+    pub fn at_index(self, index: usize) -> Self {
+        let field = match self.field.as_str() {
+            "$" => format!("[{}]", index),
+            field => format!("[{}].{}", index, field)
+        };
+        Self { field, ..self }
+    }
+}
+
+// One line for places that report a failure as a single message, such as import rows
+pub fn describe_field_errors(errors: &[FieldError]) -> String {
+    errors.iter().map(FieldError::to_string).collect::<Vec<String>>().join("; ")
+}
+
+// Collects every failing field instead of stopping at the first one
+#[derive(Debug, Default)]
+pub struct Validator {
+    path: Vec<String>,
+    errors: Vec<FieldError>
+}
+
+impl Validator {
+    fn nested(&mut self, segment: String, check: impl FnOnce(&mut Self)) -> &mut Self {
+        self.path.push(segment);
+        check(self);
+        self.path.pop();
+        self
+    }
+
+    pub fn field(&mut self, name: &str, check: impl FnOnce(&mut Self)) -> &mut Self {
+        let segment = match self.path.is_empty() {
+            true => name.to_string(),
+            false => format!(".{}", name)
+        };
+        self.nested(segment, check)
+    }
+
+    pub fn index(&mut self, index: usize, check: impl FnOnce(&mut Self)) -> &mut Self {
+        self.nested(format!("[{}]", index), check)
+    }
+
+    pub fn fail(&mut self, message: impl Into<String>) {
+        let field = match self.path.concat() {
+            path if path.is_empty() => "$".to_string(),
+            path => path
+        };
+        self.errors.push(FieldError { field, message: message.into() });
+    }
+
+    pub fn check(&mut self, ok: bool, message: impl Into<String>) {
+        if !ok {
+            self.fail(message);
+        }
+    }
+
+    // Not blank and at most `max` characters
+    pub fn text(&mut self, value: &str, max: usize) {
+        if value.trim().is_empty() {
+            self.fail("must not be blank");
+        } else if value.chars().count() > max {
+            self.fail(format!("must be at most {} characters", max));
+        }
+    }
+
+    pub fn cost(&mut self, cost: &Money) {
+        if cost.amount < 0 {
+            self.fail("must not be negative");
+        } else if cost.amount > MAX_COST {
+            self.fail(format!("must be at most {}", MAX_COST));
+        }
+    }
+
+    pub fn count<T>(&mut self, items: &[T], max: usize) {
+        self.check(items.len() <= max, format!("must have at most {} entries", max));
+    }
+
+    pub fn variant_values<'a>(&mut self, values: impl IntoIterator<Item = Option<&'a str>>) {
+        let mut seen = HashSet::new();
+        for (index, value) in values.into_iter().enumerate() {
+            self.index(index, |v| match value {
+                None => v.fail("must not be null"),
+                Some(value) => {
+                    v.text(value, MAX_VARIANT_VALUE_LENGTH);
+                    v.check(seen.insert(value.trim()), "is listed more than once");
+                }
+            });
+        }
+    }
+
+    pub fn finish(self) -> Result<(), Vec<FieldError>> {
+        match self.errors.is_empty() {
+            true => Ok(()),
+            false => Err(self.errors)
+        }
+    }
+}
+
+// Request bodies checked before they reach a service
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+
+    fn validated(&self) -> Result<(), Vec<FieldError>> {
+        let mut validator = Validator::default();
+        self.validate(&mut validator);
+        validator.finish()
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    fn validate(&self, v: &mut Validator) {
+        for (index, item) in self.iter().enumerate() {
+            v.index(index, |v| item.validate(v));
+        }
+    }
+}
+
+impl Validate for NewProduct {
+    fn validate(&self, v: &mut Validator) {
+        v.field("name", |v| v.text(&self.name, MAX_PRODUCT_NAME_LENGTH))
+            .field("cost", |v| v.cost(&self.cost));
+    }
+}
+
+impl Validate for ProductUpdates {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(name) = &self.name {
+            v.field("name", |v| v.text(name, MAX_PRODUCT_NAME_LENGTH));
+        }
+        if let Some(cost) = &self.cost {
+            v.field("cost", |v| v.cost(cost));
+        }
+    }
+}
+
+impl Validate for BulkUpdate {
+    fn validate(&self, v: &mut Validator) {
+        self.updates.validate(v);
+    }
+}
+
+impl Validate for NewVariant {
+    fn validate(&self, v: &mut Validator) {
+        v.field("name", |v| v.text(&self.name, MAX_VARIANT_NAME_LENGTH));
+        if let Some(allowed) = &self.allowed_values {
+            v.field("allowed_values", |v| {
+                v.count(allowed, MAX_VALUES_PER_VARIANT);
+                v.variant_values(allowed.iter().map(|value| Some(value.as_str())));
+            });
+        }
+    }
+}
+
+impl Validate for VariantUpdate {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(name) = &self.name {
+            v.field("name", |v| v.text(name, MAX_VARIANT_NAME_LENGTH));
+        }
+        if let Some(Some(allowed)) = &self.allowed_values {
+            v.field("allowed_values", |v| {
+                v.count(allowed, MAX_VALUES_PER_VARIANT);
+                v.variant_values(allowed.iter().map(|value| Some(value.as_str())));
+            });
+        }
+    }
+}
+
+impl Validate for VariantRef {
+    fn validate(&self, v: &mut Validator) {
+        if let Self::Name(variant) = self {
+            variant.validate(v);
+        }
+    }
+}
+
+impl Validate for NewVariantValue {
+    fn validate(&self, v: &mut Validator) {
+        v.field("variant", |v| self.variant.validate(v))
+            .field("values", |v| {
+                v.check(!self.values.is_empty(), "must have at least one value");
+                v.count(&self.values, MAX_VALUES_PER_VARIANT);
+                v.variant_values(self.values.iter().map(Option::as_deref));
+            });
+    }
+}
+
+impl Validate for NewProductVariantValue {
+    fn validate(&self, v: &mut Validator) {
+        v.field("value", |v| v.text(&self.value, MAX_VARIANT_VALUE_LENGTH));
+    }
+}
+
+impl Validate for ProductVariantUpdates {
+    fn validate(&self, v: &mut Validator) {
+        v.field("value", |v| v.text(&self.value, MAX_VARIANT_VALUE_LENGTH));
+    }
+}
+
+// Variants given twice, by the same id or by the same name once trimmed, in any case
+fn variant_key(variant: &VariantRef) -> String {
+    match variant {
+        VariantRef::Id { id } => id.to_string(),
+        VariantRef::Name(variant) => variant.name.trim().to_lowercase()
+    }
+}
+
+impl Validate for NewCompleteProduct {
+    fn validate(&self, v: &mut Validator) {
+        v.field("product", |v| self.product.validate(v))
+            .field("variants", |v| {
+                v.count(&self.variants, MAX_VARIANTS_PER_PRODUCT);
+                let mut seen = HashSet::new();
+                for (index, variant) in self.variants.iter().enumerate() {
+                    v.index(index, |v| {
+                        variant.validate(v);
+                        if !seen.insert(variant_key(&variant.variant)) {
+                            v.field("variant", |v| v.fail(format!("'{}' is listed more than once", variant.variant)));
+                        }
+                    });
+                }
+            });
+    }
+}
+
+impl Validate for ProductDocument {
+    fn validate(&self, v: &mut Validator) {
+        v.field("name", |v| v.text(&self.name, MAX_PRODUCT_NAME_LENGTH))
+            .field("cost", |v| v.cost(&self.cost))
+            .field("variants", |v| {
+                v.check(
+                    self.variants.len() <= MAX_VARIANTS_PER_PRODUCT,
+                    format!("must have at most {} entries", MAX_VARIANTS_PER_PRODUCT)
+                );
+                let mut seen = HashSet::new();
+                for (name, values) in &self.variants {
+                    v.field(name, |v| {
+                        v.text(name, MAX_VARIANT_NAME_LENGTH);
+                        v.check(seen.insert(name.trim().to_lowercase()), "is listed more than once");
+                        v.check(!values.is_empty(), "must have at least one value");
+                        v.count(values, MAX_VALUES_PER_VARIANT);
+                        v.variant_values(values.iter().map(|value| Some(value.as_str())));
+                    });
+                }
+            });
+    }
+}
+
+// Import rows are checked by column, since there is no JSON path to point at
+impl Validate for ImportRow {
+    fn validate(&self, v: &mut Validator) {
+        v.field("name", |v| v.text(&self.name, MAX_PRODUCT_NAME_LENGTH))
+            .field("cost", |v| v.cost(&self.cost));
+        for (name, values) in &self.variants {
+            v.field(&format!("{}{}", VARIANT_COLUMN_PREFIX, name), |v| {
+                // Names from the header follow the rules of variants named in a request body
+                NewVariant { name: name.clone(), allowed_values: None }.validate(v);
+                v.count(values, MAX_VALUES_PER_VARIANT);
+                for value in values {
+                    v.check(
+                        value.chars().count() <= MAX_VARIANT_VALUE_LENGTH,
+                        format!("'{}' is longer than {} characters", value, MAX_VARIANT_VALUE_LENGTH)
+                    );
+                }
+            });
+        }
+    }
+}
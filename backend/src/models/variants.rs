@@ -1,6 +1,7 @@
 use diesel::{Insertable, Queryable, Identifiable};
 use diesel::prelude::*;
-use serde::{Serialize, Deserialize};
+use std::fmt;
+use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
 use crate::schema::*;
 
@@ -13,7 +14,27 @@ use crate::schema::*;
 )]
 #[diesel(table_name = variants)]
 pub struct NewVariant {
-    pub name: String
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_values: Option<Vec<String>>
+}
+
+// Points at a shared variant definition, either an existing one by id or one
+// looked up by name and created on first use
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum VariantRef {
+    Id { id: Uuid },
+    Name(NewVariant)
+}
+
+impl fmt::Display for VariantRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Id { id } => write!(f, "{}", id),
+            Self::Name(variant) => write!(f, "{}", variant.name)
+        }
+    }
 }
 
 #[derive(
@@ -25,7 +46,19 @@ pub struct NewVariant {
 )]
 #[diesel(table_name = variants)]
 pub struct VariantUpdate {
-    pub name: Option<String>
+    pub name: Option<String>,
+    // Left as it is when absent, and null lifts the restriction so any value is accepted
+    #[serde(default, deserialize_with = "explicit_null", skip_serializing_if = "Option::is_none")]
+    pub allowed_values: Option<Option<Vec<String>>>
+}
+
+// Tells an explicit null, `Some(None)`, from a field that is absent, `None` by default
+fn explicit_null<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>
+{
+    Option::<T>::deserialize(deserializer).map(Some)
 }
 
 #[derive(
@@ -41,7 +74,17 @@ pub struct VariantUpdate {
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct Variant {
     pub id: Uuid,
-    pub name: String
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_values: Option<Vec<String>>
+}
+
+impl Variant {
+    pub fn allows(&self, value: &str) -> bool {
+        self.allowed_values
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|a| a == value))
+    }
 }
 
 #[derive(
@@ -58,6 +101,13 @@ pub struct NewProductVariant {
     pub value: String
 }
 
+// Request body for adding a single value to a product's variant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewProductVariantValue {
+    pub value: String
+}
+
+// A value stays with its product and variant, only its text can change
 #[derive(
     AsChangeset, 
     Debug, 
@@ -66,10 +116,9 @@ pub struct NewProductVariant {
     Deserialize
 )]
 #[diesel(table_name = product_variants)]
+#[serde(deny_unknown_fields)]
 pub struct ProductVariantUpdates {
-    pub variant_id: Option<Uuid>,
-    pub product_id: Option<Uuid>,
-    pub value: Option<String>
+    pub value: String
 }
 
 #[derive(
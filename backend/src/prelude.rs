@@ -1,3 +1,3 @@
 // Re-export commonly used items
-pub use crate::traits::responses::{ResponseHelper, ErrorResponse};
-pub use actix_web::{web, Result as ActixResult, HttpResponse};
\ No newline at end of file
+pub use crate::traits::responses::{error_response, AppError, CachedResponseHelper, ResponseHelper};
+pub use actix_web::{web, Result as ActixResult, HttpResponse, ResponseError};
\ No newline at end of file
@@ -1,27 +1,88 @@
 // @generated automatically by Diesel CLI.
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    idempotency_keys (key) {
+        #[max_length = 255]
+        key -> Varchar,
+        #[max_length = 64]
+        request_hash -> Varchar,
+        status_code -> Nullable<Int2>,
+        response_headers -> Nullable<Jsonb>,
+        response_body -> Nullable<Bytea>,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        locked_until -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
+    product_revisions (product_id, revision) {
+        product_id -> Uuid,
+        revision -> Int4,
+        #[max_length = 16]
+        action -> Varchar,
+        changed_by -> Nullable<Varchar>,
+        changed_at -> Timestamptz,
+        name -> Varchar,
+        cost -> Numeric,
+        #[max_length = 3]
+        currency -> Varchar,
+        active -> Bool,
+        deleted -> Bool,
+        variants -> Jsonb,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
     product_variants (id) {
         id -> Uuid,
         product_id -> Uuid,
         variant_id -> Uuid,
         value -> Nullable<Varchar>,
+        search_vector -> Tsvector,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
     products (id) {
         id -> Uuid,
         name -> Varchar,
-        cost -> Float8,
+        cost -> Numeric,
         active -> Bool,
+        #[max_length = 3]
+        currency -> Varchar,
+        search_vector -> Tsvector,
+        version -> Int4,
+        updated_at -> Timestamptz,
+        created_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use diesel_full_text_search::Tsvector;
+
     variants (id) {
         id -> Uuid,
         name -> Varchar,
+        allowed_values -> Nullable<Array<Text>>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -29,6 +90,8 @@ diesel::joinable!(product_variants -> products (product_id));
 diesel::joinable!(product_variants -> variants (variant_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    idempotency_keys,
+    product_revisions,
     product_variants,
     products,
     variants,
@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+use bigdecimal::{BigDecimal, ToPrimitive};
+use diesel::dsl::{count_distinct, count_star, sql};
+use diesel::pg::{Pg, PgConnection};
+use diesel::prelude::*;
+use diesel::sql_types::{Numeric, Uuid as SqlUuid};
+use crate::models::{
+    minor_units, ActiveCounts, AttributeFacet, CostBucket, Facets, Money, ProductFilters, ValueCount, COST_BUCKETS
+};
+use crate::schema::{product_variants, products, variants};
+use crate::services::product_queries::apply_filters;
+use tracing::debug;
+
+// Diesel only ships min/max for types it knows how to order, which excludes NUMERIC
+diesel::define_sql_function! {
+    #[aggregate]
+    #[sql_name = "min"]
+    fn lowest(amount: Numeric) -> Nullable<Numeric>
+}
+diesel::define_sql_function! {
+    #[aggregate]
+    #[sql_name = "max"]
+    fn highest(amount: Numeric) -> Nullable<Numeric>
+}
+
+diesel::allow_columns_to_appear_in_same_group_by_clause!(variants::name, product_variants::value);
+
+// Ids of the products matching the filters, for use as a subselect
+fn matching_ids(filters: &ProductFilters) -> products::BoxedQuery<'_, Pg, SqlUuid> {
+    apply_filters(products::table.into_boxed(), filters).select(products::id)
+}
+
+// (variant name, value, products) for every variant value of the matching products
+fn count_values(conn: &mut PgConnection, filters: &ProductFilters) -> QueryResult<Vec<(String, String, i64)>> {
+    product_variants::table
+        .inner_join(variants::table)
+        .filter(product_variants::product_id.eq_any(matching_ids(filters)))
+        .filter(product_variants::value.is_not_null())
+        .group_by((variants::name, product_variants::value))
+        .select((
+            variants::name,
+            product_variants::value.assume_not_null(),
+            count_distinct(product_variants::product_id)
+        ))
+        .load(conn)
+}
+
+fn attribute_facets(conn: &mut PgConnection, filters: &ProductFilters) -> QueryResult<Vec<AttributeFacet>> {
+    // Attributes nobody filters on are counted against the full filter set
+    let mut rows: Vec<(String, String, i64)> = count_values(conn, filters)?
+        .into_iter()
+        .filter(|(name, _, _)| !filters.attributes.contains_key(&name.to_lowercase()))
+        .collect();
+
+    // A filtered attribute is counted as if its own filter wasn't there
+    for name in filters.attributes.keys() {
+        let mut without_own = filters.clone();
+        without_own.attributes.remove(name);
+        rows.extend(
+            count_values(conn, &without_own)?
+                .into_iter()
+                .filter(|(variant, _, _)| variant.to_lowercase() == *name)
+        );
+    }
+
+    let mut grouped: BTreeMap<String, Vec<ValueCount>> = BTreeMap::new();
+    for (name, value, count) in rows {
+        grouped.entry(name).or_default().push(ValueCount { value, count });
+    }
+
+    Ok(grouped
+        .into_iter()
+        .map(|(name, mut values)| {
+            values.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+            AttributeFacet { name, values }
+        })
+        .collect())
+}
+
+// Round bucket width (1, 2 or 5 times a power of ten) splitting the range into about COST_BUCKETS
+fn bucket_width(low: &BigDecimal, high: &BigDecimal) -> i64 {
+    let raw = ((high - low).to_f64().unwrap_or_default() / COST_BUCKETS as f64).max(1.0);
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let width = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|m| m * magnitude)
+        .find(|w| *w >= raw)
+        .unwrap_or(raw);
+    width.ceil() as i64
+}
+
+fn cost_facets(conn: &mut PgConnection, filters: &ProductFilters) -> QueryResult<Vec<CostBucket>> {
+    let without_own = ProductFilters { cost_ge: None, cost_le: None, ..filters.clone() };
+
+    let ranges: Vec<(String, Option<BigDecimal>, Option<BigDecimal>)> = products::table
+        .filter(products::id.eq_any(matching_ids(&without_own)))
+        .group_by(products::currency)
+        .select((products::currency, lowest(products::cost), highest(products::cost)))
+        .order(products::currency.asc())
+        .load(conn)?;
+
+    let mut buckets = Vec::new();
+    for (currency, low, high) in ranges {
+        let (Some(low), Some(high)) = (low, high) else {
+            continue;
+        };
+        let width = bucket_width(&low, &high);
+        debug!(currency = %currency, width = width, "Counting cost buckets");
+
+        // The width is computed above, never taken from the request
+        let bucket = sql::<Numeric>(&format!("floor(products.cost / {})", width));
+        let counts: Vec<(BigDecimal, i64)> = products::table
+            .filter(products::id.eq_any(matching_ids(&without_own)))
+            .filter(products::currency.eq(&currency))
+            .group_by(bucket.clone())
+            .select((bucket.clone(), count_star()))
+            .order(bucket.asc())
+            .load(conn)?;
+
+        let units = minor_units(&currency);
+        let money = |amount: BigDecimal| Money { amount: amount.with_scale(units), currency: currency.clone() };
+        for (index, count) in counts {
+            let min = index * BigDecimal::from(width);
+            let max = &min + BigDecimal::from(width);
+            buckets.push(CostBucket { min: money(min), max: money(max), count });
+        }
+    }
+
+    Ok(buckets)
+}
+
+fn active_facets(conn: &mut PgConnection, filters: &ProductFilters) -> QueryResult<ActiveCounts> {
+    let without_own = ProductFilters { is_active: None, ..filters.clone() };
+
+    let counts: Vec<(bool, i64)> = products::table
+        .filter(products::id.eq_any(matching_ids(&without_own)))
+        .group_by(products::active)
+        .select((products::active, count_star()))
+        .load(conn)?;
+
+    let mut active = ActiveCounts::default();
+    for (is_active, count) in counts {
+        if is_active {
+            active.active = count;
+        } else {
+            active.inactive = count;
+        }
+    }
+    Ok(active)
+}
+
+pub fn load_facets(conn: &mut PgConnection, filters: &ProductFilters) -> QueryResult<Facets> {
+    debug!(attribute_filters = filters.attributes.len(), "Computing product facets");
+
+    Ok(Facets {
+        attributes: attribute_facets(conn, filters)?,
+        cost: cost_facets(conn, filters)?,
+        active: active_facets(conn, filters)?
+    })
+}
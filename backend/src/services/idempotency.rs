@@ -0,0 +1,144 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use crate::config::Database;
+use crate::models::{IdempotencyClaim, IdempotencyRecord, NewIdempotencyKey, StoredResponse};
+use crate::schema::idempotency_keys;
+use anyhow::Result;
+use tracing::{info, warn, error, instrument, debug};
+
+pub struct IdempotencyService {
+    pub db: Database,
+    pub ttl: Duration,
+    // How long a claim holds the key before a retry may take it over
+    pub lease: Duration
+}
+
+impl IdempotencyService {
+    pub fn new(db: Database, ttl: Duration, lease: Duration) -> Self {
+        Self { db, ttl, lease }
+    }
+
+    // Reserves the key for this request, or tells how an earlier use of it went.
+    // The insert is what serializes concurrent retries: only one of them wins it.
+    // A key still without a response once its lease is over was left behind by a
+    // server that went down mid-request, so it is handed to this request instead.
+    #[instrument(skip(self, request_hash))]
+    pub async fn claim(&self, key: &str, request_hash: &str) -> Result<IdempotencyClaim> {
+        let (key, request_hash, ttl, lease) = (key.to_string(), request_hash.to_string(), self.ttl, self.lease);
+
+        let result = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
+            let expired = diesel::delete(idempotency_keys::table.filter(idempotency_keys::expires_at.lt(Utc::now())))
+                .execute(conn)?;
+            debug!(expired = expired, "Removed expired idempotency keys");
+
+            let inserted = diesel::insert_into(idempotency_keys::table)
+                .values(NewIdempotencyKey {
+                    key: key.clone(),
+                    request_hash: request_hash.clone(),
+                    expires_at: Utc::now() + ttl,
+                    locked_until: Utc::now() + lease
+                })
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            if inserted > 0 {
+                return Ok(IdempotencyClaim::Started);
+            }
+
+            let taken_over = diesel::update(
+                idempotency_keys::table
+                    .find(&key)
+                    .filter(idempotency_keys::status_code.is_null())
+                    .filter(idempotency_keys::locked_until.lt(Utc::now()))
+            )
+                .set((
+                    idempotency_keys::request_hash.eq(&request_hash),
+                    idempotency_keys::expires_at.eq(Utc::now() + ttl),
+                    idempotency_keys::locked_until.eq(Utc::now() + lease)
+                ))
+                .execute(conn)?;
+            if taken_over > 0 {
+                warn!("Took over idempotency key whose lease ran out");
+                return Ok(IdempotencyClaim::Started);
+            }
+
+            let record = idempotency_keys::table
+                .find(&key)
+                .select(IdempotencyRecord::as_select())
+                .first(conn)?;
+
+            if record.request_hash != request_hash {
+                return Ok(IdempotencyClaim::Mismatch);
+            }
+            Ok(match StoredResponse::from_record(record) {
+                Some(response) => IdempotencyClaim::Replay(response),
+                None => IdempotencyClaim::InProgress
+            })
+        })).await;
+
+        match &result {
+            Ok(IdempotencyClaim::Started) => info!("Idempotency key claimed"),
+            Ok(IdempotencyClaim::Replay(response)) => info!(status = response.status, "Replaying stored response"),
+            Ok(IdempotencyClaim::InProgress) => warn!("Idempotency key is still in use"),
+            Ok(IdempotencyClaim::Mismatch) => warn!("Idempotency key reused with a different request"),
+            Err(e) => error!(error = %e, "Database error while claiming idempotency key")
+        }
+        result
+    }
+
+    #[instrument(skip(self, response), fields(status = response.status))]
+    pub async fn complete(&self, key: &str, response: &StoredResponse) -> Result<()> {
+        let (key, status, headers, body) = (
+            key.to_string(),
+            response.status as i16,
+            response.headers_json(),
+            response.body.clone()
+        );
+
+        let result = self.db.run(move |conn| {
+            Ok(diesel::update(idempotency_keys::table.find(key))
+                .set((
+                    idempotency_keys::status_code.eq(status),
+                    idempotency_keys::response_headers.eq(headers),
+                    idempotency_keys::response_body.eq(body)
+                ))
+                .execute(conn)?)
+        }).await;
+
+        match result {
+            Ok(_) => {
+                info!("Stored response for idempotency key");
+                Ok(())
+            }
+            Err(e) => {
+                error!(error = %e, "Database error while storing idempotent response");
+                Err(e)
+            }
+        }
+    }
+
+    // Gives the key back after a failure the client should be able to retry
+    #[instrument(skip(self))]
+    pub async fn release(&self, key: &str) -> Result<()> {
+        let key = key.to_string();
+
+        let result = self.db.run(move |conn| {
+            Ok(diesel::delete(
+                idempotency_keys::table
+                    .find(key)
+                    .filter(idempotency_keys::status_code.is_null())
+            )
+                .execute(conn)?)
+        }).await;
+
+        match result {
+            Ok(_) => {
+                info!("Released idempotency key");
+                Ok(())
+            }
+            Err(e) => {
+                error!(error = %e, "Database error while releasing idempotency key");
+                Err(e)
+            }
+        }
+    }
+}
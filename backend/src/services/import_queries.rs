@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use uuid::Uuid;
+use crate::models::{
+    describe_field_errors, ImportAction, ImportRow, ImportRowResult, NewCompleteProduct, NewProduct,
+    NewProductVariant, NewVariant, NewVariantValue, ParsedRow, Product, ProductChangeset, ProductUpdates,
+    RevisionAction, Validate, VariantRef, VariantWithValues
+};
+use crate::schema::{product_variants, products};
+use crate::services::insert_queries::{insert_prepared, prepare_products};
+use crate::services::product_queries::load_variant_groups;
+use crate::services::revision_queries::{record_revisions, INSERT_CHUNK_ROWS};
+use crate::services::variant_queries::{check_allowed, VariantLookup};
+use crate::traits::responses::AppError;
+use anyhow::Result;
+use tracing::debug;
+
+fn variant_ref(name: &str) -> VariantRef {
+    VariantRef::Name(NewVariant { name: name.to_string(), allowed_values: None })
+}
+
+// Values the product currently has for the named variant, sorted for comparison
+fn current_values(groups: Option<&Vec<VariantWithValues>>, variant_id: Uuid) -> Vec<String> {
+    let mut values: Vec<String> = groups
+        .into_iter()
+        .flatten()
+        .filter(|group| group.variant.id == variant_id)
+        .flat_map(|group| group.values.iter().filter_map(|v| v.value.clone()))
+        .collect();
+    values.sort();
+    values
+}
+
+// Upserts the parsed rows: rows matching a live product update it, the others
+// create a new one. Returns the outcome of every row in file order.
+pub fn import_rows(
+    conn: &mut PgConnection,
+    rows: Vec<ParsedRow>,
+    changed_by: Option<&str>
+) -> Result<Vec<ImportRowResult>> {
+    let mut results: Vec<Option<ImportRowResult>> = vec![None; rows.len()];
+    let mut valid: Vec<(usize, u64, ImportRow)> = Vec::new();
+    let mut first_lines: HashMap<String, u64> = HashMap::new();
+    for (index, (line, row)) in rows.into_iter().enumerate() {
+        match row {
+            Ok(row) => match (row.validated(), first_lines.get(&row.key())) {
+                (Err(errors), _) => results[index] = Some(ImportRowResult::failed(line, describe_field_errors(&errors))),
+                (Ok(()), Some(first)) => results[index] = Some(ImportRowResult::failed(line, format!("Same product as line {}", first))),
+                (Ok(()), None) => {
+                    first_lines.insert(row.key(), line);
+                    valid.push((index, line, row));
+                }
+            },
+            Err(e) => results[index] = Some(ImportRowResult::failed(line, e))
+        }
+    }
+
+    // Existing products, by id including deleted ones and by name among the live ones
+    let ids: Vec<Uuid> = valid.iter().filter_map(|(_, _, row)| row.id).collect();
+    let names: Vec<&String> = valid.iter().filter(|(_, _, row)| row.id.is_none()).map(|(_, _, row)| &row.name).collect();
+    let by_id: HashMap<Uuid, Product> = products::table
+        .filter(products::id.eq_any(&ids))
+        .select(Product::as_select())
+        .load::<Product>(conn)?
+        .into_iter()
+        .map(|product| (product.id, product))
+        .collect();
+    let mut by_name: HashMap<String, Vec<Product>> = HashMap::new();
+    for product in products::table
+        .filter(products::name.eq_any(names))
+        .filter(products::deleted_at.is_null())
+        .select(Product::as_select())
+        .load::<Product>(conn)? {
+        by_name.entry(product.name.clone()).or_default().push(product);
+    }
+
+    let mut creates: Vec<(usize, u64, ImportRow)> = Vec::new();
+    let mut updates: Vec<(usize, u64, ImportRow, Product)> = Vec::new();
+    for (index, line, row) in valid {
+        let existing = match row.id {
+            Some(id) => by_id.get(&id).cloned().map(Ok),
+            None => match by_name.get(&row.name).map(Vec::as_slice) {
+                None | Some([]) => None,
+                Some([product]) => Some(Ok(product.clone())),
+                Some(matches) => Some(Err(format!(
+                    "{} products are named '{}', add an id column to pick one", matches.len(), row.name
+                )))
+            }
+        };
+        match existing {
+            None => creates.push((index, line, row)),
+            Some(Ok(product)) if product.deleted_at.is_some() => {
+                results[index] = Some(ImportRowResult::failed(line, format!("Product {} is deleted, restore it first", product.id)));
+            }
+            Some(Ok(product)) => updates.push((index, line, row, product)),
+            Some(Err(e)) => results[index] = Some(ImportRowResult::failed(line, e))
+        }
+    }
+    debug!(creates = creates.len(), updates = updates.len(), "Matched import rows to products");
+
+    let items: Vec<NewCompleteProduct> = creates
+        .iter()
+        .map(|(_, _, row)| NewCompleteProduct {
+            product: NewProduct {
+                id: row.id,
+                name: row.name.clone(),
+                cost: row.cost.clone(),
+                active: row.active.unwrap_or(true)
+            },
+            variants: row.variants
+                .iter()
+                .map(|(name, values)| NewVariantValue {
+                    variant: variant_ref(name),
+                    values: values.iter().cloned().map(Some).collect()
+                })
+                .collect()
+        })
+        .collect();
+    let mut prepared = Vec::new();
+    let mut created_rows = Vec::new();
+    for ((index, line, _), item) in creates.into_iter().zip(prepare_products(conn, items)?) {
+        match item {
+            Ok(item) => {
+                prepared.push(item);
+                created_rows.push((index, line));
+            }
+            Err(e) => results[index] = Some(ImportRowResult::failed(line, AppError::from(e)))
+        }
+    }
+    for ((index, line), product) in created_rows.into_iter().zip(insert_prepared(conn, prepared, changed_by)?) {
+        results[index] = Some(ImportRowResult::done(line, product.id, ImportAction::Created));
+    }
+
+    update_rows(conn, updates, changed_by, &mut results)?;
+
+    Ok(results.into_iter().flatten().collect())
+}
+
+// Applies the rows matched to existing products, skipping those that wouldn't change
+// anything. Each product is its own UPDATE since every row sets different values.
+fn update_rows(
+    conn: &mut PgConnection,
+    updates: Vec<(usize, u64, ImportRow, Product)>,
+    changed_by: Option<&str>,
+    results: &mut [Option<ImportRowResult>]
+) -> Result<()> {
+    let refs: Vec<VariantRef> = updates
+        .iter()
+        .flat_map(|(_, _, row, _)| row.variants.iter().map(|(name, _)| variant_ref(name)))
+        .collect();
+    let lookup = VariantLookup::load(conn, &refs)?;
+    let product_ids: Vec<Uuid> = updates.iter().map(|(_, _, _, product)| product.id).collect();
+    let groups = load_variant_groups(conn, &product_ids)?;
+
+    let mut updated: Vec<Product> = Vec::new();
+    let mut values: Vec<NewProductVariant> = Vec::new();
+    for (index, line, row, product) in updates {
+        let mut replaced: Vec<(Uuid, Vec<String>)> = Vec::new();
+        let mut failure = None;
+        for (name, new_values) in &row.variants {
+            let checked = lookup
+                .get(&variant_ref(name))
+                .and_then(|variant| check_allowed(variant, new_values.iter().map(String::as_str)).map(|_| variant));
+            match checked {
+                Ok(variant) => {
+                    let mut sorted = new_values.clone();
+                    sorted.sort();
+                    if current_values(groups.get(&product.id), variant.id) != sorted {
+                        replaced.push((variant.id, new_values.clone()));
+                    }
+                }
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                }
+            }
+        }
+        if let Some(e) = failure {
+            results[index] = Some(ImportRowResult::failed(line, AppError::from(e)));
+            continue;
+        }
+
+        let fields_changed = row.name != product.name ||
+            row.cost != product.cost ||
+            row.active.is_some_and(|active| active != product.active);
+        if !fields_changed && replaced.is_empty() {
+            results[index] = Some(ImportRowResult::done(line, product.id, ImportAction::Unchanged));
+            continue;
+        }
+
+        let product = diesel::update(products::table.find(product.id))
+            .set((
+                ProductChangeset::from(ProductUpdates { name: Some(row.name), cost: Some(row.cost), active: row.active }),
+                products::version.eq(products::version + 1),
+                products::updated_at.eq(diesel::dsl::now)
+            ))
+            .returning(Product::as_select())
+            .get_result(conn)?;
+
+        for (variant_id, new_values) in replaced {
+            diesel::delete(
+                product_variants::table
+                    .filter(product_variants::product_id.eq(product.id))
+                    .filter(product_variants::variant_id.eq(variant_id))
+            )
+            .execute(conn)?;
+            values.extend(
+                new_values
+                    .into_iter()
+                    .map(|value| NewProductVariant { variant_id, product_id: product.id, value })
+            );
+        }
+
+        results[index] = Some(ImportRowResult::done(line, product.id, ImportAction::Updated));
+        updated.push(product);
+    }
+
+    for chunk in values.chunks(INSERT_CHUNK_ROWS) {
+        diesel::insert_into(product_variants::table)
+            .values(chunk)
+            .execute(conn)?;
+    }
+    record_revisions(conn, &updated, RevisionAction::Update, changed_by)?;
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use uuid::Uuid;
+use crate::models::{
+    NewCompleteProduct, NewProduct, NewProductVariant, Product, RevisionAction, Validate
+};
+use crate::schema::{product_variants, products};
+use crate::services::revision_queries::{record_revisions, INSERT_CHUNK_ROWS};
+use crate::services::variant_queries::{check_allowed, VariantLookup};
+use crate::traits::responses::AppError;
+use anyhow::Result;
+use tracing::debug;
+
+// A product that passed its checks, with the id fixed up front so its variant
+// values can be built before anything is inserted
+#[derive(Debug, Clone)]
+pub struct PreparedProduct {
+    pub product: NewProduct,
+    pub values: Vec<NewProductVariant>
+}
+
+// Resolves the variants of all items together and checks each item on its own,
+// so one bad item doesn't hide the state of the others
+pub fn prepare_products(
+    conn: &mut PgConnection,
+    items: Vec<NewCompleteProduct>
+) -> QueryResult<Vec<Result<PreparedProduct>>> {
+    let lookup = VariantLookup::load(
+        conn,
+        items.iter().flat_map(|item| item.variants.iter().map(|v| &v.variant))
+    )?;
+
+    Ok(items.into_iter().map(|item| prepare(&lookup, item)).collect())
+}
+
+fn prepare(lookup: &VariantLookup, item: NewCompleteProduct) -> Result<PreparedProduct> {
+    if let Err(errors) = item.validated() {
+        return Err(AppError::InvalidFields(errors).into());
+    }
+    let NewCompleteProduct { mut product, variants } = item;
+    let product_id = *product.id.get_or_insert_with(Uuid::new_v4);
+
+    let mut values = Vec::new();
+    for variant_value in &variants {
+        let variant = lookup.get(&variant_value.variant)?;
+        let given: Vec<&String> = variant_value.values.iter().flatten().collect();
+        check_allowed(variant, given.iter().map(|v| v.as_str()))?;
+        values.extend(
+            given
+                .into_iter()
+                .map(|value| NewProductVariant { variant_id: variant.id, product_id, value: value.clone() })
+        );
+    }
+
+    Ok(PreparedProduct { product, values })
+}
+
+// Writes the products, their variant values and their first revisions with one
+// insert per table. Products come back in the order they were given.
+pub fn insert_prepared(
+    conn: &mut PgConnection,
+    prepared: Vec<PreparedProduct>,
+    changed_by: Option<&str>
+) -> QueryResult<Vec<Product>> {
+    if prepared.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (new_products, values): (Vec<NewProduct>, Vec<Vec<NewProductVariant>>) = prepared
+        .into_iter()
+        .map(|p| (p.product, p.values))
+        .unzip();
+    let positions: HashMap<Uuid, usize> = new_products
+        .iter()
+        .enumerate()
+        .filter_map(|(index, product)| Some((product.id?, index)))
+        .collect();
+    debug!(product_count = new_products.len(), "Inserting products in one batch");
+
+    let mut inserted = diesel::insert_into(products::table)
+        .values(&new_products)
+        .returning(Product::as_select())
+        .get_results::<Product>(conn)?;
+    // RETURNING doesn't promise to keep the order of VALUES
+    inserted.sort_by_key(|product| positions.get(&product.id).copied());
+
+    let values: Vec<NewProductVariant> = values.into_iter().flatten().collect();
+    for chunk in values.chunks(INSERT_CHUNK_ROWS) {
+        diesel::insert_into(product_variants::table)
+            .values(chunk)
+            .execute(conn)?;
+    }
+
+    record_revisions(conn, &inserted, RevisionAction::Create, changed_by)?;
+    Ok(inserted)
+}
@@ -1,2 +1,12 @@
+mod facet_queries;
+mod import_queries;
+mod insert_queries;
+mod product_queries;
+mod revision_queries;
+mod variant_queries;
+pub mod idempotency;
 pub mod products;
-pub use products::*;
\ No newline at end of file
+pub mod variants;
+pub use idempotency::*;
+pub use products::*;
+pub use variants::*;
\ No newline at end of file
@@ -0,0 +1,307 @@
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use bigdecimal::BigDecimal;
+use diesel::dsl::case_when;
+use diesel::sql_types::{Bool, Float, Integer, Numeric, SingleValue, SqlType, Text};
+use diesel_full_text_search::configuration::TsConfigurationByName;
+use diesel_full_text_search::{to_tsquery_with_search_config, ts_rank, TsVectorExtensions};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use diesel::pg::PgConnection;
+use uuid::Uuid;
+use crate::models::{
+    prefix_term, Cursor, IfMatch, THREE_DECIMAL_CURRENCIES, ZERO_DECIMAL_CURRENCIES, Product, ProductFilters, ProductVariant, SortDirection, SortField, SortOrder, SortValue, Variant,
+    VariantValue, VariantWithValues
+};
+use crate::schema::{product_revisions, product_variants, products, variants};
+use crate::traits::responses::AppError;
+use anyhow::Result;
+use tracing::debug;
+
+// No stemming, so brand and model names are matched as typed
+const SEARCH_CONFIG: TsConfigurationByName = TsConfigurationByName("simple");
+
+diesel::define_sql_function!(fn word_similarity(needle: Text, haystack: Text) -> Float);
+diesel::define_sql_function!(fn lower<T: SqlType + SingleValue>(text: T) -> T);
+diesel::define_sql_function!(fn btrim(text: Text) -> Text);
+diesel::define_sql_function!(fn round(value: Numeric, scale: Integer) -> Numeric);
+// pg_trgm: true when the needle is similar enough to some word sequence of the haystack
+diesel::infix_operator!(WordSimilarTo, " <% ", backend: Pg);
+
+pub type ProductPredicate = Box<dyn BoxableExpression<products::table, Pg, SqlType = Bool>>;
+pub type CostExpression = Box<dyn BoxableExpression<products::table, Pg, SqlType = Numeric>>;
+pub type RankExpression = Box<dyn BoxableExpression<products::table, Pg, SqlType = Float>>;
+
+// Typo-tolerant name match backed by the trigram index
+pub fn name_resembles(needle: String) -> ProductPredicate {
+    Box::new(WordSimilarTo::new(needle.into_sql::<Text>(), products::name))
+}
+
+pub fn name_similarity(needle: String) -> RankExpression {
+    Box::new(word_similarity(needle, products::name))
+}
+
+// Products whose name matches the whole search query
+fn name_matches(search_query: String) -> ProductPredicate {
+    Box::new(products::search_vector.matches(to_tsquery_with_search_config(SEARCH_CONFIG, search_query)))
+}
+
+// Every term has to match either the product name or one of its variant values,
+// so "red 42" finds a red shoe that comes in size 42
+fn name_or_variant_matches(terms: &[String]) -> Option<ProductPredicate> {
+    terms.iter().map(|term| {
+        let in_variants = product_variants::table
+            .select(product_variants::product_id)
+            .filter(product_variants::search_vector.matches(
+                to_tsquery_with_search_config(SEARCH_CONFIG, prefix_term(term))
+            ));
+        let predicate: ProductPredicate = Box::new(
+            name_matches(prefix_term(term)).or(products::id.eq_any(in_variants))
+        );
+        predicate
+    }).reduce(|all, next| Box::new(all.and(next)))
+}
+
+// Products with a value of the named variant among the given ones, both compared case-insensitively
+fn has_attribute(name: &str, values: &[String]) -> ProductPredicate {
+    let with_value = product_variants::table
+        .inner_join(variants::table)
+        .select(product_variants::product_id)
+        .filter(lower(variants::name).eq(name.to_string()))
+        .filter(lower(product_variants::value).eq_any(values.to_vec()));
+    Box::new(products::id.eq_any(with_value))
+}
+
+// Narrows a products query down to the rows matching the given filters
+pub fn apply_filters<'a>(
+    mut query: products::BoxedQuery<'a, Pg>,
+    filters: &ProductFilters
+) -> products::BoxedQuery<'a, Pg> {
+    if !filters.include_deleted.unwrap_or(false) {
+        query = query.filter(products::deleted_at.is_null());
+    }
+
+    if let Some(name_pattern) = filters.name_pattern() {
+        debug!(filter_name = ?filters.name, "Applying name filter");
+        query = query.filter(products::name.ilike(name_pattern));
+    }
+
+    if let Some(min_cost) = &filters.cost_ge {
+        debug!(min_cost = %min_cost, "Applying minimum cost filter");
+        query = query.filter(products::cost.ge(min_cost.clone()));
+    }
+
+    if let Some(max_cost) = &filters.cost_le {
+        debug!(max_cost = %max_cost, "Applying maximum cost filter");
+        query = query.filter(products::cost.le(max_cost.clone()));
+    }
+
+    if let Some(currency) = &filters.currency {
+        debug!(currency = %currency, "Applying currency filter");
+        query = query.filter(products::currency.eq(currency.clone()));
+    }
+
+    if let Some(is_active_filter) = filters.is_active {
+        debug!(is_active = is_active_filter, "Applying active status filter");
+        query = query.filter(products::active.eq(is_active_filter));
+    }
+
+    if let Some(created_after) = filters.created_after {
+        debug!(created_after = %created_after, "Applying created after filter");
+        query = query.filter(products::created_at.gt(created_after));
+    }
+
+    if let Some(updated_after) = filters.updated_after {
+        debug!(updated_after = %updated_after, "Applying updated after filter");
+        query = query.filter(products::updated_at.gt(updated_after));
+    }
+
+    for (name, values) in &filters.attributes {
+        debug!(attribute = %name, values = ?values, "Applying attribute filter");
+        query = query.filter(has_attribute(name, values));
+    }
+
+    if let Some(fuzzy_query) = filters.fuzzy_query() {
+        debug!(fuzzy_query = %fuzzy_query, "Applying fuzzy name search");
+        query = query.filter(name_resembles(fuzzy_query));
+    } else if let Some(search_query) = filters.search_query() {
+        let include_variants = filters.search_variants.unwrap_or(false);
+        debug!(search_query = %search_query, include_variants = include_variants, "Applying full-text search");
+        if include_variants {
+            if let Some(predicate) = name_or_variant_matches(&filters.search_terms()) {
+                query = query.filter(predicate);
+            }
+        } else {
+            query = query.filter(name_matches(search_query));
+        }
+    }
+
+    query
+}
+
+// `cost * factor + offset`, rounded to the minor units of each row's currency
+pub fn adjusted_cost(factor: BigDecimal, offset: BigDecimal) -> CostExpression {
+    let scale = case_when(products::currency.eq_any(ZERO_DECIMAL_CURRENCIES.to_vec()), 0.into_sql::<Integer>())
+        .when(products::currency.eq_any(THREE_DECIMAL_CURRENCIES.to_vec()), 3.into_sql::<Integer>())
+        .otherwise(2.into_sql::<Integer>());
+    Box::new(round(products::cost * factor + offset, scale))
+}
+
+// Search rank of each row, or a constant when there is nothing to rank by
+pub fn rank_expr(filters: &ProductFilters) -> RankExpression {
+    if let Some(fuzzy_query) = filters.fuzzy_query() {
+        return name_similarity(fuzzy_query);
+    }
+
+    match filters.search_query() {
+        Some(search_query) => Box::new(ts_rank(
+            products::search_vector,
+            to_tsquery_with_search_config(SEARCH_CONFIG, search_query)
+        )),
+        None => Box::new(0.0f32.into_sql::<Float>())
+    }
+}
+
+// Orders by the requested fields, using the id as a tie-breaker so keyset pages are stable
+pub fn apply_sort<'a>(
+    mut query: products::BoxedQuery<'a, Pg>,
+    orders: &[SortOrder],
+    filters: &ProductFilters
+) -> products::BoxedQuery<'a, Pg> {
+    for order in orders {
+        debug!(field = ?order.field, direction = ?order.direction, "Applying sort order");
+        query = match (order.field, order.direction) {
+            (SortField::Name, SortDirection::Asc) => query.then_order_by(products::name.asc()),
+            (SortField::Name, SortDirection::Desc) => query.then_order_by(products::name.desc()),
+            (SortField::Cost, SortDirection::Asc) => query.then_order_by(products::cost.asc()),
+            (SortField::Cost, SortDirection::Desc) => query.then_order_by(products::cost.desc()),
+            (SortField::Active, SortDirection::Asc) => query.then_order_by(products::active.asc()),
+            (SortField::Active, SortDirection::Desc) => query.then_order_by(products::active.desc()),
+            (SortField::CreatedAt, SortDirection::Asc) => query.then_order_by(products::created_at.asc()),
+            (SortField::CreatedAt, SortDirection::Desc) => query.then_order_by(products::created_at.desc()),
+            (SortField::UpdatedAt, SortDirection::Asc) => query.then_order_by(products::updated_at.asc()),
+            (SortField::UpdatedAt, SortDirection::Desc) => query.then_order_by(products::updated_at.desc()),
+            (SortField::Relevance, SortDirection::Asc) => query.then_order_by(rank_expr(filters).asc()),
+            (SortField::Relevance, SortDirection::Desc) => query.then_order_by(rank_expr(filters).desc()),
+        };
+    }
+
+    query.then_order_by(products::id.asc())
+}
+
+fn sort_key_mismatch() -> anyhow::Error {
+    AppError::Validation("Cursor does not match the requested sort order".to_string()).into()
+}
+
+// Rows strictly after the cursor value in the given sort direction
+fn after_key(order: &SortOrder, key: &SortValue, filters: &ProductFilters) -> Result<ProductPredicate> {
+    let descending = order.direction == SortDirection::Desc;
+    Ok(match (order.field, key) {
+        (SortField::Name, SortValue::Text(v)) if descending => Box::new(products::name.lt(v.clone())),
+        (SortField::Name, SortValue::Text(v)) => Box::new(products::name.gt(v.clone())),
+        (SortField::Cost, SortValue::Decimal(v)) if descending => Box::new(products::cost.lt(v.clone())),
+        (SortField::Cost, SortValue::Decimal(v)) => Box::new(products::cost.gt(v.clone())),
+        (SortField::Active, SortValue::Bool(v)) if descending => Box::new(products::active.lt(*v)),
+        (SortField::Active, SortValue::Bool(v)) => Box::new(products::active.gt(*v)),
+        (SortField::CreatedAt, SortValue::Timestamp(v)) if descending => Box::new(products::created_at.lt(*v)),
+        (SortField::CreatedAt, SortValue::Timestamp(v)) => Box::new(products::created_at.gt(*v)),
+        (SortField::UpdatedAt, SortValue::Timestamp(v)) if descending => Box::new(products::updated_at.lt(*v)),
+        (SortField::UpdatedAt, SortValue::Timestamp(v)) => Box::new(products::updated_at.gt(*v)),
+        (SortField::Relevance, SortValue::Rank(v)) if descending => Box::new(rank_expr(filters).lt(*v)),
+        (SortField::Relevance, SortValue::Rank(v)) => Box::new(rank_expr(filters).gt(*v)),
+        _ => return Err(sort_key_mismatch())
+    })
+}
+
+fn equal_key(field: SortField, key: &SortValue, filters: &ProductFilters) -> Result<ProductPredicate> {
+    Ok(match (field, key) {
+        (SortField::Name, SortValue::Text(v)) => Box::new(products::name.eq(v.clone())),
+        (SortField::Cost, SortValue::Decimal(v)) => Box::new(products::cost.eq(v.clone())),
+        (SortField::Active, SortValue::Bool(v)) => Box::new(products::active.eq(*v)),
+        (SortField::CreatedAt, SortValue::Timestamp(v)) => Box::new(products::created_at.eq(*v)),
+        (SortField::UpdatedAt, SortValue::Timestamp(v)) => Box::new(products::updated_at.eq(*v)),
+        (SortField::Relevance, SortValue::Rank(v)) => Box::new(rank_expr(filters).eq(*v)),
+        _ => return Err(sort_key_mismatch())
+    })
+}
+
+// Expands `(k1, k2, id) > (v1, v2, cursor_id)` honouring each field's direction:
+// k1 after v1 OR (k1 = v1 AND (k2 after v2 OR (k2 = v2 AND id > cursor_id)))
+pub fn keyset_predicate(
+    orders: &[SortOrder],
+    cursor: &Cursor,
+    filters: &ProductFilters
+) -> Result<ProductPredicate> {
+    if orders.len() != cursor.keys.len() {
+        return Err(sort_key_mismatch());
+    }
+
+    let mut predicate: ProductPredicate = Box::new(products::id.gt(cursor.id));
+    for (order, key) in orders.iter().zip(&cursor.keys).rev() {
+        predicate = Box::new(
+            after_key(order, key, filters)?.or(equal_key(order.field, key, filters)?.and(predicate))
+        );
+    }
+
+    Ok(predicate)
+}
+
+// The newest change to any product. Deletes bump updated_at and purges leave a
+// revision behind, so products leaving a page move it forward too.
+pub fn catalog_last_modified(conn: &mut PgConnection) -> QueryResult<Option<DateTime<Utc>>> {
+    let updated: Option<DateTime<Utc>> = products::table
+        .select(diesel::dsl::max(products::updated_at))
+        .get_result(conn)?;
+    let revised: Option<DateTime<Utc>> = product_revisions::table
+        .select(diesel::dsl::max(product_revisions::changed_at))
+        .get_result(conn)?;
+    Ok(updated.max(revised))
+}
+
+// Locks the live product for the rest of the transaction and checks it against If-Match.
+// Every write to a product or its variant values takes this lock first, so a full
+// replacement never works from variant values another request is changing.
+pub fn lock_product(conn: &mut PgConnection, product_id: Uuid, if_match: Option<&IfMatch>) -> Result<Option<Product>> {
+    let product: Option<Product> = products::table
+        .filter(products::id.eq(product_id))
+        .filter(products::deleted_at.is_null())
+        .select(Product::as_select())
+        .for_update()
+        .first(conn)
+        .optional()?;
+
+    match (product, if_match) {
+        (Some(product), Some(expected)) if !expected.matches(product.version) => {
+            debug!(product_id = %product_id, current_version = product.version, "If-Match precondition failed");
+            Err(AppError::PreconditionFailed.into())
+        }
+        (product, _) => Ok(product)
+    }
+}
+
+// Variants of the given products in one query, with the values grouped under each variant
+pub fn load_variant_groups(
+    conn: &mut PgConnection,
+    product_ids: &[Uuid]
+) -> QueryResult<HashMap<Uuid, Vec<VariantWithValues>>> {
+    debug!(product_count = product_ids.len(), "Batch loading product variants");
+
+    let rows = product_variants::table
+        .inner_join(variants::table)
+        .filter(product_variants::product_id.eq_any(product_ids))
+        .order((variants::name.asc(), product_variants::value.asc(), product_variants::id.asc()))
+        .select((ProductVariant::as_select(), Variant::as_select()))
+        .load::<(ProductVariant, Variant)>(conn)?;
+
+    let mut grouped: HashMap<Uuid, Vec<VariantWithValues>> = HashMap::new();
+    for (product_variant, variant) in rows {
+        let groups = grouped.entry(product_variant.product_id).or_default();
+        let value = VariantValue { id: product_variant.id, value: product_variant.value };
+        match groups.iter_mut().find(|g| g.variant.id == variant.id) {
+            Some(group) => group.values.push(value),
+            None => groups.push(VariantWithValues { variant, values: vec![value] })
+        }
+    }
+
+    Ok(grouped)
+}
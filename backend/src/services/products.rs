@@ -1,55 +1,73 @@
+use std::collections::HashMap;
+use bigdecimal::BigDecimal;
 use diesel::prelude::*;
 use diesel::{ExpressionMethods, RunQueryDsl};
-use crate::config::{DbConnection, DbPool};
-use crate::models::{NewCompleteProduct, NewProductVariant, Product, ProductFilters, ProductUpdates};
+use crate::config::Database;
+use diesel::pg::PgConnection;
+use chrono::{DateTime, Duration, Utc};
+use crate::models::{
+    escape_like, parse_import_csv, BulkChangeResult, BulkCreateResult, BulkItemResult, BulkMode, BulkUpdate,
+    CompleteProduct, Cursor, IfMatch, ImportReport, NewCompleteProduct, NewProductVariant, NewVariant, NewVariantValue,
+    Page, Product,
+    ProductChangeset, ProductDocument, ProductFilters, ProductPatch, ProductRevision, ProductSuggestion, ProductUpdates,
+    PurgeSummary, RevisionAction, SuggestQuery, Validate, VariantRef, VariantWithValues, DEFAULT_SUGGESTIONS,
+    csv_header, csv_rows, ndjson_rows, ExportFormat, EXPORT_BATCH_SIZE, EXPORT_BUFFERED_BATCHES, MAX_COST
+};
+use crate::services::product_queries::{
+    adjusted_cost, apply_filters, apply_sort, catalog_last_modified, keyset_predicate, load_variant_groups, lock_product, name_resembles,
+    name_similarity, rank_expr
+};
+use crate::services::facet_queries::load_facets;
+use crate::traits::responses::AppError;
+use crate::services::revision_queries::{find_revision, load_history, record_revision, record_revisions};
+use crate::services::variant_queries::{find_variant, insert_variant_values};
+use crate::services::import_queries::import_rows;
+use crate::services::insert_queries::{insert_prepared, prepare_products, PreparedProduct};
 use uuid::Uuid;
 use crate::schema::{product_variants, products, variants};
 use anyhow::Result;
-use tracing::{info, warn, error, instrument, debug};
+use json_patch::PatchErrorKind;
+use tokio::sync::mpsc;
+use tracing::{info, warn, error, instrument, debug, Instrument};
+
+// A result the caller still gets although its transaction is rolled back, carried
+// out of the transaction as an error. Used for rejected atomic batches and dry runs.
+#[derive(Debug)]
+struct RolledBack<T>(T);
+
+impl<T> std::fmt::Display for RolledBack<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "transaction rolled back")
+    }
+}
+
+impl<T: std::fmt::Debug> std::error::Error for RolledBack<T> {}
 
 pub struct ProductService {
-    pub pool: DbPool
+    pub db: Database
 }
 
 impl ProductService {
-    pub fn new(pool: DbPool) -> Self {
-        Self { pool }
+    pub fn new(db: Database) -> Self {
+        Self { db }
     } 
 
-    #[instrument(
-        name = "db_get_connection",
-        skip(self),
-        fields(service = "ProductService")
-    )]
-    fn get_connection(&self) -> Result<DbConnection> {
-        debug!("Acquiring database connection from pool");
-        match self.pool.get() {
-            Ok(conn) => {
-                debug!("Database connection acquired successfully");
-                Ok(conn)
-            }
-            Err(e) => {
-                error!(error = %e, "Failed to acquire database connection");
-                Err(e.into())
-            }
-        }
-    }
-
     #[instrument(
         name = "service_get_product_by_id",
         skip(self),
         fields(product_id = %product_id)
     )]
-    pub fn get_product_by_id(&self, product_id: Uuid) -> Result<Option<Product>> {
+    pub async fn get_product_by_id(&self, product_id: Uuid) -> Result<Option<Product>> {
         info!(product_id = %product_id, "🔍 Fetching product by ID from database");
         
-        let mut conn = self.get_connection()?;
-        
-        let result = products::table
-            .filter(products::id.eq(product_id))
-            .select(Product::as_select())
-            .first(&mut conn)
-            .optional();
+        let result = self.db.run(move |conn| {
+            Ok(products::table
+                .filter(products::id.eq(product_id))
+                .filter(products::deleted_at.is_null())
+                .select(Product::as_select())
+                .first(conn)
+                .optional()?)
+        }).await;
             
         match result {
             Ok(Some(product)) => {
@@ -70,7 +88,67 @@ impl ProductService {
                     error = %e,
                     "Database error while fetching product"
                 );
-                Err(e.into())
+                Err(e)
+            }
+        }
+    }
+
+    // Loads the variants of all given products in a single query,
+    // keeping the products in their original order
+    fn attach_variants(conn: &mut PgConnection, products: Vec<Product>) -> QueryResult<Vec<CompleteProduct>> {
+        let product_ids: Vec<Uuid> = products.iter().map(|p| p.id).collect();
+        let mut grouped = load_variant_groups(conn, &product_ids)?;
+
+        Ok(products
+            .into_iter()
+            .map(|product| {
+                let variants = grouped.remove(&product.id).unwrap_or_default();
+                CompleteProduct { product, variants }
+            })
+            .collect())
+    }
+
+    #[instrument(
+        name = "service_get_complete_product_by_id",
+        skip(self),
+        fields(product_id = %product_id)
+    )]
+    pub async fn get_complete_product_by_id(&self, product_id: Uuid) -> Result<Option<CompleteProduct>> {
+        let Some(product) = self.get_product_by_id(product_id).await? else {
+            return Ok(None);
+        };
+
+        match self.db.run(move |conn| Ok(Self::attach_variants(conn, vec![product])?)).await {
+            Ok(mut complete) => {
+                info!(product_id = %product_id, "Product variants loaded successfully");
+                Ok(complete.pop())
+            }
+            Err(e) => {
+                error!(
+                    product_id = %product_id,
+                    error = %e,
+                    "Database error while loading product variants"
+                );
+                Err(e)
+            }
+        }
+    }
+
+    #[instrument(name = "service_get_complete_products", skip(self, filters))]
+    pub async fn get_complete_products(&self, filters: Option<ProductFilters>) -> Result<Page<CompleteProduct>> {
+        let Page { items, next_cursor, total, facets, last_modified } = self.get_products(filters).await?;
+
+        match self.db.run(move |conn| Ok(Self::attach_variants(conn, items)?)).await {
+            Ok(items) => {
+                info!(product_count = items.len(), "Product variants loaded successfully");
+                Ok(Page { items, next_cursor, total, facets, last_modified })
+            }
+            Err(e) => {
+                error!(
+                    error = %e,
+                    "Database error while loading product variants"
+                );
+                Err(e)
             }
         }
     }
@@ -84,7 +162,7 @@ impl ProductService {
             filter_active = filters.as_ref().and_then(|f| f.is_active)
         )
     )]
-    pub fn get_products(&self, filters: Option<ProductFilters>) -> Result<Vec<Product>> {
+    pub async fn get_products(&self, filters: Option<ProductFilters>) -> Result<Page<Product>> {
         let has_filters = filters.is_some();
         info!(
             has_filters = has_filters,
@@ -93,167 +171,326 @@ impl ProductService {
             "Fetching products from database with filters"
         );
         
-        let mut conn = self.get_connection()?;
-        let mut query = products::table.into_boxed();
-        
-        if let Some(filters) = filters {
-            if let Some(product_name) = filters.name {
-                debug!(filter_name = %product_name, "Applying name filter");
-                query = query.filter(products::name.ilike(format!("%{}%", product_name)));
-            }
-            
-            if let Some(min_cost) = filters.cost_ge {
-                debug!(min_cost = min_cost, "Applying minimum cost filter");
-                query = query.filter(products::cost.ge(min_cost));
-            }
-            
-            if let Some(max_cost) = filters.cost_le {
-                debug!(max_cost = max_cost, "Applying maximum cost filter");
-                query = query.filter(products::cost.le(max_cost));
-            }
-            
-            if let Some(is_active_filter) = filters.is_active {
-                debug!(is_active = is_active_filter, "Applying active status filter");
-                query = query.filter(products::active.eq(is_active_filter));
+        let filters = filters.unwrap_or_default();
+        let page_size = filters.page_size();
+        let sort_order = filters.sort_order().map_err(AppError::Validation)?;
+        let cursor = filters.cursor
+            .as_deref()
+            .map(|raw| Cursor::decode(raw).map_err(|e| AppError::Validation(e.to_string())))
+            .transpose()?;
+
+        let query_sort_order = sort_order.clone();
+        let result = self.db.run(move |conn| {
+            // Read before the rows, so a write racing the page can only make it look older
+            let last_modified = catalog_last_modified(conn)?;
+
+            let total = if filters.include_total.unwrap_or(false) {
+                debug!("Counting products matching filters");
+                Some(
+                    apply_filters(products::table.into_boxed(), &filters)
+                        .count()
+                        .get_result::<i64>(conn)?
+                )
+            } else {
+                None
+            };
+
+            let facets = if filters.include_facets.unwrap_or(false) {
+                Some(load_facets(conn, &filters)?)
+            } else {
+                None
+            };
+
+            let mut query = apply_filters(products::table.into_boxed(), &filters);
+
+            if let Some(cursor) = &cursor {
+                debug!(after_id = %cursor.id, "Applying keyset cursor");
+                query = query.filter(keyset_predicate(&query_sort_order, cursor, &filters)?);
             }
-        }
-        
-        let result = query
-            .select(Product::as_select())
-            .load(&mut conn);
-            
+
+            // Fetch one extra row to find out whether another page follows
+            let rows = apply_sort(query, &query_sort_order, &filters)
+                .limit(page_size + 1)
+                .select((Product::as_select(), rank_expr(&filters)))
+                .load::<(Product, f32)>(conn)?;
+            Ok((rows, total, facets, last_modified))
+        }).await;
+
         match result {
-            Ok(products) => {
+            Ok((mut rows, total, facets, last_modified)) => {
+                let has_more = rows.len() as i64 > page_size;
+                rows.truncate(page_size as usize);
+
+                let next_cursor = if has_more {
+                    rows.last().map(|(last, rank)| Cursor {
+                        id: last.id,
+                        keys: sort_order.iter().map(|o| o.field.value_of(last, *rank)).collect()
+                    }.encode())
+                } else {
+                    None
+                };
+                let products: Vec<Product> = rows.into_iter().map(|(product, _)| product).collect();
+
                 info!(
                     product_count = products.len(),
+                    has_more = has_more,
                     "Products fetched successfully from database"
                 );
-                Ok(products)
+                Ok(Page { items: products, next_cursor, total, facets, last_modified })
             }
             Err(e) => {
                 error!(
                     error = %e,
                     "Database error while fetching products"
                 );
-                Err(e.into())
+                Err(e)
             }
         }
     }
 
     #[instrument(
-        name = "service_update_product",
-        skip(self, updates),
-        fields(
-            product_id = %product_id,
-            update_name = updates.name.is_some(),
-            update_cost = updates.cost.is_some(),
-            update_active = updates.active.is_some()
-        )
+        name = "service_suggest_products",
+        skip(self, query),
+        fields(prefix = %query.prefix, limit = query.limit)
     )]
-    pub fn update_product(&self, product_id: Uuid, updates: ProductUpdates) -> Result<Option<Product>> {
+    pub async fn suggest_products(&self, query: SuggestQuery) -> Result<Vec<ProductSuggestion>> {
+        let prefix = query.prefix.trim().to_lowercase();
+        let limit = query.limit.unwrap_or(DEFAULT_SUGGESTIONS);
+        info!(prefix = %prefix, limit = limit, "🔎 Fetching product name suggestions");
+
+        // Exact prefixes always qualify, anything else has to be close enough to a word in the name
+        let escaped = escape_like(&prefix);
+        let result = self.db.run(move |conn| {
+            Ok(products::table
+                .filter(products::active.eq(true))
+                .filter(products::deleted_at.is_null())
+                .filter(products::name.ilike(format!("{}%", escaped)).or(name_resembles(prefix.clone())))
+                .order((name_similarity(prefix.clone()).desc(), products::name.asc()))
+                .limit(limit)
+                .select((products::id, products::name, name_similarity(prefix)))
+                .load::<ProductSuggestion>(conn)?)
+        }).await;
+
+        match result {
+            Ok(suggestions) => {
+                info!(
+                    suggestion_count = suggestions.len(),
+                    "Product suggestions fetched successfully from database"
+                );
+                Ok(suggestions)
+            }
+            Err(e) => {
+                error!(
+                    error = %e,
+                    "Database error while fetching product suggestions"
+                );
+                Err(e)
+            }
+        }
+    }
+
+    // Makes the locked product match the document, writing only what differs.
+    // Variants that keep the same values keep their rows, and a document that
+    // changes nothing leaves the version alone.
+    fn write_document(
+        conn: &mut PgConnection,
+        current: Product,
+        groups: Vec<VariantWithValues>,
+        document: ProductDocument,
+        changed_by: Option<&str>
+    ) -> Result<CompleteProduct> {
+        let product_id = current.id;
+        let PreparedProduct { product: target, values } = prepare_products(conn, vec![document.into_new_product(product_id)])?
+            .pop()
+            .expect("one prepared product per item")?;
+
+        let mut wanted: HashMap<Uuid, Vec<String>> = HashMap::new();
+        for value in values {
+            wanted.entry(value.variant_id).or_default().push(value.value);
+        }
+        let mut existing: HashMap<Uuid, Vec<String>> = HashMap::new();
+        for group in &groups {
+            existing
+                .entry(group.variant.id)
+                .or_default()
+                .extend(group.values.iter().filter_map(|v| v.value.clone()));
+        }
+        existing.retain(|_, values| !values.is_empty());
+
+        let sorted = |values: Option<&Vec<String>>| {
+            let mut values = values.cloned().unwrap_or_default();
+            values.sort();
+            values
+        };
+        let replaced: Vec<Uuid> = wanted
+            .keys()
+            .chain(existing.keys().filter(|id| !wanted.contains_key(id)))
+            .filter(|id| sorted(wanted.get(id)) != sorted(existing.get(id)))
+            .copied()
+            .collect();
+        let fields_changed = target.name != current.name || target.cost != current.cost || target.active != current.active;
+        if !fields_changed && replaced.is_empty() {
+            debug!(product_id = %product_id, "Product already matches the document");
+            return Ok(CompleteProduct { product: current, variants: groups });
+        }
+
+        let product = diesel::update(products::table.find(product_id))
+            .set((
+                ProductChangeset::from(ProductUpdates {
+                    name: Some(target.name),
+                    cost: Some(target.cost),
+                    active: Some(target.active)
+                }),
+                products::version.eq(products::version + 1),
+                products::updated_at.eq(diesel::dsl::now)
+            ))
+            .returning(Product::as_select())
+            .get_result(conn)?;
+
+        if !replaced.is_empty() {
+            debug!(product_id = %product_id, variant_count = replaced.len(), "Replacing variant values");
+            diesel::delete(
+                product_variants::table
+                    .filter(product_variants::product_id.eq(product_id))
+                    .filter(product_variants::variant_id.eq_any(&replaced))
+            )
+            .execute(conn)?;
+
+            let values: Vec<NewProductVariant> = replaced
+                .iter()
+                .flat_map(|variant_id| {
+                    wanted
+                        .get(variant_id)
+                        .into_iter()
+                        .flatten()
+                        .map(|value| NewProductVariant { variant_id: *variant_id, product_id, value: value.clone() })
+                })
+                .collect();
+            diesel::insert_into(product_variants::table)
+                .values(&values)
+                .execute(conn)?;
+        }
+
+        record_revision(conn, &product, RevisionAction::Update, changed_by)?;
+        Ok(Self::attach_variants(conn, vec![product])?.pop().expect("one complete product per product"))
+    }
+
+    // Replaces every editable field of a live product, including its variants
+    #[instrument(
+        name = "service_replace_product",
+        skip(self, document),
+        fields(product_id = %product_id, variant_count = document.variants.len())
+    )]
+    pub async fn replace_product(
+        &self,
+        product_id: Uuid,
+        document: ProductDocument,
+        if_match: Option<IfMatch>,
+        changed_by: Option<&str>
+    ) -> Result<Option<CompleteProduct>> {
         info!(
             product_id = %product_id,
-            update_name = updates.name.is_some(),
-            update_cost = updates.cost.is_some(),
-            update_active = updates.active.is_some(),
-            "Updating product in database"
+            variant_count = document.variants.len(),
+            conditional = if_match.is_some(),
+            "Replacing product in database"
         );
-        
-        let mut conn = self.get_connection()?;
-        
-        let result = diesel::update(products::table.filter(products::id.eq(product_id)))
-                .set(&updates)
-                .returning(Product::as_select())
-                .get_result(&mut conn)
-                .optional();
-                
+
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction(|conn| {
+            let Some(current) = lock_product(conn, product_id, if_match.as_ref())? else {
+                return Ok(None);
+            };
+            let groups = load_variant_groups(conn, &[product_id])?.remove(&product_id).unwrap_or_default();
+            Self::write_document(conn, current, groups, document, changed_by.as_deref()).map(Some)
+        })).await;
+
         match result {
-            Ok(Some(product)) => {
-                info!(
-                    product_id = %product_id,
-                    product_name = %product.name,
-                    "Product updated successfully in database"
-                );
-                Ok(Some(product))
+            Ok(Some(complete)) => {
+                info!(product_id = %product_id, version = complete.product.version, "Product replaced successfully");
+                Ok(Some(complete))
             }
             Ok(None) => {
-                info!(product_id = %product_id, "Product not found for update");
+                info!(product_id = %product_id, "Product not found for replacement");
                 Ok(None)
             }
             Err(e) => {
-                error!(
-                    product_id = %product_id,
-                    error = %e,
-                    "Database error while updating product"
-                );
-                Err(e.into())
+                warn!(product_id = %product_id, error = %e, "Product replacement failed");
+                Err(e)
+            }
+        }
+    }
+
+    // Applies a merge patch or JSON patch to the product's document and writes the result
+    #[instrument(name = "service_patch_product", skip(self, patch), fields(product_id = %product_id))]
+    pub async fn patch_product(
+        &self,
+        product_id: Uuid,
+        patch: ProductPatch,
+        if_match: Option<IfMatch>,
+        changed_by: Option<&str>
+    ) -> Result<Option<CompleteProduct>> {
+        info!(product_id = %product_id, conditional = if_match.is_some(), "Patching product in database");
+
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction(|conn| {
+            let Some(current) = lock_product(conn, product_id, if_match.as_ref())? else {
+                return Ok(None);
+            };
+            let groups = load_variant_groups(conn, &[product_id])?.remove(&product_id).unwrap_or_default();
+
+            let patched = patch
+                .apply(&ProductDocument::new(&current, &groups))
+                .map_err(|e| match e.kind {
+                    PatchErrorKind::TestFailed => AppError::Conflict(e.to_string()),
+                    _ => AppError::Unprocessable(e.to_string())
+                })?;
+            let document: ProductDocument = serde_json::from_value(patched)
+                .map_err(|e| AppError::Unprocessable(format!("The patched product is invalid: {}", e)))?;
+            document.validated().map_err(AppError::InvalidFields)?;
+
+            Self::write_document(conn, current, groups, document, changed_by.as_deref()).map(Some)
+        })).await;
+
+        match result {
+            Ok(Some(complete)) => {
+                info!(product_id = %product_id, version = complete.product.version, "Product patched successfully");
+                Ok(Some(complete))
+            }
+            Ok(None) => {
+                info!(product_id = %product_id, "Product not found for patch");
+                Ok(None)
+            }
+            Err(e) => {
+                warn!(product_id = %product_id, error = %e, "Product patch failed");
+                Err(e)
             }
         }
     }
    
     #[instrument(skip(self), fields(product_name = new_complete_product.product.name))]
-    pub fn create_product(&self, new_complete_product: NewCompleteProduct) -> Result<Product> {
+    pub async fn create_product(&self, new_complete_product: NewCompleteProduct, changed_by: Option<&str>) -> Result<Product> {
         info!("🆕 Creating new product with {} variants", new_complete_product.variants.len());
         
-        let mut conn = self.get_connection()
-            .map_err(|e| {
-                warn!("Failed to get database connection for product creation: {}", e);
-                e
-            })?;
-
-        let NewCompleteProduct { 
-            product: new_product, 
-            variants: new_variants
-        } = new_complete_product;
-
-        let result = conn.transaction(|conn| {
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
             info!("💾 Inserting product into database");
-            let product = diesel::insert_into(products::table)
-                .values(new_product)
-                .returning(Product::as_select())
-                .get_result(conn)
+            let prepared = prepare_products(conn, vec![new_complete_product])?
+                .into_iter()
+                .next()
+                .expect("one prepared product per item")
                 .map_err(|e| {
-                    warn!("Failed to insert product: {}", e);
+                    warn!("Failed to prepare product variants: {}", e);
                     e
                 })?;
 
-            info!("Product created successfully with ID: {}", product.id);
-
-            for (index, variant_value) in new_variants.iter().enumerate() {
-                info!("Processing variant {} of {}", index + 1, new_variants.len());
-                
-                let variant_id = diesel::insert_into(variants::table)
-                    .values(&variant_value.variant)
-                    .returning(variants::id)
-                    .get_result::<Uuid>(conn)
-                    .map_err(|e| {
-                        warn!("Failed to insert variant {}: {}", index + 1, e);
-                        e
-                    })?;
-
-                // Insert each value for this variant
-                for (value_index, value) in variant_value.values.iter().enumerate() {
-                    if let Some(val) = value {
-                        let new_product_variant = NewProductVariant {
-                            variant_id,
-                            product_id: product.id,
-                            value: val.clone(),
-                        };
-                        diesel::insert_into(product_variants::table)
-                            .values(new_product_variant)
-                            .execute(conn)
-                            .map_err(|e| {
-                                warn!("Failed to insert variant value {} for variant {}: {}", value_index + 1, index + 1, e);
-                                e
-                            })?;
-                    }
-                }
-                info!("Variant {} processed successfully", index + 1);
-            }
+            let product = insert_prepared(conn, vec![prepared], changed_by.as_deref())?
+                .into_iter()
+                .next()
+                .expect("one inserted product per prepared product");
 
-            info!("All variants processed successfully for product {}", product.id);
+            info!("Product created successfully with ID: {}", product.id);
             Ok(product)
-        });
+        })).await;
 
         match &result {
             Ok(product) => {
@@ -267,20 +504,137 @@ impl ProductService {
         result
     }
 
+    // Creates many products at once. Atomic mode writes all of them or none; per-item
+    // mode writes the good ones and reports the rest.
+    #[instrument(skip(self, items), fields(item_count = items.len(), mode = ?mode))]
+    pub async fn create_products(
+        &self,
+        items: Vec<NewCompleteProduct>,
+        mode: BulkMode,
+        changed_by: Option<&str>
+    ) -> Result<BulkCreateResult> {
+        info!(item_count = items.len(), mode = ?mode, "🆕 Creating products in bulk");
+
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| match mode {
+            BulkMode::Atomic => Self::create_all_or_none(conn, items, changed_by.as_deref()),
+            BulkMode::PerItem => Self::create_each(conn, items, changed_by.as_deref())
+        })).await;
+
+        match result {
+            Ok(result) => {
+                info!(created = result.created, failed = result.failed, "Bulk product creation finished");
+                Ok(result)
+            }
+            Err(e) => {
+                warn!(error = %e, "Bulk product creation failed");
+                Err(e)
+            }
+        }
+    }
+
+    fn create_all_or_none(
+        conn: &mut PgConnection,
+        items: Vec<NewCompleteProduct>,
+        changed_by: Option<&str>
+    ) -> Result<BulkCreateResult> {
+        let attempt = conn.transaction::<_, anyhow::Error, _>(|conn| {
+            let prepared = prepare_products(conn, items)?;
+            if prepared.iter().any(|item| item.is_err()) {
+                let results: Vec<BulkItemResult> = prepared
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, item)| match item {
+                        Ok(_) => BulkItemResult::skipped(index),
+                        Err(e) => BulkItemResult::failed(index, AppError::from(e))
+                    })
+                    .collect();
+                // Rolls back the variant definitions created along the way
+                return Err(RolledBack(results).into());
+            }
+
+            let prepared = prepared.into_iter().collect::<Result<Vec<_>>>()?;
+            let products = insert_prepared(conn, prepared, changed_by)?;
+            Ok(products
+                .iter()
+                .enumerate()
+                .map(|(index, product)| BulkItemResult::created(index, product.id))
+                .collect())
+        });
+
+        match attempt {
+            Ok(results) => Ok(BulkCreateResult::new(results)),
+            Err(e) => match e.downcast::<RolledBack<Vec<BulkItemResult>>>() {
+                Ok(RolledBack(results)) => Ok(BulkCreateResult::new(results)),
+                Err(e) => Err(e)
+            }
+        }
+    }
+
+    fn create_each(
+        conn: &mut PgConnection,
+        items: Vec<NewCompleteProduct>,
+        changed_by: Option<&str>
+    ) -> Result<BulkCreateResult> {
+        let mut results: Vec<Option<BulkItemResult>> = vec![None; items.len()];
+        let mut valid: Vec<(usize, PreparedProduct)> = Vec::new();
+        for (index, item) in prepare_products(conn, items)?.into_iter().enumerate() {
+            match item {
+                Ok(prepared) => valid.push((index, prepared)),
+                Err(e) => results[index] = Some(BulkItemResult::failed(index, AppError::from(e)))
+            }
+        }
+
+        // One batch for everything that passed its checks. Only if the database
+        // rejects it are the items retried one savepoint at a time to find the culprits.
+        let batch: Vec<PreparedProduct> = valid.iter().map(|(_, prepared)| prepared.clone()).collect();
+        match conn.transaction(|conn| insert_prepared(conn, batch, changed_by)) {
+            Ok(products) => {
+                for ((index, _), product) in valid.iter().zip(products) {
+                    results[*index] = Some(BulkItemResult::created(*index, product.id));
+                }
+            }
+            Err(e) => {
+                debug!(error = %e, "Batch insert failed, inserting items one by one");
+                for (index, prepared) in valid {
+                    let outcome = conn.transaction(|conn| insert_prepared(conn, vec![prepared], changed_by));
+                    results[index] = Some(match outcome {
+                        Ok(products) => BulkItemResult::created(index, products[0].id),
+                        // Only the client-facing message, the database's own stays in the log
+                        Err(e) => BulkItemResult::failed(index, AppError::from(e))
+                    });
+                }
+            }
+        }
+
+        Ok(BulkCreateResult::new(results.into_iter().flatten().collect()))
+    }
+
+    // Tombstones the product. It disappears from every read but keeps its variant
+    // values until a purge, so it can still be restored.
     #[instrument(skip(self), fields(product_id = %product_id))]
-    pub fn delete_product(&self, product_id: Uuid) -> Result<bool> {
+    pub async fn delete_product(&self, product_id: Uuid, if_match: Option<IfMatch>, changed_by: Option<&str>) -> Result<bool> {
         info!("Attempting to delete product with ID: {}", product_id);
         
-        let mut conn = self.get_connection()
-            .map_err(|e| {
-                warn!("Failed to get database connection for product deletion: {}", e);
-                e
-            })?;
-        
-        let result = diesel::delete(products::table.filter(products::id.eq(product_id)))
-            .execute(&mut conn)
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction::<usize, anyhow::Error, _>(|conn| {
+            if lock_product(conn, product_id, if_match.as_ref())?.is_none() {
+                return Ok(0);
+            }
+            let product = diesel::update(products::table.filter(products::id.eq(product_id)))
+                .set((
+                    products::deleted_at.eq(diesel::dsl::now),
+                    products::version.eq(products::version + 1),
+                    products::updated_at.eq(diesel::dsl::now)
+                ))
+                .returning(Product::as_select())
+                .get_result(conn)?;
+
+            record_revision(conn, &product, RevisionAction::Delete, changed_by.as_deref())?;
+            Ok(1)
+        })).await
             .map_err(|e| {
-                warn!("Failed to execute delete query for product {}: {}", product_id, e);
+                warn!("Failed to delete product {}: {}", product_id, e);
                 e
             })?;
         
@@ -294,4 +648,492 @@ impl ProductService {
         
         Ok(deleted)
     }
-}
\ No newline at end of file
+
+    // Brings a deleted product back. Restoring a live product changes nothing.
+    #[instrument(skip(self), fields(product_id = %product_id))]
+    pub async fn restore_product(&self, product_id: Uuid, changed_by: Option<&str>) -> Result<Option<Product>> {
+        info!(product_id = %product_id, "Restoring deleted product");
+
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
+            let restored = diesel::update(
+                products::table
+                    .filter(products::id.eq(product_id))
+                    .filter(products::deleted_at.is_not_null())
+            )
+                .set((
+                    products::deleted_at.eq(None::<DateTime<Utc>>),
+                    products::version.eq(products::version + 1),
+                    products::updated_at.eq(diesel::dsl::now)
+                ))
+                .returning(Product::as_select())
+                .get_result(conn)
+                .optional()?;
+
+            if let Some(product) = &restored {
+                record_revision(conn, product, RevisionAction::Restore, changed_by.as_deref())?;
+            }
+            Ok(restored)
+        })).await;
+
+        match result {
+            Ok(Some(product)) => {
+                info!(product_id = %product_id, "Product restored successfully");
+                Ok(Some(product))
+            }
+            Ok(None) => self.get_product_by_id(product_id).await,
+            Err(e) => {
+                error!(product_id = %product_id, error = %e, "Database error while restoring product");
+                Err(e)
+            }
+        }
+    }
+
+    // Permanently removes products deleted before the retention period, together
+    // with their variant values. Their history stays, ending in a 'purge' revision.
+    #[instrument(skip(self), fields(retention_days = retention.num_days()))]
+    pub async fn purge_deleted_products(&self, retention: Duration) -> Result<PurgeSummary> {
+        let deleted_before = Utc::now() - retention;
+        info!(deleted_before = %deleted_before, "Purging deleted products");
+
+        let result = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
+            let purged: Vec<Product> = diesel::update(products::table.filter(products::deleted_at.lt(deleted_before)))
+                .set((
+                    products::version.eq(products::version + 1),
+                    products::updated_at.eq(diesel::dsl::now)
+                ))
+                .returning(Product::as_select())
+                .get_results(conn)?;
+            record_revisions(conn, &purged, RevisionAction::Purge, None)?;
+
+            let ids: Vec<Uuid> = purged.iter().map(|product| product.id).collect();
+            Ok(diesel::delete(products::table.filter(products::id.eq_any(&ids))).execute(conn)?)
+        })).await;
+
+        match result {
+            Ok(purged) => {
+                info!(purged = purged, "Deleted products purged");
+                Ok(PurgeSummary { purged, deleted_before })
+            }
+            Err(e) => {
+                error!(error = %e, "Database error while purging deleted products");
+                Err(e)
+            }
+        }
+    }
+
+    // Live products matching the selector, locked for the change unless it's a dry run.
+    // One row more than the limit is read to tell when the limit is exceeded.
+    fn select_for_change(
+        conn: &mut PgConnection,
+        filters: &ProductFilters,
+        limit: i64,
+        lock: bool
+    ) -> QueryResult<Vec<Uuid>> {
+        let ids: Vec<Uuid> = apply_filters(products::table.into_boxed(), filters)
+            .filter(products::deleted_at.is_null())
+            .select(products::id)
+            .order(products::id.asc())
+            .limit(limit + 1)
+            .load(conn)?;
+        if !lock || ids.len() as i64 > limit {
+            return Ok(ids);
+        }
+
+        // Boxed queries can't take a locking clause, so the rows are locked by id
+        products::table
+            .filter(products::id.eq_any(&ids))
+            .filter(products::deleted_at.is_null())
+            .select(products::id)
+            .order(products::id.asc())
+            .for_update()
+            .load(conn)
+    }
+
+    // Runs `change` on the products matching the selector, as long as there are no
+    // more than `limit` of them. A dry run only reports what would be changed.
+    async fn change_matching<F>(
+        &self,
+        filters: ProductFilters,
+        dry_run: bool,
+        limit: i64,
+        change: F
+    ) -> Result<BulkChangeResult>
+    where
+        F: FnOnce(&mut PgConnection, &[Uuid]) -> Result<Vec<Product>> + Send + 'static
+    {
+        self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
+            let ids = Self::select_for_change(conn, &filters, limit, !dry_run)?;
+
+            if ids.len() as i64 > limit {
+                let matched: i64 = apply_filters(products::table.into_boxed(), &filters)
+                    .filter(products::deleted_at.is_null())
+                    .count()
+                    .get_result(conn)?;
+                if !dry_run {
+                    return Err(AppError::Validation(format!(
+                        "{} products match, more than the limit of {} per bulk change", matched, limit
+                    )).into());
+                }
+                return Ok(BulkChangeResult { dry_run, matched, limit, ids: Vec::new() });
+            }
+
+            let ids = if dry_run {
+                ids
+            } else {
+                change(conn, &ids)?.into_iter().map(|product| product.id).collect()
+            };
+            Ok(BulkChangeResult { dry_run, matched: ids.len() as i64, limit, ids })
+        })).await
+    }
+
+    // Costs a bulk adjustment produces must pass the same bounds as costs written one by one
+    fn check_adjusted_costs(costs: &[BigDecimal]) -> Result<()> {
+        let zero = BigDecimal::from(0);
+        let max = BigDecimal::from(MAX_COST);
+        let negative = costs.iter().filter(|cost| **cost < zero).count();
+        if negative > 0 {
+            return Err(AppError::Validation(format!(
+                "The cost adjustment would make the cost of {} products negative", negative
+            )).into());
+        }
+        let too_high = costs.iter().filter(|cost| **cost > max).count();
+        if too_high > 0 {
+            return Err(AppError::Validation(format!(
+                "The cost adjustment would raise the cost of {} products above {}", too_high, MAX_COST
+            )).into());
+        }
+        Ok(())
+    }
+
+    #[instrument(skip(self, filters, update))]
+    pub async fn bulk_update_products(
+        &self,
+        filters: ProductFilters,
+        update: BulkUpdate,
+        dry_run: bool,
+        limit: i64,
+        changed_by: Option<&str>
+    ) -> Result<BulkChangeResult> {
+        info!(
+            dry_run = dry_run,
+            limit = limit,
+            adjust_cost = update.cost_adjustment.is_some(),
+            "Updating products in bulk"
+        );
+
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.change_matching(filters, dry_run, limit, move |conn, ids| {
+            let target = products::table.filter(products::id.eq_any(ids));
+            let changeset = ProductChangeset::from(update.updates);
+            let bump = (products::version.eq(products::version + 1), products::updated_at.eq(diesel::dsl::now));
+
+            let updated: Vec<Product> = match &update.cost_adjustment {
+                Some(adjustment) => {
+                    let (factor, offset) = adjustment.factor_and_offset();
+                    // Checked before writing, since costs past the column's precision fail the update itself
+                    let adjusted: Vec<BigDecimal> = products::table
+                        .filter(products::id.eq_any(ids))
+                        .select(adjusted_cost(factor.clone(), offset.clone()))
+                        .load(conn)?;
+                    Self::check_adjusted_costs(&adjusted)?;
+                    diesel::update(target)
+                        .set((changeset, products::cost.eq(adjusted_cost(factor, offset)), bump))
+                        .returning(Product::as_select())
+                        .get_results(conn)?
+                }
+                None => diesel::update(target)
+                    .set((changeset, bump))
+                    .returning(Product::as_select())
+                    .get_results(conn)?
+            };
+
+            record_revisions(conn, &updated, RevisionAction::Update, changed_by.as_deref())?;
+            Ok(updated)
+        }).await;
+
+        match result {
+            Ok(result) => {
+                info!(matched = result.matched, dry_run = result.dry_run, "Bulk update finished");
+                Ok(result)
+            }
+            Err(e) => {
+                warn!(error = %e, "Bulk update failed");
+                Err(e)
+            }
+        }
+    }
+
+    // Tombstones every matching product, like `delete_product` does for one
+    #[instrument(skip(self, filters))]
+    pub async fn bulk_delete_products(
+        &self,
+        filters: ProductFilters,
+        dry_run: bool,
+        limit: i64,
+        changed_by: Option<&str>
+    ) -> Result<BulkChangeResult> {
+        info!(dry_run = dry_run, limit = limit, "Deleting products in bulk");
+
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.change_matching(filters, dry_run, limit, move |conn, ids| {
+            let deleted: Vec<Product> = diesel::update(products::table.filter(products::id.eq_any(ids)))
+                .set((
+                    products::deleted_at.eq(diesel::dsl::now),
+                    products::version.eq(products::version + 1),
+                    products::updated_at.eq(diesel::dsl::now)
+                ))
+                .returning(Product::as_select())
+                .get_results(conn)?;
+
+            record_revisions(conn, &deleted, RevisionAction::Delete, changed_by.as_deref())?;
+            Ok(deleted)
+        }).await;
+
+        match result {
+            Ok(result) => {
+                info!(matched = result.matched, dry_run = result.dry_run, "Bulk delete finished");
+                Ok(result)
+            }
+            Err(e) => {
+                warn!(error = %e, "Bulk delete failed");
+                Err(e)
+            }
+        }
+    }
+
+    // Upserts the products of a CSV file in one transaction. Rows that fail validation
+    // are reported with their line and skipped; a dry run reports the same outcome
+    // and rolls everything back.
+    #[instrument(skip(self, data), fields(bytes = data.len()))]
+    pub async fn import_products(&self, data: &[u8], dry_run: bool, changed_by: Option<&str>) -> Result<ImportReport> {
+        let rows = parse_import_csv(data).map_err(AppError::Validation)?;
+        info!(row_count = rows.len(), dry_run = dry_run, "Importing products");
+
+        let changed_by = changed_by.map(str::to_string);
+        let attempt = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
+            let report = ImportReport::new(dry_run, import_rows(conn, rows, changed_by.as_deref())?);
+            if dry_run {
+                return Err(RolledBack(report).into());
+            }
+            Ok(report)
+        })).await;
+        let result = match attempt {
+            Err(e) => match e.downcast::<RolledBack<ImportReport>>() {
+                Ok(RolledBack(report)) => Ok(report),
+                Err(e) => Err(e)
+            },
+            ok => ok
+        };
+
+        match result {
+            Ok(report) => {
+                info!(
+                    created = report.created,
+                    updated = report.updated,
+                    unchanged = report.unchanged,
+                    failed = report.failed,
+                    dry_run = report.dry_run,
+                    "Product import finished"
+                );
+                Ok(report)
+            }
+            Err(e) => {
+                error!(error = %e, "Product import failed");
+                Err(e)
+            }
+        }
+    }
+
+    // Streams the export as encoded chunks, the CSV header first. Every batch is read
+    // inside one read-only repeatable-read transaction, so the header and all rows come
+    // from the same snapshot however long the client takes to read them. The first
+    // message is the header (empty for NDJSON) or the error that stopped the export
+    // before it began.
+    #[instrument(skip(self, filters))]
+    pub fn export_products(&self, filters: ProductFilters, format: ExportFormat) -> mpsc::Receiver<Result<Vec<u8>>> {
+        let (sender, receiver) = mpsc::channel(EXPORT_BUFFERED_BATCHES);
+        let db = self.db.clone();
+        let span = tracing::Span::current();
+
+        tokio::spawn(async move {
+            let chunks = sender.clone();
+            let result = db.run(move |conn| {
+                conn.build_transaction()
+                    .repeatable_read()
+                    .read_only()
+                    .run(|conn| Self::write_export(conn, &filters, format, &chunks))
+            }).await;
+
+            match result {
+                Ok(products) => debug!(product_count = products, "Product export finished"),
+                Err(e) => {
+                    error!(error = %e, "Database error while exporting products");
+                    let _ = sender.send(Err(e)).await;
+                }
+            }
+        }.instrument(span));
+
+        receiver
+    }
+
+    // Sends the header and then one encoded chunk per batch of matching products in id
+    // order. Stops early without an error once the client has gone away. Returns the
+    // number of products sent.
+    fn write_export(
+        conn: &mut PgConnection,
+        filters: &ProductFilters,
+        format: ExportFormat,
+        chunks: &mpsc::Sender<Result<Vec<u8>>>
+    ) -> Result<usize> {
+        // The CSV header names a column per variant used by the exported products
+        let (header, variant_names) = match format {
+            ExportFormat::Csv => {
+                let matching = apply_filters(products::table.into_boxed(), filters).select(products::id);
+                let names = variants::table
+                    .inner_join(product_variants::table)
+                    .filter(product_variants::product_id.eq_any(matching))
+                    .select(variants::name)
+                    .distinct()
+                    .order(variants::name.asc())
+                    .load::<String>(conn)?;
+                (csv_header(&names)?, names)
+            }
+            ExportFormat::Ndjson => (Vec::new(), Vec::new())
+        };
+        if chunks.blocking_send(Ok(header)).is_err() {
+            return Ok(0);
+        }
+
+        let mut sent = 0;
+        let mut after: Option<Uuid> = None;
+        loop {
+            let mut query = apply_filters(products::table.into_boxed(), filters);
+            if let Some(after) = after {
+                query = query.filter(products::id.gt(after));
+            }
+            let products = query
+                .order(products::id.asc())
+                .limit(EXPORT_BATCH_SIZE)
+                .select(Product::as_select())
+                .load::<Product>(conn)?;
+            let Some(last) = products.last().map(|product| product.id) else {
+                return Ok(sent);
+            };
+            let full = products.len() as i64 == EXPORT_BATCH_SIZE;
+            let batch = Self::attach_variants(conn, products)?;
+
+            let encoded = match format {
+                ExportFormat::Csv => csv_rows(&batch, &variant_names)?,
+                ExportFormat::Ndjson => ndjson_rows(&batch)?
+            };
+            debug!(batch_size = batch.len(), "Export batch encoded");
+            if chunks.blocking_send(Ok(encoded)).is_err() {
+                debug!("Export client went away");
+                return Ok(sent);
+            }
+            sent += batch.len();
+
+            if !full {
+                return Ok(sent);
+            }
+            after = Some(last);
+        }
+    }
+
+    // Revisions of the product, newest first. Deleted and purged products keep their
+    // history readable.
+    #[instrument(skip(self), fields(product_id = %product_id))]
+    pub async fn product_history(&self, product_id: Uuid) -> Result<Option<Vec<ProductRevision>>> {
+        info!(product_id = %product_id, "Fetching product history");
+
+        let result = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
+            let history = load_history(conn, product_id)?;
+            if !history.is_empty() {
+                return Ok(Some(history));
+            }
+            let exists: bool = diesel::select(diesel::dsl::exists(products::table.find(product_id)))
+                .get_result(conn)?;
+            Ok(exists.then_some(history))
+        })).await;
+
+        match result {
+            Ok(history) => {
+                info!(
+                    product_id = %product_id,
+                    revision_count = history.as_ref().map(Vec::len),
+                    "Product history fetched successfully"
+                );
+                Ok(history)
+            }
+            Err(e) => {
+                error!(product_id = %product_id, error = %e, "Database error while fetching product history");
+                Err(e)
+            }
+        }
+    }
+
+    // Writes the name, cost, active flag and variants of an earlier revision back as a new revision
+    #[instrument(skip(self), fields(product_id = %product_id, revision = revision))]
+    pub async fn revert_product(
+        &self,
+        product_id: Uuid,
+        revision: i32,
+        if_match: Option<IfMatch>,
+        changed_by: Option<&str>
+    ) -> Result<Option<Product>> {
+        info!(product_id = %product_id, revision = revision, "Reverting product");
+
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction(|conn| {
+            if lock_product(conn, product_id, if_match.as_ref())?.is_none() {
+                return Ok(None);
+            }
+
+            let target = find_revision(conn, product_id, revision)?
+                .ok_or_else(|| AppError::NotFound(format!("Product has no revision {}", revision)))?;
+
+            // Values go back as recorded, a definition deleted since is created again by name
+            diesel::delete(product_variants::table.filter(product_variants::product_id.eq(product_id)))
+                .execute(conn)?;
+            for snapshot in target.variant_snapshots()? {
+                let variant = match find_variant(conn, snapshot.variant_id)? {
+                    Some(variant) => VariantRef::Id { id: variant.id },
+                    None => VariantRef::Name(NewVariant { name: snapshot.name, allowed_values: None })
+                };
+                insert_variant_values(conn, product_id, &NewVariantValue { variant, values: snapshot.values })?;
+            }
+
+            let product = diesel::update(products::table.filter(products::id.eq(product_id)))
+                .set((
+                    ProductChangeset::from(ProductUpdates {
+                        name: Some(target.name.clone()),
+                        cost: Some(target.cost()),
+                        active: Some(target.active)
+                    }),
+                    products::version.eq(products::version + 1),
+                    products::updated_at.eq(diesel::dsl::now)
+                ))
+                .returning(Product::as_select())
+                .get_result(conn)?;
+
+            record_revision(conn, &product, RevisionAction::Revert, changed_by.as_deref())?;
+            Ok(Some(product))
+        })).await;
+
+        match result {
+            Ok(Some(product)) => {
+                info!(product_id = %product_id, version = product.version, "Product reverted successfully");
+                Ok(Some(product))
+            }
+            Ok(None) => {
+                info!(product_id = %product_id, "Product not found for revert");
+                Ok(None)
+            }
+            Err(e) => {
+                warn!(product_id = %product_id, error = %e, "Product revert failed");
+                Err(e)
+            }
+        }
+    }
+}
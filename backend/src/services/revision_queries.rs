@@ -0,0 +1,73 @@
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use uuid::Uuid;
+use crate::models::{Product, ProductRevision, RevisionAction, RevisionRecord};
+use crate::schema::product_revisions;
+use crate::services::product_queries::load_variant_groups;
+use tracing::debug;
+
+// Rows per INSERT statement, keeping well below Postgres' limit of 65535 bind parameters
+pub const INSERT_CHUNK_ROWS: usize = 1000;
+
+// Snapshots the product as just written, variants included. Runs inside the
+// write's transaction so a change is never saved without its revision.
+pub fn record_revision(
+    conn: &mut PgConnection,
+    product: &Product,
+    action: RevisionAction,
+    changed_by: Option<&str>
+) -> QueryResult<()> {
+    record_revisions(conn, std::slice::from_ref(product), action, changed_by)
+}
+
+pub fn record_revisions(
+    conn: &mut PgConnection,
+    products: &[Product],
+    action: RevisionAction,
+    changed_by: Option<&str>
+) -> QueryResult<()> {
+    debug!(product_count = products.len(), action = action.as_str(), "Recording product revisions");
+
+    let ids: Vec<Uuid> = products.iter().map(|product| product.id).collect();
+    let variants = load_variant_groups(conn, &ids)?;
+    let records: Vec<RevisionRecord> = products
+        .iter()
+        .map(|product| {
+            let groups = variants.get(&product.id).map(Vec::as_slice).unwrap_or_default();
+            RevisionRecord::of(product, groups, action, changed_by)
+        })
+        .collect();
+    for chunk in records.chunks(INSERT_CHUNK_ROWS) {
+        diesel::insert_into(product_revisions::table)
+            .values(chunk)
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+pub fn find_revision(conn: &mut PgConnection, product_id: Uuid, revision: i32) -> QueryResult<Option<RevisionRecord>> {
+    product_revisions::table
+        .find((product_id, revision))
+        .select(RevisionRecord::as_select())
+        .first(conn)
+        .optional()
+}
+
+// Every revision of the product with its field-level diff, newest first
+pub fn load_history(conn: &mut PgConnection, product_id: Uuid) -> QueryResult<Vec<ProductRevision>> {
+    let records = product_revisions::table
+        .filter(product_revisions::product_id.eq(product_id))
+        .order(product_revisions::revision.asc())
+        .select(RevisionRecord::as_select())
+        .load::<RevisionRecord>(conn)?;
+
+    let mut history = Vec::with_capacity(records.len());
+    let mut previous: Option<RevisionRecord> = None;
+    for record in records {
+        history.push(ProductRevision::between(previous.as_ref(), record.clone()));
+        previous = Some(record);
+    }
+
+    history.reverse();
+    Ok(history)
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use uuid::Uuid;
+use crate::models::{NewProductVariant, NewVariant, NewVariantValue, Product, RevisionAction, Variant, VariantRef};
+use crate::schema::{product_variants, products, variants};
+use crate::services::product_queries::{btrim, lower};
+use crate::services::revision_queries::record_revisions;
+use crate::traits::responses::AppError;
+use anyhow::Result;
+use tracing::debug;
+
+// Variant names are unique by this key, see the variants_name_lower_key index
+fn name_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+pub fn find_variant(conn: &mut PgConnection, variant_id: Uuid) -> QueryResult<Option<Variant>> {
+    variants::table
+        .find(variant_id)
+        .select(Variant::as_select())
+        .first(conn)
+        .optional()
+}
+
+// Trimmed, non-empty variant name
+pub fn normalize_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::Validation("Variant name must not be empty".to_string()).into());
+    }
+    Ok(name.to_string())
+}
+
+// Turns a clash on the unique variant name into a client error
+pub fn name_taken(e: DieselError, name: &str) -> anyhow::Error {
+    match e {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            AppError::Conflict(format!("A variant named '{}' already exists", name)).into()
+        }
+        other => other.into()
+    }
+}
+
+// Turns a value the product already has for the variant, see the
+// product_variants_value_key index, into a client error
+pub fn value_taken(e: DieselError, variant: &Variant) -> anyhow::Error {
+    match e {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            AppError::Conflict(format!("The product already has this value for variant '{}'", variant.name)).into()
+        }
+        other => other.into()
+    }
+}
+
+// The shared definition a reference points at. Named variants are created on
+// first use; an existing definition keeps its own allowed values.
+pub fn resolve_variant(conn: &mut PgConnection, variant: &VariantRef) -> Result<Variant> {
+    match variant {
+        VariantRef::Id { id } => find_variant(conn, *id)?
+            .ok_or_else(|| AppError::Validation(format!("Variant {} does not exist", id)).into()),
+        VariantRef::Name(new_variant) => {
+            let name = normalize_name(&new_variant.name)?;
+            let created = diesel::insert_into(variants::table)
+                .values(NewVariant { name: name.clone(), allowed_values: new_variant.allowed_values.clone() })
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            debug!(variant_name = %name, created = created > 0, "Resolved variant by name");
+
+            Ok(variants::table
+                .filter(lower(btrim(variants::name)).eq(name_key(&name)))
+                .select(Variant::as_select())
+                .first(conn)?)
+        }
+    }
+}
+
+// Definitions for many references at once: one insert for names seen for the
+// first time, then one select for the names and one for the ids
+pub struct VariantLookup {
+    by_id: HashMap<Uuid, Variant>,
+    by_name: HashMap<String, Variant>
+}
+
+impl VariantLookup {
+    pub fn load<'a>(conn: &mut PgConnection, refs: impl IntoIterator<Item = &'a VariantRef>) -> QueryResult<Self> {
+        let mut ids: Vec<Uuid> = Vec::new();
+        let mut new_variants: Vec<NewVariant> = Vec::new();
+        for variant in refs {
+            match variant {
+                VariantRef::Id { id } => ids.push(*id),
+                // Invalid names are reported by `get` for the item that uses them
+                VariantRef::Name(new_variant) => {
+                    if let Ok(name) = normalize_name(&new_variant.name)
+                        && !new_variants.iter().any(|v| name_key(&v.name) == name_key(&name)) {
+                        new_variants.push(NewVariant { name, allowed_values: new_variant.allowed_values.clone() });
+                    }
+                }
+            }
+        }
+
+        if !new_variants.is_empty() {
+            let created = diesel::insert_into(variants::table)
+                .values(&new_variants)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            debug!(names = new_variants.len(), created = created, "Resolved variants by name");
+        }
+
+        let keys: Vec<String> = new_variants.iter().map(|v| name_key(&v.name)).collect();
+        let by_name = variants::table
+            .filter(lower(btrim(variants::name)).eq_any(keys))
+            .select(Variant::as_select())
+            .load::<Variant>(conn)?
+            .into_iter()
+            .map(|variant| (name_key(&variant.name), variant))
+            .collect();
+        let by_id = variants::table
+            .filter(variants::id.eq_any(ids))
+            .select(Variant::as_select())
+            .load::<Variant>(conn)?
+            .into_iter()
+            .map(|variant| (variant.id, variant))
+            .collect();
+
+        Ok(Self { by_id, by_name })
+    }
+
+    pub fn get(&self, variant: &VariantRef) -> Result<&Variant> {
+        match variant {
+            VariantRef::Id { id } => self.by_id
+                .get(id)
+                .ok_or_else(|| AppError::Validation(format!("Variant {} does not exist", id)).into()),
+            VariantRef::Name(new_variant) => {
+                let name = normalize_name(&new_variant.name)?;
+                self.by_name
+                    .get(&name_key(&name))
+                    .ok_or_else(|| anyhow::anyhow!("Variant '{}' was not resolved", name))
+            }
+        }
+    }
+}
+
+pub fn check_allowed<'a>(variant: &Variant, values: impl IntoIterator<Item = &'a str>) -> Result<()> {
+    for value in values {
+        if !variant.allows(value) {
+            return Err(AppError::Validation(format!(
+                "'{}' is not an allowed value for variant '{}'", value, variant.name
+            )).into());
+        }
+    }
+    Ok(())
+}
+
+// Resolves the variant and attaches the given values to the product
+pub fn insert_variant_values(
+    conn: &mut PgConnection,
+    product_id: Uuid,
+    variant_value: &NewVariantValue
+) -> Result<Variant> {
+    let variant = resolve_variant(conn, &variant_value.variant)?;
+    let values: Vec<&String> = variant_value.values.iter().flatten().collect();
+    check_allowed(&variant, values.iter().map(|v| v.as_str()))?;
+
+    let new_values: Vec<NewProductVariant> = values
+        .into_iter()
+        .map(|value| NewProductVariant { variant_id: variant.id, product_id, value: value.clone() })
+        .collect();
+
+    diesel::insert_into(product_variants::table)
+        .values(&new_values)
+        .execute(conn)
+        .map_err(|e| value_taken(e, &variant))?;
+
+    Ok(variant)
+}
+
+// Variant changes are changes to the products carrying them, so their version and
+// updated_at move too, cached representations get invalidated and the history
+// records the new state. Call it once the variants are as they will be saved.
+pub fn touch_products(conn: &mut PgConnection, product_ids: &[Uuid], changed_by: Option<&str>) -> QueryResult<()> {
+    if product_ids.is_empty() {
+        return Ok(());
+    }
+    let touched: Vec<Product> = diesel::update(products::table.filter(products::id.eq_any(product_ids)))
+        .set((products::version.eq(products::version + 1), products::updated_at.eq(diesel::dsl::now)))
+        .returning(Product::as_select())
+        .get_results(conn)?;
+    record_revisions(conn, &touched, RevisionAction::Update, changed_by)
+}
+
+pub fn touch_product(conn: &mut PgConnection, product_id: Uuid, changed_by: Option<&str>) -> QueryResult<()> {
+    touch_products(conn, &[product_id], changed_by)
+}
+
+// Live products carrying values of the variant. Deleted products are read-only
+// until they are restored or purged, so variant changes leave them alone.
+pub fn variant_products(conn: &mut PgConnection, variant_id: Uuid) -> QueryResult<Vec<Uuid>> {
+    product_variants::table
+        .inner_join(products::table)
+        .filter(product_variants::variant_id.eq(variant_id))
+        .filter(products::deleted_at.is_null())
+        .select(product_variants::product_id)
+        .distinct()
+        .load(conn)
+}
+
+// Whether a deleted product still carries values of the variant
+pub fn used_by_deleted_products(conn: &mut PgConnection, variant_id: Uuid) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        product_variants::table
+            .inner_join(products::table)
+            .filter(product_variants::variant_id.eq(variant_id))
+            .filter(products::deleted_at.is_not_null())
+    ))
+    .get_result(conn)
+}
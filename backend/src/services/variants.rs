@@ -0,0 +1,434 @@
+use diesel::prelude::*;
+use diesel::{ExpressionMethods, RunQueryDsl};
+use crate::config::Database;
+use crate::models::{
+    NewProductVariant, NewProductVariantValue, NewVariant, NewVariantValue, ProductVariant, ProductVariantUpdates,
+    Variant, VariantUpdate, VariantWithValues
+};
+use crate::services::product_queries::{load_variant_groups, lock_product};
+use crate::services::variant_queries::{
+    check_allowed, find_variant, insert_variant_values, name_taken, normalize_name, touch_product, touch_products,
+    used_by_deleted_products, value_taken, variant_products
+};
+use uuid::Uuid;
+use crate::schema::{product_variants, products, variants};
+use crate::traits::responses::AppError;
+use anyhow::Result;
+use tracing::{info, warn, error, instrument};
+
+pub struct VariantService {
+    pub db: Database
+}
+
+impl VariantService {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    fn product_exists(conn: &mut PgConnection, product_id: Uuid) -> QueryResult<bool> {
+        diesel::select(diesel::dsl::exists(
+            products::table
+                .filter(products::id.eq(product_id))
+                .filter(products::deleted_at.is_null())
+        ))
+            .get_result(conn)
+    }
+
+    #[instrument(skip(self), fields(product_id = %product_id))]
+    pub async fn list_product_variants(&self, product_id: Uuid) -> Result<Option<Vec<VariantWithValues>>> {
+        info!(product_id = %product_id, "Fetching variants for product");
+
+        let result = self.db.run(move |conn| Ok(conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            if !Self::product_exists(conn, product_id)? {
+                return Ok(None);
+            }
+
+            let mut grouped = load_variant_groups(conn, &[product_id])?;
+            Ok(Some(grouped.remove(&product_id).unwrap_or_default()))
+        })?)).await;
+
+        match result {
+            Ok(Some(groups)) => {
+                info!(product_id = %product_id, variant_count = groups.len(), "Product variants fetched successfully");
+                Ok(Some(groups))
+            }
+            Ok(None) => {
+                info!(product_id = %product_id, "Product not found while listing variants");
+                Ok(None)
+            }
+            Err(e) => {
+                error!(product_id = %product_id, error = %e, "Database error while listing product variants");
+                Err(e)
+            }
+        }
+    }
+
+    #[instrument(skip(self, new_variant), fields(product_id = %product_id, variant = %new_variant.variant))]
+    pub async fn add_product_variant(
+        &self,
+        product_id: Uuid,
+        new_variant: NewVariantValue,
+        changed_by: Option<&str>
+    ) -> Result<Option<VariantWithValues>> {
+        info!(product_id = %product_id, "🆕 Adding variant with {} values to product", new_variant.values.len());
+
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
+            if lock_product(conn, product_id, None)?.is_none() {
+                return Ok(None);
+            }
+
+            let variant = insert_variant_values(conn, product_id, &new_variant)?;
+            touch_product(conn, product_id, changed_by.as_deref())?;
+
+            let mut grouped = load_variant_groups(conn, &[product_id])?;
+            let group = grouped
+                .remove(&product_id)
+                .and_then(|groups| groups.into_iter().find(|g| g.variant.id == variant.id))
+                .unwrap_or(VariantWithValues { variant, values: vec![] });
+            Ok(Some(group))
+        })).await;
+
+        match result {
+            Ok(Some(group)) => {
+                info!(product_id = %product_id, variant_id = %group.variant.id, "Variant added to product successfully");
+                Ok(Some(group))
+            }
+            Ok(None) => {
+                info!(product_id = %product_id, "Product not found while adding variant");
+                Ok(None)
+            }
+            Err(e) => {
+                warn!(product_id = %product_id, error = %e, "Failed to add variant to product");
+                Err(e)
+            }
+        }
+    }
+
+    // Removes the variant's values from the product, the shared definition stays
+    #[instrument(skip(self), fields(product_id = %product_id, variant_id = %variant_id))]
+    pub async fn remove_product_variant(&self, product_id: Uuid, variant_id: Uuid, changed_by: Option<&str>) -> Result<bool> {
+        info!(product_id = %product_id, variant_id = %variant_id, "Removing variant from product");
+
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
+            if lock_product(conn, product_id, None)?.is_none() {
+                return Ok(false);
+            }
+            let removed = diesel::delete(
+                product_variants::table
+                    .filter(product_variants::product_id.eq(product_id))
+                    .filter(product_variants::variant_id.eq(variant_id))
+            ).execute(conn)?;
+
+            if removed > 0 {
+                touch_product(conn, product_id, changed_by.as_deref())?;
+            }
+            Ok(removed > 0)
+        })).await;
+
+        match result {
+            Ok(removed) => {
+                if removed {
+                    info!(product_id = %product_id, variant_id = %variant_id, "Variant removed from product successfully");
+                } else {
+                    warn!(product_id = %product_id, variant_id = %variant_id, "Variant not found on product");
+                }
+                Ok(removed)
+            }
+            Err(e) => {
+                error!(product_id = %product_id, variant_id = %variant_id, error = %e, "Database error while removing variant");
+                Err(e)
+            }
+        }
+    }
+
+    #[instrument(skip(self, new_value), fields(product_id = %product_id, variant_id = %variant_id))]
+    pub async fn add_variant_value(
+        &self,
+        product_id: Uuid,
+        variant_id: Uuid,
+        new_value: NewProductVariantValue,
+        changed_by: Option<&str>
+    ) -> Result<Option<ProductVariant>> {
+        info!(product_id = %product_id, variant_id = %variant_id, "🆕 Adding value to product variant");
+
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
+            if lock_product(conn, product_id, None)?.is_none() {
+                return Ok(None);
+            }
+            let Some(variant) = find_variant(conn, variant_id)? else {
+                return Ok(None);
+            };
+            check_allowed(&variant, [new_value.value.as_str()])?;
+
+            let value = diesel::insert_into(product_variants::table)
+                .values(NewProductVariant { variant_id, product_id, value: new_value.value })
+                .returning(ProductVariant::as_select())
+                .get_result(conn)
+                .map_err(|e| value_taken(e, &variant))?;
+            touch_product(conn, product_id, changed_by.as_deref())?;
+            Ok(Some(value))
+        })).await;
+
+        match result {
+            Ok(Some(value)) => {
+                info!(value_id = %value.id, "Variant value added successfully");
+                Ok(Some(value))
+            }
+            Ok(None) => {
+                info!(product_id = %product_id, variant_id = %variant_id, "Product or variant not found while adding value");
+                Ok(None)
+            }
+            Err(e) => {
+                warn!(product_id = %product_id, variant_id = %variant_id, error = %e, "Failed to add variant value");
+                Err(e)
+            }
+        }
+    }
+
+    #[instrument(skip(self, updates), fields(product_id = %product_id, variant_id = %variant_id, value_id = %value_id))]
+    pub async fn update_variant_value(
+        &self,
+        product_id: Uuid,
+        variant_id: Uuid,
+        value_id: Uuid,
+        updates: ProductVariantUpdates,
+        changed_by: Option<&str>
+    ) -> Result<Option<ProductVariant>> {
+        info!(value_id = %value_id, "Updating product variant value");
+
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
+            if lock_product(conn, product_id, None)?.is_none() {
+                return Ok(None);
+            }
+            let Some(variant) = find_variant(conn, variant_id)? else {
+                return Ok(None);
+            };
+            let updated = diesel::update(
+                product_variants::table
+                    .filter(product_variants::id.eq(value_id))
+                    .filter(product_variants::product_id.eq(product_id))
+                    .filter(product_variants::variant_id.eq(variant_id))
+            )
+                .set((&updates, product_variants::updated_at.eq(diesel::dsl::now)))
+                .returning(ProductVariant::as_select())
+                .get_result(conn)
+                .optional()
+                .map_err(|e| value_taken(e, &variant))?;
+
+            if updated.is_some() {
+                check_allowed(&variant, [updates.value.as_str()])?;
+                touch_product(conn, product_id, changed_by.as_deref())?;
+            }
+            Ok(updated)
+        })).await;
+
+        match result {
+            Ok(Some(value)) => {
+                info!(value_id = %value_id, "Variant value updated successfully");
+                Ok(Some(value))
+            }
+            Ok(None) => {
+                info!(value_id = %value_id, "Variant value not found for update");
+                Ok(None)
+            }
+            Err(e) => {
+                warn!(value_id = %value_id, error = %e, "Failed to update variant value");
+                Err(e)
+            }
+        }
+    }
+
+    #[instrument(skip(self), fields(product_id = %product_id, variant_id = %variant_id, value_id = %value_id))]
+    pub async fn remove_variant_value(
+        &self,
+        product_id: Uuid,
+        variant_id: Uuid,
+        value_id: Uuid,
+        changed_by: Option<&str>
+    ) -> Result<bool> {
+        info!(value_id = %value_id, "Removing product variant value");
+
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
+            if lock_product(conn, product_id, None)?.is_none() {
+                return Ok(0);
+            }
+            let removed = diesel::delete(
+                product_variants::table
+                    .filter(product_variants::id.eq(value_id))
+                    .filter(product_variants::product_id.eq(product_id))
+                    .filter(product_variants::variant_id.eq(variant_id))
+            ).execute(conn)?;
+
+            if removed > 0 {
+                touch_product(conn, product_id, changed_by.as_deref())?;
+            }
+            Ok(removed)
+        })).await;
+
+        match result {
+            Ok(removed) => {
+                if removed > 0 {
+                    info!(value_id = %value_id, "Variant value removed successfully");
+                } else {
+                    warn!(value_id = %value_id, "Variant value not found or already removed");
+                }
+                Ok(removed > 0)
+            }
+            Err(e) => {
+                error!(value_id = %value_id, error = %e, "Database error while removing variant value");
+                Err(e)
+            }
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn list_variants(&self) -> Result<Vec<Variant>> {
+        info!("Fetching variant definitions");
+
+        let result = self.db.run(|conn| {
+            Ok(variants::table
+                .order(variants::name.asc())
+                .select(Variant::as_select())
+                .load(conn)?)
+        }).await;
+
+        match result {
+            Ok(variants) => {
+                info!(variant_count = variants.len(), "Variant definitions fetched successfully");
+                Ok(variants)
+            }
+            Err(e) => {
+                error!(error = %e, "Database error while fetching variants");
+                Err(e)
+            }
+        }
+    }
+
+    #[instrument(skip(self, new_variant), fields(variant_name = %new_variant.name))]
+    pub async fn create_variant(&self, new_variant: NewVariant) -> Result<Variant> {
+        info!("🆕 Creating variant definition");
+
+        let name = normalize_name(&new_variant.name)?;
+        let inserted_name = name.clone();
+        let result = self.db.run(move |conn| {
+            diesel::insert_into(variants::table)
+                .values(NewVariant { name: inserted_name.clone(), allowed_values: new_variant.allowed_values })
+                .returning(Variant::as_select())
+                .get_result(conn)
+                .map_err(|e| name_taken(e, &inserted_name))
+        }).await;
+
+        match result {
+            Ok(variant) => {
+                info!(variant_id = %variant.id, "Variant definition created successfully");
+                Ok(variant)
+            }
+            Err(e) => {
+                warn!(variant_name = %name, error = %e, "Failed to create variant definition");
+                Err(e)
+            }
+        }
+    }
+
+    #[instrument(skip(self), fields(variant_id = %variant_id))]
+    pub async fn get_variant(&self, variant_id: Uuid) -> Result<Option<Variant>> {
+        info!(variant_id = %variant_id, "🔍 Fetching variant by ID");
+
+        let result = self.db.run(move |conn| Ok(find_variant(conn, variant_id)?)).await;
+
+        match result {
+            Ok(variant) => Ok(variant),
+            Err(e) => {
+                error!(variant_id = %variant_id, error = %e, "Database error while fetching variant");
+                Err(e)
+            }
+        }
+    }
+
+    // Renames the definition or changes its allowed values, which must still cover the values in use
+    #[instrument(skip(self, updates), fields(variant_id = %variant_id))]
+    pub async fn update_variant(&self, variant_id: Uuid, updates: VariantUpdate, changed_by: Option<&str>) -> Result<Option<Variant>> {
+        info!(variant_id = %variant_id, "Updating variant definition");
+
+        let name = updates.name.as_deref().map(normalize_name).transpose()?;
+        let updates = VariantUpdate { name: name.clone(), ..updates };
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
+            let updated = diesel::update(variants::table.filter(variants::id.eq(variant_id)))
+                .set((&updates, variants::updated_at.eq(diesel::dsl::now)))
+                .returning(Variant::as_select())
+                .get_result(conn)
+                .optional()
+                .map_err(|e| name_taken(e, name.as_deref().unwrap_or_default()))?;
+
+            if let Some(variant) = &updated {
+                let carrying = variant_products(conn, variant_id)?;
+                touch_products(conn, &carrying, changed_by.as_deref())?;
+                let in_use: Vec<String> = product_variants::table
+                    .filter(product_variants::variant_id.eq(variant_id))
+                    .filter(product_variants::value.is_not_null())
+                    .select(product_variants::value.assume_not_null())
+                    .distinct()
+                    .load(conn)?;
+                check_allowed(variant, in_use.iter().map(String::as_str))?;
+            }
+            Ok(updated)
+        })).await;
+
+        match result {
+            Ok(Some(variant)) => {
+                info!(variant_id = %variant_id, variant_name = %variant.name, "Variant updated successfully");
+                Ok(Some(variant))
+            }
+            Ok(None) => {
+                info!(variant_id = %variant_id, "Variant not found for update");
+                Ok(None)
+            }
+            Err(e) => {
+                warn!(variant_id = %variant_id, error = %e, "Failed to update variant");
+                Err(e)
+            }
+        }
+    }
+
+    // Deleting a variant cascades to every product value that uses it. Deleted products
+    // can't lose values behind their back, so they have to be restored or purged first.
+    #[instrument(skip(self), fields(variant_id = %variant_id))]
+    pub async fn delete_variant(&self, variant_id: Uuid, changed_by: Option<&str>) -> Result<bool> {
+        info!(variant_id = %variant_id, "Deleting variant");
+
+        let changed_by = changed_by.map(str::to_string);
+        let result = self.db.run(move |conn| conn.transaction::<_, anyhow::Error, _>(|conn| {
+            if used_by_deleted_products(conn, variant_id)? {
+                return Err(AppError::Conflict(
+                    "Deleted products still carry values of this variant; restore or purge them first".to_string()
+                ).into());
+            }
+            let carrying = variant_products(conn, variant_id)?;
+            let deleted = diesel::delete(variants::table.filter(variants::id.eq(variant_id))).execute(conn)?;
+            // Recorded once the cascade removed the values, so the revisions no longer list them
+            touch_products(conn, &carrying, changed_by.as_deref())?;
+            Ok(deleted)
+        })).await;
+
+        match result {
+            Ok(deleted) => {
+                if deleted > 0 {
+                    info!(variant_id = %variant_id, "Variant deleted successfully");
+                } else {
+                    warn!(variant_id = %variant_id, "Variant not found or already deleted");
+                }
+                Ok(deleted > 0)
+            }
+            Err(e) => {
+                warn!(variant_id = %variant_id, error = %e, "Failed to delete variant");
+                Err(e)
+            }
+        }
+    }
+}
@@ -1,10 +1,283 @@
-use actix_web::{HttpResponse, Result as ActixResult};
-use serde::Serialize;
-use crate::models::Product;
+use std::fmt;
+use std::time::SystemTime;
+use actix_web::http::header::{self, HttpDate};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, ResponseError, Result as ActixResult};
+use chrono::{DateTime, Utc};
+use diesel::r2d2::PoolError;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{error, warn};
+use crate::models::{
+    names_tag, FieldError, version_etag, BulkChangeResult, BulkCreateResult, CompleteProduct, ImportReport, Page, Product, ProductVariant, PurgeSummary, Variant, VariantWithValues
+};
 
-#[derive(Serialize)]
-pub struct ErrorResponse {
-    pub error: String
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+// Prefix of the `type` of every problem, followed by its code
+pub const PROBLEM_TYPE_PREFIX: &str = "urn:problem-type:";
+
+// RFC 7807 problem details, the body of every error response. `instance` and
+// `request_id` are filled in by the problem middleware once the request is known.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    // Same as the end of `type`, for clients that only want the identifier
+    pub code: String,
+    // Every failing field of a request body that didn't validate
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+    // Outcome of every item of a bulk request or import none of whose items went through
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<serde_json::Value>
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, code: &str, detail: impl Into<String>) -> Self {
+        Self {
+            problem_type: format!("{}{}", PROBLEM_TYPE_PREFIX, code),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: detail.into(),
+            instance: None,
+            request_id: None,
+            code: code.to_string(),
+            errors: Vec::new(),
+            report: None
+        }
+    }
+
+    // For failures that only have a status, such as those raised by actix itself
+    pub fn from_status(status: StatusCode, detail: impl Into<String>) -> Self {
+        let code = match status {
+            StatusCode::BAD_REQUEST => "bad_request",
+            StatusCode::NOT_FOUND => "not_found",
+            StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+            StatusCode::NOT_ACCEPTABLE => "not_acceptable",
+            StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+            StatusCode::SERVICE_UNAVAILABLE => "service_unavailable",
+            status if status.is_server_error() => "internal_error",
+            _ => "client_error"
+        };
+        Self::new(status, code, detail)
+    }
+
+    pub fn with_request(self, instance: String, request_id: Option<String>) -> Self {
+        Self { instance: Some(instance), request_id, ..self }
+    }
+
+    // The problem is kept in the response extensions so the middleware can complete it
+    pub fn into_response(self) -> HttpResponse {
+        let mut response = HttpResponse::build(StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR))
+            .content_type(PROBLEM_CONTENT_TYPE)
+            .json(&self);
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+// Every failure a client can see. Services raise these through `anyhow`, and
+// anything else is classified by `From<anyhow::Error>`. Only the messages of the
+// client-facing variants are returned; the causes of the others are logged.
+#[derive(Debug)]
+pub enum AppError {
+    // A 400 for input the service itself rejects, e.g. an unparsable cursor or a
+    // variant value outside the variant's allowed values. Bodies that break the
+    // declared field rules are `InvalidFields` instead.
+    Validation(String),
+    NotFound(String),
+    // Clashes with the current state, such as a name that is already taken
+    Conflict(String),
+    // An If-Match header naming a version other than the current one
+    PreconditionFailed,
+    // Understood but refused, such as an Idempotency-Key reused for another request
+    Unprocessable(String),
+    // A request body that broke one or more validation rules
+    InvalidFields(Vec<FieldError>),
+    // The database can't be reached or the pool has no connection to spare
+    Unavailable(anyhow::Error),
+    Internal(anyhow::Error)
+}
+
+impl AppError {
+    // Stable identifier clients can match on, unlike the message
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Validation(_) => "validation_failed",
+            Self::NotFound(_) => "not_found",
+            Self::Conflict(_) => "conflict",
+            Self::PreconditionFailed => "precondition_failed",
+            Self::Unprocessable(_) => "unprocessable",
+            Self::InvalidFields(_) => "invalid_fields",
+            Self::Unavailable(_) => "service_unavailable",
+            Self::Internal(_) => "internal_error"
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Validation(message) |
+            Self::NotFound(message) |
+            Self::Conflict(message) |
+            Self::Unprocessable(message) => write!(f, "{}", message),
+            Self::PreconditionFailed => write!(f, "The resource has been modified since it was fetched"),
+            Self::InvalidFields(errors) if errors.len() == 1 => write!(f, "1 field is invalid"),
+            Self::InvalidFields(errors) => write!(f, "{} fields are invalid", errors.len()),
+            Self::Unavailable(_) => write!(f, "The service is temporarily unavailable, please retry"),
+            Self::Internal(_) => write!(f, "Internal server error")
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Unavailable(cause) | Self::Internal(cause) => Some(cause.as_ref()),
+            _ => None
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Validation(_) => StatusCode::BAD_REQUEST,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            Self::Unprocessable(_) | Self::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let Self::Unavailable(cause) | Self::Internal(cause) = self {
+            error!(code = self.code(), error = %cause, "Request failed");
+        }
+        let mut problem = Problem::new(self.status_code(), self.code(), self.to_string());
+        if let Self::InvalidFields(errors) = self {
+            problem.errors = errors.clone();
+        }
+        problem.into_response()
+    }
+}
+
+impl From<DieselError> for AppError {
+    fn from(err: DieselError) -> Self {
+        match err {
+            DieselError::NotFound => Self::NotFound("Not found".to_string()),
+            DieselError::DatabaseError(kind, ref info) => {
+                // Constraint and column names stay in the log
+                warn!(kind = ?kind, constraint = ?info.constraint_name(), error = %err, "Database rejected the request");
+                match kind {
+                    DatabaseErrorKind::UniqueViolation => {
+                        Self::Conflict("A resource with the same identifier already exists".to_string())
+                    }
+                    DatabaseErrorKind::ForeignKeyViolation => {
+                        Self::Conflict("The change conflicts with a related resource".to_string())
+                    }
+                    DatabaseErrorKind::SerializationFailure => {
+                        Self::Conflict("The resource was changed concurrently, please retry".to_string())
+                    }
+                    DatabaseErrorKind::NotNullViolation | DatabaseErrorKind::CheckViolation => {
+                        Self::Validation("The request violates a data constraint".to_string())
+                    }
+                    DatabaseErrorKind::ClosedConnection | DatabaseErrorKind::UnableToSendCommand => {
+                        Self::Unavailable(err.into())
+                    }
+                    // Cancelled by the statement timeout
+                    DatabaseErrorKind::Unknown if info.message().contains("statement timeout") => {
+                        Self::Unavailable(err.into())
+                    }
+                    _ => Self::Internal(err.into())
+                }
+            }
+            other => Self::Internal(other.into())
+        }
+    }
+}
+
+impl From<PoolError> for AppError {
+    fn from(err: PoolError) -> Self {
+        Self::Unavailable(err.into())
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<AppError>() {
+            Ok(app_error) => return app_error,
+            Err(err) => err
+        };
+        let err = match err.downcast::<DieselError>() {
+            Ok(diesel_error) => return diesel_error.into(),
+            Err(err) => err
+        };
+        match err.downcast::<PoolError>() {
+            Ok(pool_error) => pool_error.into(),
+            Err(err) => Self::Internal(err)
+        }
+    }
+}
+
+pub fn error_response(err: anyhow::Error) -> ActixResult<HttpResponse> {
+    Ok(AppError::from(err).error_response())
+}
+
+// Response bodies that carry an ETag and Last-Modified header
+pub trait EntityTag {
+    fn entity_tag(&self) -> Option<String> {
+        None
+    }
+
+    fn last_modified(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+}
+
+impl EntityTag for Product {
+    fn entity_tag(&self) -> Option<String> {
+        Some(version_etag(self.version, None))
+    }
+
+    fn last_modified(&self) -> Option<DateTime<Utc>> {
+        Some(self.updated_at)
+    }
+}
+
+impl EntityTag for CompleteProduct {
+    fn entity_tag(&self) -> Option<String> {
+        Some(version_etag(self.product.version, Some("variants")))
+    }
+
+    fn last_modified(&self) -> Option<DateTime<Utc>> {
+        self.product.last_modified()
+    }
+}
+
+impl EntityTag for Variant {}
+impl EntityTag for VariantWithValues {}
+impl EntityTag for ProductVariant {}
+impl<T> EntityTag for Vec<T> {}
+
+fn ok_with_tag<T: Serialize + EntityTag>(data: T) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    if let Some(tag) = data.entity_tag() {
+        response.insert_header((header::ETAG, tag));
+    }
+    response.json(data)
 }
 
 pub trait ResponseHelper {
@@ -15,26 +288,18 @@ impl ResponseHelper for anyhow::Result<bool> {
     fn to_response(self) -> ActixResult<HttpResponse> {
         match self {
             Ok(true) => Ok(HttpResponse::NoContent().finish()),
-            Ok(false) => Ok(HttpResponse::NotFound().json(ErrorResponse {
-                error: "Not found".to_string()
-            })),
-            Err(err) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: err.to_string()
-            }))
+            Ok(false) => Ok(AppError::NotFound("Not found".to_string()).error_response()),
+            Err(err) => error_response(err)
         }
     }
 }
 
-impl<T: Serialize> ResponseHelper for anyhow::Result<Option<T>> {
+impl<T: Serialize + EntityTag> ResponseHelper for anyhow::Result<Option<T>> {
     fn to_response(self) -> ActixResult<HttpResponse> {
         match self {
-            Ok(Some(data)) => Ok(HttpResponse::Ok().json(data)),
-            Ok(None) => Ok(HttpResponse::NotFound().json(ErrorResponse {
-                error: "Not found".to_string()
-            })),
-            Err(err) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: err.to_string()
-            }))
+            Ok(Some(data)) => Ok(ok_with_tag(data)),
+            Ok(None) => Ok(AppError::NotFound("Not found".to_string()).error_response()),
+            Err(err) => error_response(err)
         }
     }
 }
@@ -43,20 +308,172 @@ impl<T: Serialize> ResponseHelper for anyhow::Result<Vec<T>> {
     fn to_response(self) -> ActixResult<HttpResponse> {
         match self {
             Ok(data) => Ok(HttpResponse::Ok().json(data)),
-            Err(err) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: err.to_string()
-            }))
+            Err(err) => error_response(err)
+        }
+    }
+}
+
+impl<T: Serialize> ResponseHelper for anyhow::Result<Page<T>> {
+    fn to_response(self) -> ActixResult<HttpResponse> {
+        match self {
+            Ok(page) => Ok(HttpResponse::Ok().json(page)),
+            Err(err) => error_response(err)
         }
     }
 }
 
 impl ResponseHelper for anyhow::Result<Product> {
+    fn to_response(self) -> ActixResult<HttpResponse> {
+        match self {
+            Ok(data) => Ok(ok_with_tag(data)),
+            Err(err) => error_response(err)
+        }
+    }
+}
+
+impl ResponseHelper for anyhow::Result<BulkChangeResult> {
+    fn to_response(self) -> ActixResult<HttpResponse> {
+        match self {
+            Ok(data) => Ok(HttpResponse::Ok().json(data)),
+            Err(err) => error_response(err)
+        }
+    }
+}
+
+// A batch none of whose items went through failed as a whole, so it is answered
+// with a problem that still carries what went wrong with each item
+fn failed_batch(detail: String, report: &impl Serialize) -> ActixResult<HttpResponse> {
+    let mut problem = Problem::new(StatusCode::BAD_REQUEST, "batch_failed", detail);
+    problem.report = serde_json::to_value(report).ok();
+    Ok(problem.into_response())
+}
+
+impl ResponseHelper for anyhow::Result<ImportReport> {
+    fn to_response(self) -> ActixResult<HttpResponse> {
+        match self {
+            Ok(data) if data.failed > 0 && data.failed == data.rows.len() => {
+                failed_batch(format!("None of the {} rows could be imported", data.failed), &data)
+            }
+            Ok(data) => Ok(HttpResponse::Ok().json(data)),
+            Err(err) => error_response(err)
+        }
+    }
+}
+
+// Nothing created at all is a client error, partial success in per-item mode is not.
+// A batch whose every item broke validation rules is answered like a single invalid body.
+impl ResponseHelper for anyhow::Result<BulkCreateResult> {
+    fn to_response(self) -> ActixResult<HttpResponse> {
+        match self {
+            Ok(data) if data.failed > 0 && data.items.iter().all(|item| !item.errors.is_empty()) => {
+                let errors = data.items.into_iter().flat_map(|item| item.errors).collect();
+                Ok(AppError::InvalidFields(errors).error_response())
+            }
+            Ok(data) if data.created == 0 && data.failed > 0 => {
+                failed_batch(format!("None of the {} products could be created", data.failed), &data)
+            }
+            Ok(data) => Ok(HttpResponse::Ok().json(data)),
+            Err(err) => error_response(err)
+        }
+    }
+}
+
+impl ResponseHelper for anyhow::Result<PurgeSummary> {
     fn to_response(self) -> ActixResult<HttpResponse> {
         match self {
             Ok(data) => Ok(HttpResponse::Ok().json(data)),
-            Err(err) => Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: err.to_string()
-            }))
+            Err(err) => error_response(err)
+        }
+    }
+}
+
+impl ResponseHelper for anyhow::Result<Variant> {
+    fn to_response(self) -> ActixResult<HttpResponse> {
+        match self {
+            Ok(data) => Ok(HttpResponse::Ok().json(data)),
+            Err(err) => error_response(err)
+        }
+    }
+}
+
+// Reads that clients and CDNs may cache. Responses carry validators plus the given
+// Cache-Control, and a request whose validators still match gets a bodiless 304.
+pub trait CachedResponseHelper {
+    fn to_cached_response(self, req: &HttpRequest, cache_control: &str) -> ActixResult<HttpResponse>;
+}
+
+// Strong tag for bodies without a version of their own, such as a page of products
+fn body_etag(body: &[u8]) -> String {
+    let digest: String = Sha256::digest(body)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("\"{}\"", digest)
+}
+
+// If-None-Match wins over If-Modified-Since when both are sent
+fn is_not_modified(req: &HttpRequest, etag: &str, last_modified: Option<DateTime<Utc>>) -> bool {
+    if let Some(if_none_match) = req.headers().get(header::IF_NONE_MATCH) {
+        return names_tag(if_none_match.to_str().unwrap_or_default(), etag);
+    }
+
+    let since = req.headers()
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<HttpDate>().ok())
+        .map(|date| DateTime::<Utc>::from(SystemTime::from(date)));
+
+    match (last_modified, since) {
+        // HTTP dates only have second precision
+        (Some(modified), Some(since)) => modified.timestamp() <= since.timestamp(),
+        _ => false
+    }
+}
+
+fn cached_ok<T: Serialize>(
+    req: &HttpRequest,
+    cache_control: &str,
+    data: &T,
+    etag: Option<String>,
+    last_modified: Option<DateTime<Utc>>
+) -> ActixResult<HttpResponse> {
+    let body = match serde_json::to_vec(data) {
+        Ok(body) => body,
+        Err(e) => return error_response(e.into())
+    };
+    let etag = etag.unwrap_or_else(|| body_etag(&body));
+    let not_modified = is_not_modified(req, &etag, last_modified);
+
+    let mut response = if not_modified { HttpResponse::NotModified() } else { HttpResponse::Ok() };
+    response.insert_header((header::ETAG, etag));
+    response.insert_header((header::CACHE_CONTROL, cache_control));
+    if let Some(modified) = last_modified {
+        response.insert_header((header::LAST_MODIFIED, HttpDate::from(SystemTime::from(modified))));
+    }
+
+    if not_modified {
+        Ok(response.finish())
+    } else {
+        Ok(response.content_type("application/json").body(body))
+    }
+}
+
+impl<T: Serialize + EntityTag> CachedResponseHelper for anyhow::Result<Option<T>> {
+    fn to_cached_response(self, req: &HttpRequest, cache_control: &str) -> ActixResult<HttpResponse> {
+        match self {
+            Ok(Some(data)) => cached_ok(req, cache_control, &data, data.entity_tag(), data.last_modified()),
+            other => other.to_response()
+        }
+    }
+}
+
+// A page changes whenever anything in it does, so its tag is taken from the body.
+// Products also drop out of pages, so it is last modified when the catalog was.
+impl<T: Serialize> CachedResponseHelper for anyhow::Result<Page<T>> {
+    fn to_cached_response(self, req: &HttpRequest, cache_control: &str) -> ActixResult<HttpResponse> {
+        match self {
+            Ok(page) => cached_ok(req, cache_control, &page, None, page.last_modified),
+            Err(err) => error_response(err)
         }
     }
 }
@@ -0,0 +1,77 @@
+// Integration tests for the Database executor
+// These tests require a running PostgreSQL database
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use backend::config::{create_pool, get_settings, Database, Settings};
+use backend::traits::responses::AppError;
+use diesel::connection::SimpleConnection;
+
+// A pool with a single connection, so one running job makes the next one wait
+fn single_connection(acquire_timeout_ms: u64, statement_timeout_ms: u64) -> Database {
+    let settings = Settings {
+        max_pool_size: 1,
+        min_idle_size: 0,
+        db_acquire_timeout_ms: acquire_timeout_ms,
+        db_statement_timeout_ms: statement_timeout_ms,
+        ..get_settings().unwrap()
+    };
+    Database::new(create_pool(&settings))
+}
+
+fn sleep_job(seconds: f64) -> impl FnOnce(&mut diesel::PgConnection) -> anyhow::Result<()> + Send + 'static {
+    move |conn| Ok(conn.batch_execute(&format!("SELECT pg_sleep({})", seconds))?)
+}
+
+#[tokio::test]
+async fn test_waiting_for_a_connection_times_out() {
+    let db = single_connection(200, 30_000);
+
+    let busy = tokio::spawn({
+        let db = db.clone();
+        async move { db.run(sleep_job(1.0)).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let err = db.run(|_| Ok(())).await.unwrap_err();
+    assert_eq!(AppError::from(err).code(), "service_unavailable");
+
+    busy.await.unwrap().unwrap();
+    // Once the slot is free again, jobs run normally
+    assert_eq!(db.run(|_| Ok(42)).await.unwrap(), 42);
+}
+
+#[tokio::test]
+async fn test_dropped_requests_skip_their_work() {
+    let db = single_connection(5_000, 30_000);
+
+    let busy = tokio::spawn({
+        let db = db.clone();
+        async move { db.run(sleep_job(0.5)).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // The client goes away while the job is still queued
+    let ran = Arc::new(AtomicBool::new(false));
+    let queued = db.run({
+        let ran = ran.clone();
+        move |_| {
+            ran.store(true, Ordering::Relaxed);
+            Ok(())
+        }
+    });
+    assert!(tokio::time::timeout(Duration::from_millis(100), queued).await.is_err());
+
+    busy.await.unwrap().unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!ran.load(Ordering::Relaxed));
+}
+
+#[tokio::test]
+async fn test_slow_statements_are_cancelled() {
+    let db = single_connection(5_000, 100);
+
+    let err = db.run(sleep_job(2.0)).await.unwrap_err();
+    assert_eq!(AppError::from(err).code(), "service_unavailable");
+}
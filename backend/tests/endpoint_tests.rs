@@ -1,5 +1,3 @@
-use reqwest;
-use tokio;
 use serde_json::{json, Value};
 use uuid::Uuid;
 
@@ -12,7 +10,7 @@ async fn test_endpoint_get_all_products() {
     
     // Test GET /products
     let response = client
-        .get(&format!("{}/products", TEST_SERVER_URL))
+        .get(format!("{}/products", TEST_SERVER_URL))
         .send()
         .await;
 
@@ -26,7 +24,7 @@ async fn test_endpoint_get_all_products() {
     assert_eq!(response.status(), 200);
     
     let body: Value = response.json().await.unwrap();
-    assert!(body.is_array());
+    assert!(body["items"].is_array());
 }
 
 #[tokio::test] 
@@ -36,7 +34,7 @@ async fn test_endpoint_create_product() {
     let new_product = json!({
         "product": {
             "name": "HTTP Integration Test Product",
-            "cost": 39.99,
+            "cost": { "amount": "39.99", "currency": "USD" },
             "active": true
         },
         "variants": []
@@ -44,7 +42,7 @@ async fn test_endpoint_create_product() {
 
     // Test POST /products
     let response = client
-        .post(&format!("{}/products", TEST_SERVER_URL))
+        .post(format!("{}/products", TEST_SERVER_URL))
         .json(&new_product)
         .send()
         .await;
@@ -59,11 +57,402 @@ async fn test_endpoint_create_product() {
     
     let body: Value = response.json().await.unwrap();
     assert_eq!(body["name"], "HTTP Integration Test Product");
-    assert_eq!(body["cost"], 39.99);
+    assert_eq!(body["cost"]["amount"], "39.99");
     assert_eq!(body["active"], true);
     assert!(body["id"].is_string()); // UUID is serialized as string
 }
 
+#[tokio::test]
+async fn test_endpoint_create_product_invalid_fields() {
+    let client = reqwest::Client::new();
+
+    let invalid_product = json!({
+        "product": {
+            "name": " ",
+            "cost": { "amount": "-5.00", "currency": "USD" },
+            "active": true
+        },
+        "variants": [
+            { "variant": { "name": "Size" }, "values": ["41"] },
+            { "variant": { "name": "Size" }, "values": ["42", ""] }
+        ]
+    });
+
+    let response = client
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .json(&invalid_product)
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let response = response.unwrap();
+    assert_eq!(response.status(), 422);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "invalid_fields");
+    let fields: Vec<&str> = body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, vec!["product.name", "product.cost", "variants[1].values[1]", "variants[1].variant"]);
+
+    // Replacements are checked the same way
+    let response = client
+        .put(format!("{}/products/{}", TEST_SERVER_URL, Uuid::new_v4()))
+        .json(&json!({ "name": "Shoe", "cost": { "amount": "-1.00", "currency": "USD" }, "active": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["errors"][0]["field"], "cost");
+}
+
+#[tokio::test]
+async fn test_endpoint_bulk_create_products() {
+    let client = reqwest::Client::new();
+    let size = format!("Size {}", Uuid::new_v4().simple());
+    let item = |name: &str, value: &str| json!({
+        "product": {
+            "name": name,
+            "cost": { "amount": "59.99", "currency": "USD" },
+            "active": true
+        },
+        "variants": [{ "variant": { "name": size, "allowed_values": ["41", "42"] }, "values": [value] }]
+    });
+    let payload = json!([item("Bulk Test A", "41"), item("Bulk Test B", "45"), item("Bulk Test C", "42")]);
+    
+    // Test POST /products/bulk, atomic by default
+    let response = client
+        .post(format!("{}/products/bulk", TEST_SERVER_URL))
+        .json(&payload)
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let response = response.unwrap();
+    assert_eq!(response.status(), 400);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "batch_failed");
+    assert_eq!(body["report"]["created"], 0);
+    assert_eq!(body["report"]["failed"], 3);
+    
+    // Test per-item mode
+    let response = client
+        .post(format!("{}/products/bulk?mode=per_item", TEST_SERVER_URL))
+        .json(&payload)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["created"], 2);
+    assert_eq!(body["failed"], 1);
+    let items = body["items"].as_array().unwrap();
+    assert!(items[1]["error"].is_string());
+    
+    // Test a batch whose every item is invalid is answered with the field paths of each item
+    let mut blank = item(" ", "41");
+    blank["product"]["cost"]["amount"] = json!("-1.00");
+    let response = client
+        .post(format!("{}/products/bulk?mode=per_item", TEST_SERVER_URL))
+        .json(&json!([item("Bulk Test D", ""), blank]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "invalid_fields");
+    let fields: Vec<&str> = body["errors"].as_array().unwrap().iter().map(|e| e["field"].as_str().unwrap()).collect();
+    assert_eq!(fields, vec!["[0].variants[0].values[0]", "[1].product.name", "[1].product.cost"]);
+    
+    // Test an unknown mode
+    let response = client
+        .post(format!("{}/products/bulk?mode=sometimes", TEST_SERVER_URL))
+        .json(&payload)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    
+    // Clean up: delete the created products
+    for item in items.iter().filter(|item| item["id"].is_string()) {
+        let _ = client
+            .delete(format!("{}/products/{}", TEST_SERVER_URL, item["id"].as_str().unwrap()))
+            .send()
+            .await;
+    }
+}
+
+#[tokio::test]
+async fn test_endpoint_import_products() {
+    let client = reqwest::Client::new();
+    let brand = format!("import{}", Uuid::new_v4().simple());
+    let csv = format!(
+        "name,cost,active,variant:Color {}\n{} Court,65.00,true,White|Green\n{} Bad,cheap,true,\n",
+        brand, brand, brand
+    );
+    
+    // Test POST /products/import as a dry run
+    let response = client
+        .post(format!("{}/products/import?dry_run=true", TEST_SERVER_URL))
+        .header("Content-Type", "text/csv")
+        .body(csv.clone())
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let response = response.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["dry_run"], true);
+    assert_eq!(body["created"], 1);
+    assert_eq!(body["failed"], 1);
+    assert_eq!(body["rows"][1]["line"], 3);
+    
+    // Test POST /products/import
+    let response = client
+        .post(format!("{}/products/import", TEST_SERVER_URL))
+        .header("Content-Type", "text/csv")
+        .body(csv)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    let product_id = body["rows"][0]["id"].as_str().unwrap().to_string();
+    
+    let response = client
+        .get(format!("{}/products/{}?include=variants", TEST_SERVER_URL, product_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let product: Value = response.json().await.unwrap();
+    assert_eq!(product["variants"][0]["values"].as_array().unwrap().len(), 2);
+    
+    // Test an import where every row fails is a problem carrying the report
+    let response = client
+        .post(format!("{}/products/import", TEST_SERVER_URL))
+        .header("Content-Type", "text/csv")
+        .body(format!("name,cost\n{} Bad,cheap\n", brand))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "batch_failed");
+    assert_eq!(body["report"]["failed"], 1);
+    assert_eq!(body["report"]["rows"][0]["line"], 2);
+    
+    // Test an import with an unknown column
+    let response = client
+        .post(format!("{}/products/import", TEST_SERVER_URL))
+        .header("Content-Type", "text/csv")
+        .body("name,cost,colour\nShoe,1.00,Red\n")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    
+    // Clean up: delete the imported product
+    let _ = client
+        .delete(format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .send()
+        .await;
+}
+
+#[tokio::test]
+async fn test_endpoint_export_products() {
+    let client = reqwest::Client::new();
+    let brand = format!("export{}", Uuid::new_v4().simple());
+    
+    let response = client
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .json(&json!({
+            "product": {
+                "name": format!("{} Loafer", brand),
+                "cost": { "amount": "95.00", "currency": "USD" },
+                "active": true
+            },
+            "variants": [{ "variant": { "name": format!("Color {}", brand) }, "values": ["Tan", "Brown"] }]
+        }))
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let created: Value = response.unwrap().json().await.unwrap();
+    let product_id = created["id"].as_str().unwrap().to_string();
+    
+    // Test GET /products/export as CSV, the default
+    let response = client
+        .get(format!("{}/products/export?name={}", TEST_SERVER_URL, brand))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/csv"));
+    assert!(response.headers()["content-disposition"].to_str().unwrap().contains("products.csv"));
+    let body = response.text().await.unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines[0], format!("id,name,cost,currency,active,variant:Color {}", brand));
+    assert_eq!(lines[1], format!("{},{} Loafer,95.00,USD,true,Brown|Tan", product_id, brand));
+    assert_eq!(lines.len(), 2);
+    
+    // Test GET /products/export as NDJSON
+    let response = client
+        .get(format!("{}/products/export?format=ndjson&name={}", TEST_SERVER_URL, brand))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let body = response.text().await.unwrap();
+    let product: Value = serde_json::from_str(body.lines().next().unwrap()).unwrap();
+    assert_eq!(product["id"], product_id.as_str());
+    assert_eq!(product["variants"][0]["values"].as_array().unwrap().len(), 2);
+    
+    // Test paging parameters and an unknown format
+    let response = client
+        .get(format!("{}/products/export?limit=10", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client
+        .get(format!("{}/products/export?format=xml", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    
+    // Clean up: delete the test product
+    let _ = client
+        .delete(format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .send()
+        .await;
+}
+
+#[tokio::test]
+async fn test_endpoint_bulk_update_and_delete() {
+    let client = reqwest::Client::new();
+    let brand = format!("bulk{}", Uuid::new_v4().simple());
+    let products_url = format!("{}/products", TEST_SERVER_URL);
+    
+    let mut ids = Vec::new();
+    for amount in ["20.00", "3.00"] {
+        let response = client
+            .post(&products_url)
+            .json(&json!({
+                "product": {
+                    "name": format!("{} {}", brand, amount),
+                    "cost": { "amount": amount, "currency": "USD" },
+                    "active": true
+                },
+                "variants": []
+            }))
+            .send()
+            .await;
+        if response.is_err() {
+            println!("Server not running, skipping endpoint tests");
+            return;
+        }
+        let created: Value = response.unwrap().json().await.unwrap();
+        ids.push(created["id"].as_str().unwrap().to_string());
+    }
+    
+    // Test PATCH /products without any filter
+    let response = client
+        .patch(&products_url)
+        .json(&json!({ "active": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    
+    // Test a dry run of PATCH /products
+    let response = client
+        .patch(format!("{}?name={}&dry_run=true", products_url, brand))
+        .json(&json!({ "cost_adjustment": { "op": "multiply", "by": "1.5" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["dry_run"], true);
+    assert_eq!(body["matched"], 2);
+    
+    // Test PATCH /products deactivating everything under $5
+    let response = client
+        .patch(format!("{}?name={}&cost_le=5&currency=USD", products_url, brand))
+        .json(&json!({ "active": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["matched"], 1);
+    assert_eq!(body["ids"][0], ids[1].as_str());
+    
+    // Test PATCH /products with a cost adjustment
+    let response = client
+        .patch(format!("{}?name={}", products_url, brand))
+        .json(&json!({ "cost_adjustment": { "op": "multiply", "by": "1.5" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let product: Value = client
+        .get(format!("{}/{}", products_url, ids[0]))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(product["cost"]["amount"], "30.00");
+    
+    // Test DELETE /products
+    let response = client
+        .delete(format!("{}?name={}", products_url, brand))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["matched"], 2);
+    
+    let response = client
+        .get(format!("{}/{}", products_url, ids[0]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
 #[tokio::test]
 async fn test_endpoint_create_product_with_variants() {
     let client = reqwest::Client::new();
@@ -71,7 +460,7 @@ async fn test_endpoint_create_product_with_variants() {
     let new_product = json!({
         "product": {
             "name": "Product with Variants HTTP Test",
-            "cost": 59.99,
+            "cost": { "amount": "59.99", "currency": "USD" },
             "active": true
         },
         "variants": [
@@ -91,7 +480,7 @@ async fn test_endpoint_create_product_with_variants() {
     });
 
     let response = client
-        .post(&format!("{}/products", TEST_SERVER_URL))
+        .post(format!("{}/products", TEST_SERVER_URL))
         .json(&new_product)
         .send()
         .await;
@@ -114,7 +503,7 @@ async fn test_endpoint_get_products_with_filters() {
     
     // Test with name filter
     let response = client
-        .get(&format!("{}/products?name=test", TEST_SERVER_URL))
+        .get(format!("{}/products?name=test", TEST_SERVER_URL))
         .send()
         .await;
 
@@ -127,7 +516,7 @@ async fn test_endpoint_get_products_with_filters() {
     assert_eq!(response.status(), 200);
     
     let body: Value = response.json().await.unwrap();
-    assert!(body.is_array());
+    assert!(body["items"].is_array());
 }
 
 #[tokio::test]
@@ -136,7 +525,7 @@ async fn test_endpoint_get_products_with_cost_filters() {
     
     // Test with cost range filters
     let response = client
-        .get(&format!("{}/products?cost_ge=10.0&cost_le=100.0", TEST_SERVER_URL))
+        .get(format!("{}/products?cost_ge=10.0&cost_le=100.0&currency=USD", TEST_SERVER_URL))
         .send()
         .await;
 
@@ -149,7 +538,15 @@ async fn test_endpoint_get_products_with_cost_filters() {
     assert_eq!(response.status(), 200);
     
     let body: Value = response.json().await.unwrap();
-    assert!(body.is_array());
+    assert!(body["items"].is_array());
+    
+    // Test cost bounds without a currency are rejected rather than compared across currencies
+    let response = client
+        .get(format!("{}/products?cost_ge=10.0", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
 }
 
 #[tokio::test]
@@ -158,7 +555,7 @@ async fn test_endpoint_get_products_with_active_filter() {
     
     // Test with active filter
     let response = client
-        .get(&format!("{}/products?is_active=true", TEST_SERVER_URL))
+        .get(format!("{}/products?is_active=true", TEST_SERVER_URL))
         .send()
         .await;
 
@@ -170,18 +567,884 @@ async fn test_endpoint_get_products_with_active_filter() {
     let response = response.unwrap();
     assert_eq!(response.status(), 200);
     
-    let body: Value = response.json().await.unwrap();
-    assert!(body.is_array());
+    let body: Value = response.json().await.unwrap();
+    assert!(body["items"].is_array());
+}
+
+#[tokio::test]
+async fn test_endpoint_get_products_with_attribute_filters() {
+    let client = reqwest::Client::new();
+    let name = format!("Attribute HTTP Test {}", Uuid::new_v4().simple());
+    
+    let new_product = json!({
+        "product": {
+            "name": name,
+            "cost": { "amount": "69.99", "currency": "USD" },
+            "active": true
+        },
+        "variants": [
+            { "variant": { "name": "Size" }, "values": ["42"] },
+            { "variant": { "name": "Color" }, "values": ["Black"] }
+        ]
+    });
+
+    let response = client
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .json(&new_product)
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let created: Value = response.unwrap().json().await.unwrap();
+    let product_id = created["id"].as_str().unwrap();
+    
+    // Test GET /products?attr.size=...&attr.color=...
+    let response = client
+        .get(format!("{}/products?name={}&attr.size=41,42&attr.color=black", TEST_SERVER_URL, name))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["items"].as_array().unwrap().len(), 1);
+    assert_eq!(body["items"][0]["id"], product_id);
+    
+    let response = client
+        .get(format!("{}/products?name={}&attr.size=42&attr.color=red", TEST_SERVER_URL, name))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert!(body["items"].as_array().unwrap().is_empty());
+    
+    // Test an attribute filter without a value
+    let response = client
+        .get(format!("{}/products?attr.size=", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    
+    // Clean up
+    let _ = client
+        .delete(format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .send()
+        .await;
+}
+
+#[tokio::test]
+async fn test_endpoint_get_products_with_facets() {
+    let client = reqwest::Client::new();
+    let name = format!("Facet HTTP Test {}", Uuid::new_v4().simple());
+    
+    let new_product = json!({
+        "product": {
+            "name": name,
+            "cost": { "amount": "74.99", "currency": "USD" },
+            "active": true
+        },
+        "variants": [
+            { "variant": { "name": "Size" }, "values": ["42", "43"] }
+        ]
+    });
+
+    let response = client
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .json(&new_product)
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let created: Value = response.unwrap().json().await.unwrap();
+    let product_id = created["id"].as_str().unwrap();
+    
+    // Test GET /products?include_facets=true
+    let response = client
+        .get(format!("{}/products?name={}&include_facets=true", TEST_SERVER_URL, name))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let body: Value = response.json().await.unwrap();
+    let facets = &body["facets"];
+    assert_eq!(facets["attributes"][0]["name"], "Size");
+    assert_eq!(facets["attributes"][0]["values"].as_array().unwrap().len(), 2);
+    assert_eq!(facets["active"]["active"], 1);
+    assert_eq!(facets["active"]["inactive"], 0);
+    assert_eq!(facets["cost"][0]["count"], 1);
+    assert_eq!(facets["cost"][0]["min"]["currency"], "USD");
+    
+    // Facets are left out unless requested
+    let response = client
+        .get(format!("{}/products?name={}", TEST_SERVER_URL, name))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert!(body.get("facets").is_none());
+    
+    // Clean up
+    let _ = client
+        .delete(format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .send()
+        .await;
+}
+
+#[tokio::test]
+async fn test_endpoint_get_products_invalid_pagination() {
+    let client = reqwest::Client::new();
+    
+    // Test with a limit outside the allowed range
+    let response = client
+        .get(format!("{}/products?limit=0", TEST_SERVER_URL))
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let response = response.unwrap();
+    assert_eq!(response.status(), 400);
+    
+    let body: Value = response.json().await.unwrap();
+    assert!(body["detail"].is_string());
+    assert_eq!(body["code"], "validation_failed");
+
+    // Test with a cursor that was not issued by the server
+    let response = client
+        .get(format!("{}/products?cursor=not-a-cursor", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_endpoint_get_products_with_sort() {
+    let client = reqwest::Client::new();
+    
+    // Test with multiple sort fields
+    let response = client
+        .get(format!("{}/products?sort=cost:desc,name:asc", TEST_SERVER_URL))
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let response = response.unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let body: Value = response.json().await.unwrap();
+    assert!(body["items"].is_array());
+
+    // Test with an unknown sort field
+    let response = client
+        .get(format!("{}/products?sort=colour", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    
+    let body: Value = response.json().await.unwrap();
+    assert!(body["detail"].as_str().unwrap().contains("colour"));
+}
+
+#[tokio::test]
+async fn test_endpoint_search_products() {
+    let client = reqwest::Client::new();
+    
+    // Test full-text search including variant values
+    let response = client
+        .get(format!("{}/products?q=red%2042&search_variants=true", TEST_SERVER_URL))
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let response = response.unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let body: Value = response.json().await.unwrap();
+    assert!(body["items"].is_array());
+
+    // Test with a query that has nothing to search for
+    let response = client
+        .get(format!("{}/products?q=%26%7C!", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_endpoint_suggest_products() {
+    let client = reqwest::Client::new();
+    
+    // Test GET /products/suggest
+    let response = client
+        .get(format!("{}/products/suggest?prefix=snea&limit=5", TEST_SERVER_URL))
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let response = response.unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let body: Value = response.json().await.unwrap();
+    assert!(body.is_array());
+    assert!(body.as_array().unwrap().len() <= 5);
+
+    // Test with an empty prefix
+    let response = client
+        .get(format!("{}/products/suggest?prefix=", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_endpoint_get_product_with_variants() {
+    let client = reqwest::Client::new();
+    
+    let new_product = json!({
+        "product": {
+            "name": "Include Variants HTTP Test",
+            "cost": { "amount": "49.99", "currency": "USD" },
+            "active": true
+        },
+        "variants": [
+            {
+                "variant": {
+                    "name": "Color"
+                },
+                "values": ["Red", "Blue"]
+            }
+        ]
+    });
+
+    let response = client
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .json(&new_product)
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let created: Value = response.unwrap().json().await.unwrap();
+    let product_id = created["id"].as_str().unwrap();
+    
+    // Test GET /products/{id}?include=variants
+    let response = client
+        .get(format!("{}/products/{}?include=variants", TEST_SERVER_URL, product_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["id"], product_id);
+    assert_eq!(body["variants"][0]["name"], "Color");
+    assert_eq!(body["variants"][0]["values"].as_array().unwrap().len(), 2);
+    
+    // Test GET /products?include=variants
+    let response = client
+        .get(format!("{}/products?include=variants&name=Include%20Variants%20HTTP", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let body: Value = response.json().await.unwrap();
+    assert!(body["items"][0]["variants"].is_array());
+    
+    // Test with an unsupported include
+    let response = client
+        .get(format!("{}/products/{}?include=reviews", TEST_SERVER_URL, product_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    
+    // Clean up
+    let _ = client
+        .delete(format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .send()
+        .await;
+}
+
+#[tokio::test]
+async fn test_endpoint_product_variant_crud() {
+    let client = reqwest::Client::new();
+    
+    let new_product = json!({
+        "product": {
+            "name": "Variant CRUD HTTP Test",
+            "cost": { "amount": "59.99", "currency": "USD" },
+            "active": true
+        },
+        "variants": []
+    });
+
+    let response = client
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .json(&new_product)
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let created: Value = response.unwrap().json().await.unwrap();
+    let product_id = created["id"].as_str().unwrap();
+    let variants_url = format!("{}/products/{}/variants", TEST_SERVER_URL, product_id);
+    // Variant definitions are shared, so use names no other run has taken
+    let width = format!("Width {}", Uuid::new_v4().simple());
+    let fit = format!("Fit {}", Uuid::new_v4().simple());
+    
+    // Test POST /products/{id}/variants
+    let response = client
+        .post(&variants_url)
+        .json(&json!({
+            "variant": { "name": width, "allowed_values": ["Narrow", "Wide", "Extra Wide"] },
+            "values": ["Narrow"]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let variant: Value = response.json().await.unwrap();
+    assert_eq!(variant["name"], width.as_str());
+    let variant_id = variant["id"].as_str().unwrap();
+    
+    // Test a value outside the allowed values
+    let response = client
+        .post(format!("{}/{}/values", variants_url, variant_id))
+        .json(&json!({ "value": "Medium" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    
+    // Test POST and PUT on /products/{id}/variants/{variant_id}/values
+    let response = client
+        .post(format!("{}/{}/values", variants_url, variant_id))
+        .json(&json!({ "value": "Wide" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let value: Value = response.json().await.unwrap();
+    let value_id = value["id"].as_str().unwrap();
+    
+    let response = client
+        .put(format!("{}/{}/values/{}", variants_url, variant_id, value_id))
+        .json(&json!({ "value": "Extra Wide" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["value"], "Extra Wide");
+
+    // A value can't be moved to another product or variant
+    let response = client
+        .put(format!("{}/{}/values/{}", variants_url, variant_id, value_id))
+        .json(&json!({ "value": "Moved", "product_id": Uuid::new_v4() }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    
+    // Test GET /products/{id}/variants
+    let response = client.get(&variants_url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body[0]["values"].as_array().unwrap().len(), 2);
+    
+    // Test PUT /variants/{id}
+    let response = client
+        .put(format!("{}/variants/{}", TEST_SERVER_URL, variant_id))
+        .json(&json!({ "name": fit }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["name"], fit.as_str());
+    
+    // Test creating a definition that already exists
+    let response = client
+        .post(format!("{}/variants", TEST_SERVER_URL))
+        .json(&json!({ "name": fit }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 409);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "conflict");
+    
+    // Test DELETE on a value and on the variant
+    let response = client
+        .delete(format!("{}/{}/values/{}", variants_url, variant_id, value_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    
+    let response = client
+        .delete(format!("{}/{}", variants_url, variant_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    
+    // Test variants of a product that doesn't exist
+    let response = client
+        .get(format!("{}/products/{}/variants", TEST_SERVER_URL, Uuid::new_v4()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    
+    // Clean up
+    let _ = client
+        .delete(format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .send()
+        .await;
+    let _ = client
+        .delete(format!("{}/variants/{}", TEST_SERVER_URL, variant_id))
+        .send()
+        .await;
+}
+
+#[tokio::test]
+async fn test_endpoint_get_product_by_id_not_found() {
+    let client = reqwest::Client::new();
+    
+    // Test GET /products/{id} with non-existent ID
+    let non_existent_id = Uuid::new_v4();
+    let response = client
+        .get(format!("{}/products/{}", TEST_SERVER_URL, non_existent_id))
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let response = response.unwrap();
+    assert_eq!(response.status(), 404);
+    
+    let body: Value = response.json().await.unwrap();
+    assert!(body["detail"].is_string());
+    assert_eq!(body["code"], "not_found");
+}
+
+#[tokio::test]
+async fn test_endpoint_update_product_not_found() {
+    let client = reqwest::Client::new();
+    
+    let update_data = json!({
+        "name": "Updated Product",
+        "cost": { "amount": "49.99", "currency": "USD" },
+        "active": false
+    });
+
+    // Test PUT /products/{id} with non-existent ID
+    let non_existent_id = Uuid::new_v4();
+    let response = client
+        .put(format!("{}/products/{}", TEST_SERVER_URL, non_existent_id))
+        .json(&update_data)
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let response = response.unwrap();
+    assert_eq!(response.status(), 404);
+    
+    let body: Value = response.json().await.unwrap();
+    assert!(body["detail"].is_string());
+    assert_eq!(body["code"], "not_found");
+}
+
+#[tokio::test]
+async fn test_endpoint_delete_product_not_found() {
+    let client = reqwest::Client::new();
+    
+    // Test DELETE /products/{id} with non-existent ID
+    let non_existent_id = Uuid::new_v4();
+    let response = client
+        .delete(format!("{}/products/{}", TEST_SERVER_URL, non_existent_id))
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let response = response.unwrap();
+    assert_eq!(response.status(), 404);
+    
+    let body: Value = response.json().await.unwrap();
+    assert!(body["detail"].is_string());
+    assert_eq!(body["code"], "not_found");
+}
+
+#[tokio::test]
+async fn test_endpoint_full_crud_flow() {
+    let client = reqwest::Client::new();
+    
+    // 1. Create a product
+    let new_product = json!({
+        "product": {
+            "name": "CRUD Flow HTTP Test Product",
+            "cost": { "amount": "25.50", "currency": "USD" },
+            "active": true
+        },
+        "variants": []
+    });
+
+    let response = client
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .json(&new_product)
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let response = response.unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let created_product: Value = response.json().await.unwrap();
+    let product_id = created_product["id"].as_str().unwrap(); // UUID is a string
+    
+    // 2. Get the created product by ID
+    let response = client
+        .get(format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .send()
+        .await
+        .unwrap();
+    
+    assert_eq!(response.status(), 200);
+    
+    let retrieved_product: Value = response.json().await.unwrap();
+    assert_eq!(retrieved_product["name"], "CRUD Flow HTTP Test Product");
+    assert_eq!(retrieved_product["cost"]["amount"], "25.50");
+    assert_eq!(retrieved_product["active"], true);
+    assert_eq!(retrieved_product["id"], product_id);
+    
+    // 3. Update the product
+    let update_data = json!({
+        "name": "Updated CRUD Flow HTTP Test Product",
+        "cost": { "amount": "35.75", "currency": "USD" },
+        "active": false
+    });
+
+    let response = client
+        .put(format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .json(&update_data)
+        .send()
+        .await
+        .unwrap();
+    
+    assert_eq!(response.status(), 200);
+    
+    let updated_product: Value = response.json().await.unwrap();
+    assert_eq!(updated_product["name"], "Updated CRUD Flow HTTP Test Product");
+    assert_eq!(updated_product["cost"]["amount"], "35.75");
+    assert_eq!(updated_product["active"], false);
+    assert_eq!(updated_product["id"], product_id);
+    
+    // 4. Verify the update by getting the product again
+    let response = client
+        .get(format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .send()
+        .await
+        .unwrap();
+    
+    assert_eq!(response.status(), 200);
+    
+    let verified_product: Value = response.json().await.unwrap();
+    assert_eq!(verified_product["name"], "Updated CRUD Flow HTTP Test Product");
+    assert_eq!(verified_product["cost"]["amount"], "35.75");
+    assert_eq!(verified_product["active"], false);
+    
+    // 5. Delete the product
+    let response = client
+        .delete(format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .send()
+        .await
+        .unwrap();
+    
+    assert_eq!(response.status(), 204);
+    
+    // 6. Verify product is deleted
+    let response = client
+        .get(format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .send()
+        .await
+        .unwrap();
+    
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_endpoint_if_match_concurrency() {
+    let client = reqwest::Client::new();
+    
+    let new_product = json!({
+        "product": {
+            "name": "If-Match HTTP Test",
+            "cost": { "amount": "89.99", "currency": "USD" },
+            "active": true
+        },
+        "variants": []
+    });
+
+    let response = client
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .json(&new_product)
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let response = response.unwrap();
+    assert_eq!(response.headers()["etag"], "\"1\"");
+    let created: Value = response.json().await.unwrap();
+    let product_id = created["id"].as_str().unwrap();
+    let product_url = format!("{}/products/{}", TEST_SERVER_URL, product_id);
+    
+    // Test GET /products/{id} exposes the current version
+    let response = client.get(&product_url).send().await.unwrap();
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(etag, "\"1\"");
+    
+    // Test PATCH with a matching If-Match
+    let response = client
+        .patch(&product_url)
+        .header("If-Match", &etag)
+        .header("content-type", "application/merge-patch+json")
+        .body(json!({ "name": "If-Match HTTP Test Edited" }).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["etag"], "\"2-variants\"");
+    
+    // Test PATCH and DELETE with the stale ETag
+    let response = client
+        .patch(&product_url)
+        .header("If-Match", &etag)
+        .header("content-type", "application/merge-patch+json")
+        .body(json!({ "name": "Lost Update" }).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 412);
+    
+    let response = client
+        .delete(&product_url)
+        .header("If-Match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 412);
+    
+    // Test the ETag of the representation with variants is accepted as well
+    let response = client
+        .get(format!("{}?include=variants", product_url))
+        .send()
+        .await
+        .unwrap();
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(etag, "\"2-variants\"");
+    
+    let response = client
+        .delete(&product_url)
+        .header("If-Match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+}
+
+#[tokio::test]
+async fn test_endpoint_conditional_get() {
+    let client = reqwest::Client::new();
+    
+    let new_product = json!({
+        "product": {
+            "name": "Conditional GET HTTP Test",
+            "cost": { "amount": "64.99", "currency": "USD" },
+            "active": true
+        },
+        "variants": []
+    });
+
+    let response = client
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .json(&new_product)
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let created: Value = response.unwrap().json().await.unwrap();
+    let product_id = created["id"].as_str().unwrap();
+    let product_url = format!("{}/products/{}", TEST_SERVER_URL, product_id);
+    
+    // Test GET /products/{id} sends validators and caching directives
+    let response = client.get(&product_url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers().contains_key("cache-control"));
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    let last_modified = response.headers()["last-modified"].to_str().unwrap().to_string();
+    
+    // Test a matching If-None-Match or If-Modified-Since gets a bodiless 304
+    let response = client
+        .get(&product_url)
+        .header("If-None-Match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 304);
+    assert_eq!(response.headers()["etag"].to_str().unwrap(), etag);
+    assert!(response.text().await.unwrap().is_empty());
+    
+    let response = client
+        .get(&product_url)
+        .header("If-Modified-Since", &last_modified)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 304);
+    
+    // Test adding a variant changes the product's ETag
+    let response = client
+        .post(format!("{}/variants", product_url))
+        .json(&json!({
+            "variant": { "name": format!("Conditional Color {}", Uuid::new_v4().simple()) },
+            "values": ["Black"]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    
+    let response = client
+        .get(&product_url)
+        .header("If-None-Match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_ne!(response.headers()["etag"].to_str().unwrap(), etag);
+    
+    // Test the product list answers If-None-Match with its own ETag, and If-Modified-Since
+    // with when the catalog last changed
+    let list_url = format!("{}/products?name=Conditional%20GET%20HTTP%20Test", TEST_SERVER_URL);
+    let response = client.get(&list_url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let list_etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(list_etag.len(), 66);
+    let list_modified = response.headers()["last-modified"].to_str().unwrap().to_string();
+    
+    let response = client
+        .get(&list_url)
+        .header("If-Modified-Since", &list_modified)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 304);
+    
+    let response = client
+        .get(&list_url)
+        .header("If-None-Match", &list_etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 304);
+    
+    // Test a product dropping out of the list invalidates both validators
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let response = client.delete(&product_url).send().await.unwrap();
+    assert_eq!(response.status(), 204);
+    
+    let response = client
+        .get(&list_url)
+        .header("If-None-Match", &list_etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_ne!(response.headers()["etag"].to_str().unwrap(), list_etag);
+    
+    let response = client
+        .get(&list_url)
+        .header("If-Modified-Since", &list_modified)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_ne!(response.headers()["last-modified"].to_str().unwrap(), list_modified);
 }
 
 #[tokio::test]
-async fn test_endpoint_get_product_by_id_not_found() {
+async fn test_endpoint_get_products_changed_since() {
     let client = reqwest::Client::new();
     
-    // Test GET /products/{id} with non-existent ID
-    let non_existent_id = Uuid::new_v4();
+    // Test GET /products with an incremental sync window, newest changes first
     let response = client
-        .get(&format!("{}/products/{}", TEST_SERVER_URL, non_existent_id))
+        .get(format!("{}/products?updated_after=2020-01-01T00:00:00Z&sort=updated_at:desc", TEST_SERVER_URL))
         .send()
         .await;
 
@@ -191,27 +1454,38 @@ async fn test_endpoint_get_product_by_id_not_found() {
     }
 
     let response = response.unwrap();
-    assert_eq!(response.status(), 404);
-    
+    assert_eq!(response.status(), 200);
     let body: Value = response.json().await.unwrap();
-    assert!(body["error"].is_string());
+    for product in body["items"].as_array().unwrap() {
+        assert!(product["created_at"].is_string());
+        assert!(product["updated_at"].is_string());
+    }
+    
+    // Test an unparseable timestamp is rejected
+    let response = client
+        .get(format!("{}/products?created_after=yesterday", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
 }
 
 #[tokio::test]
-async fn test_endpoint_update_product_not_found() {
+async fn test_endpoint_soft_delete_and_restore() {
     let client = reqwest::Client::new();
     
-    let update_data = json!({
-        "name": "Updated Product",
-        "cost": 49.99,
-        "active": false
+    let new_product = json!({
+        "product": {
+            "name": format!("Soft Delete HTTP Test {}", Uuid::new_v4()),
+            "cost": { "amount": "54.99", "currency": "USD" },
+            "active": true
+        },
+        "variants": []
     });
 
-    // Test PUT /products/{id} with non-existent ID
-    let non_existent_id = Uuid::new_v4();
     let response = client
-        .put(&format!("{}/products/{}", TEST_SERVER_URL, non_existent_id))
-        .json(&update_data)
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .json(&new_product)
         .send()
         .await;
 
@@ -220,21 +1494,71 @@ async fn test_endpoint_update_product_not_found() {
         return;
     }
 
-    let response = response.unwrap();
+    let created: Value = response.unwrap().json().await.unwrap();
+    let product_id = created["id"].as_str().unwrap();
+    let product_url = format!("{}/products/{}", TEST_SERVER_URL, product_id);
+    
+    // Test DELETE /products/{id} hides the product
+    let response = client.delete(&product_url).send().await.unwrap();
+    assert_eq!(response.status(), 204);
+    
+    let response = client.get(&product_url).send().await.unwrap();
     assert_eq!(response.status(), 404);
     
+    // Test the purge keeps products deleted within the retention period
+    let response = client
+        .post(format!("{}/admin/products/purge", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
     let body: Value = response.json().await.unwrap();
-    assert!(body["error"].is_string());
+    assert!(body["purged"].is_u64());
+    
+    // Test POST /products/{id}/restore
+    let response = client
+        .post(format!("{}/restore", product_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let restored: Value = response.json().await.unwrap();
+    assert_eq!(restored["id"], product_id);
+    assert!(restored.get("deleted_at").is_none());
+    
+    let response = client.get(&product_url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    
+    // Test restoring an unknown product
+    let response = client
+        .post(format!("{}/products/{}/restore", TEST_SERVER_URL, Uuid::new_v4()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    
+    // Clean up
+    let response = client.delete(&product_url).send().await.unwrap();
+    assert_eq!(response.status(), 204);
 }
 
 #[tokio::test]
-async fn test_endpoint_delete_product_not_found() {
+async fn test_endpoint_product_history_and_revert() {
     let client = reqwest::Client::new();
     
-    // Test DELETE /products/{id} with non-existent ID
-    let non_existent_id = Uuid::new_v4();
+    let new_product = json!({
+        "product": {
+            "name": "History HTTP Test",
+            "cost": { "amount": "120.00", "currency": "USD" },
+            "active": true
+        },
+        "variants": []
+    });
+
     let response = client
-        .delete(&format!("{}/products/{}", TEST_SERVER_URL, non_existent_id))
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .header("X-Actor", "catalog-bot")
+        .json(&new_product)
         .send()
         .await;
 
@@ -243,29 +1567,80 @@ async fn test_endpoint_delete_product_not_found() {
         return;
     }
 
-    let response = response.unwrap();
+    let created: Value = response.unwrap().json().await.unwrap();
+    let product_id = created["id"].as_str().unwrap();
+    let product_url = format!("{}/products/{}", TEST_SERVER_URL, product_id);
+    
+    let response = client
+        .patch(&product_url)
+        .header("X-Actor", "jane")
+        .header("content-type", "application/merge-patch+json")
+        .body(json!({ "cost": { "amount": "99.00", "currency": "USD" } }).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    
+    // Test GET /products/{id}/history
+    let response = client.get(format!("{}/history", product_url)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let history: Value = response.json().await.unwrap();
+    let history = history.as_array().unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0]["action"], "update");
+    assert_eq!(history[0]["changed_by"], "jane");
+    assert_eq!(history[0]["changes"][0]["field"], "cost");
+    assert_eq!(history[0]["changes"][0]["from"]["amount"], "120.00");
+    assert_eq!(history[1]["changed_by"], "catalog-bot");
+    
+    // Test POST /products/{id}/revert/{revision}
+    let first_revision = history[1]["revision"].as_i64().unwrap();
+    let response = client
+        .post(format!("{}/revert/{}", product_url, first_revision))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let reverted: Value = response.json().await.unwrap();
+    assert_eq!(reverted["cost"]["amount"], "120.00");
+    
+    let response = client
+        .post(format!("{}/revert/999", product_url))
+        .send()
+        .await
+        .unwrap();
     assert_eq!(response.status(), 404);
     
-    let body: Value = response.json().await.unwrap();
-    assert!(body["error"].is_string());
+    // Test history of an unknown product
+    let response = client
+        .get(format!("{}/products/{}/history", TEST_SERVER_URL, Uuid::new_v4()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    
+    // Clean up
+    let response = client.delete(&product_url).send().await.unwrap();
+    assert_eq!(response.status(), 204);
 }
 
 #[tokio::test]
-async fn test_endpoint_full_crud_flow() {
+async fn test_endpoint_idempotent_create() {
     let client = reqwest::Client::new();
+    let key = Uuid::new_v4().to_string();
     
-    // 1. Create a product
     let new_product = json!({
         "product": {
-            "name": "CRUD Flow HTTP Test Product",
-            "cost": 25.50,
+            "name": "Idempotency HTTP Test",
+            "cost": { "amount": "74.99", "currency": "USD" },
             "active": true
         },
         "variants": []
     });
 
     let response = client
-        .post(&format!("{}/products", TEST_SERVER_URL))
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .header("Idempotency-Key", &key)
         .json(&new_product)
         .send()
         .await;
@@ -277,78 +1652,71 @@ async fn test_endpoint_full_crud_flow() {
 
     let response = response.unwrap();
     assert_eq!(response.status(), 200);
+    assert!(!response.headers().contains_key("idempotent-replayed"));
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    let created: Value = response.json().await.unwrap();
     
-    let created_product: Value = response.json().await.unwrap();
-    let product_id = created_product["id"].as_str().unwrap(); // UUID is a string
-    
-    // 2. Get the created product by ID
+    // Test a retry replays the original response instead of creating a duplicate
     let response = client
-        .get(&format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .header("Idempotency-Key", &key)
+        .json(&new_product)
         .send()
         .await
         .unwrap();
-    
     assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["idempotent-replayed"], "true");
+    assert_eq!(response.headers()["etag"].to_str().unwrap(), etag);
+    let replayed: Value = response.json().await.unwrap();
+    assert_eq!(replayed["id"], created["id"]);
     
-    let retrieved_product: Value = response.json().await.unwrap();
-    assert_eq!(retrieved_product["name"], "CRUD Flow HTTP Test Product");
-    assert_eq!(retrieved_product["cost"], 25.5);
-    assert_eq!(retrieved_product["active"], true);
-    assert_eq!(retrieved_product["id"], product_id);
-    
-    // 3. Update the product
-    let update_data = json!({
-        "name": "Updated CRUD Flow HTTP Test Product",
-        "cost": 35.75,
-        "active": false
-    });
-
+    // Test reusing the key for a different body
+    let mut changed = new_product.clone();
+    changed["product"]["name"] = json!("Idempotency HTTP Test Changed");
     let response = client
-        .put(&format!("{}/products/{}", TEST_SERVER_URL, product_id))
-        .json(&update_data)
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .header("Idempotency-Key", &key)
+        .json(&changed)
         .send()
         .await
         .unwrap();
+    assert_eq!(response.status(), 422);
     
-    assert_eq!(response.status(), 200);
-    
-    let updated_product: Value = response.json().await.unwrap();
-    assert_eq!(updated_product["name"], "Updated CRUD Flow HTTP Test Product");
-    assert_eq!(updated_product["cost"], 35.75);
-    assert_eq!(updated_product["active"], false);
-    assert_eq!(updated_product["id"], product_id);
-    
-    // 4. Verify the update by getting the product again
+    // Test an oversized key is rejected
     let response = client
-        .get(&format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .header("Idempotency-Key", "k".repeat(256))
+        .json(&new_product)
         .send()
         .await
         .unwrap();
+    assert_eq!(response.status(), 400);
     
-    assert_eq!(response.status(), 200);
-    
-    let verified_product: Value = response.json().await.unwrap();
-    assert_eq!(verified_product["name"], "Updated CRUD Flow HTTP Test Product");
-    assert_eq!(verified_product["cost"], 35.75);
-    assert_eq!(verified_product["active"], false);
-    
-    // 5. Delete the product
+    // Test a keyed import is held to the import's own body limit, not the default one
+    let mut csv = String::from("name,cost\n");
+    for row in 0..3000 {
+        csv.push_str(&format!("Idempotent Import Test {} {},1.00\n", row, "x".repeat(80)));
+    }
+    assert!(csv.len() > 256 * 1024);
     let response = client
-        .delete(&format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .post(format!("{}/products/import?dry_run=true", TEST_SERVER_URL))
+        .header("Idempotency-Key", Uuid::new_v4().to_string())
+        .header("Content-Type", "text/csv")
+        .body(csv)
         .send()
         .await
         .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["created"], 3000);
     
-    assert_eq!(response.status(), 204);
-    
-    // 6. Verify product is deleted
+    // Clean up
     let response = client
-        .get(&format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .delete(format!("{}/products/{}", TEST_SERVER_URL, created["id"].as_str().unwrap()))
         .send()
         .await
         .unwrap();
-    
-    assert_eq!(response.status(), 404);
+    assert_eq!(response.status(), 204);
 }
 
 #[tokio::test]
@@ -359,14 +1727,14 @@ async fn test_endpoint_update_partial_fields() {
     let new_product = json!({
         "product": {
             "name": "Partial Update HTTP Test",
-            "cost": 20.0,
+            "cost": { "amount": "20.00", "currency": "USD" },
             "active": true
         },
         "variants": []
     });
 
     let response = client
-        .post(&format!("{}/products", TEST_SERVER_URL))
+        .post(format!("{}/products", TEST_SERVER_URL))
         .json(&new_product)
         .send()
         .await;
@@ -386,8 +1754,9 @@ async fn test_endpoint_update_partial_fields() {
     });
 
     let response = client
-        .put(&format!("{}/products/{}", TEST_SERVER_URL, product_id))
-        .json(&partial_update)
+        .patch(format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .header("content-type", "application/merge-patch+json")
+        .body(partial_update.to_string())
         .send()
         .await
         .unwrap();
@@ -396,14 +1765,125 @@ async fn test_endpoint_update_partial_fields() {
     
     let updated_product: Value = response.json().await.unwrap();
     assert_eq!(updated_product["name"], "Only Name Updated HTTP");
-    assert_eq!(updated_product["cost"], 20.0); // Should remain unchanged
+    assert_eq!(updated_product["cost"]["amount"], "20.00"); // Should remain unchanged
     assert_eq!(updated_product["active"], true); // Should remain unchanged
     
     // Clean up
     let _ = client
-        .delete(&format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .delete(format!("{}/products/{}", TEST_SERVER_URL, product_id))
+        .send()
+        .await;
+}
+
+#[tokio::test]
+async fn test_endpoint_patch_product_variants() {
+    let client = reqwest::Client::new();
+
+    let new_product = json!({
+        "product": {
+            "name": "Patch Variants HTTP Test",
+            "cost": { "amount": "30.00", "currency": "USD" },
+            "active": true
+        },
+        "variants": []
+    });
+
+    let response = client
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .json(&new_product)
         .send()
         .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let created_product: Value = response.unwrap().json().await.unwrap();
+    let product_url = format!("{}/products/{}", TEST_SERVER_URL, created_product["id"].as_str().unwrap());
+    let values = |product: &Value| -> Vec<String> {
+        product["variants"][0]["values"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value["value"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    // A merge patch adds a variant by name
+    let response = client
+        .patch(&product_url)
+        .header("content-type", "application/merge-patch+json")
+        .body(json!({ "variants": { "Size": ["41", "42"] } }).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let product: Value = response.json().await.unwrap();
+    assert_eq!(product["variants"][0]["name"], "Size");
+    assert_eq!(values(&product), vec!["41", "42"]);
+
+    // A JSON patch appends to its values
+    let response = client
+        .patch(&product_url)
+        .header("content-type", "application/json-patch+json")
+        .body(json!([{ "op": "add", "path": "/variants/Size/-", "value": "43" }]).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let product: Value = response.json().await.unwrap();
+    assert_eq!(values(&product), vec!["41", "42", "43"]);
+
+    // A failed test operation leaves the product alone
+    let response = client
+        .patch(&product_url)
+        .header("content-type", "application/json-patch+json")
+        .body(json!([
+            { "op": "test", "path": "/name", "value": "Someone Else's Name" },
+            { "op": "replace", "path": "/name", "value": "Overwritten" }
+        ]).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 409);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "conflict");
+
+    // Null is only allowed on optional members
+    let response = client
+        .patch(&product_url)
+        .header("content-type", "application/merge-patch+json")
+        .body(json!({ "name": null }).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+
+    // Plain JSON is not a patch format
+    let response = client
+        .patch(&product_url)
+        .json(&json!({ "name": "Plain JSON" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 415);
+    assert!(response.headers()["accept-patch"].to_str().unwrap().contains("application/merge-patch+json"));
+
+    // Null removes the variant
+    let response = client
+        .patch(&product_url)
+        .header("content-type", "application/merge-patch+json")
+        .body(json!({ "variants": { "Size": null } }).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let product: Value = response.json().await.unwrap();
+    assert_eq!(product["name"], "Patch Variants HTTP Test");
+    assert_eq!(product["variants"], json!([]));
+
+    let _ = client.delete(&product_url).send().await;
 }
 
 #[tokio::test]
@@ -412,7 +1892,7 @@ async fn test_endpoint_invalid_product_id() {
     
     // Test with non-numeric ID
     let response = client
-        .get(&format!("{}/products/invalid", TEST_SERVER_URL))
+        .get(format!("{}/products/invalid", TEST_SERVER_URL))
         .send()
         .await;
 
@@ -422,7 +1902,13 @@ async fn test_endpoint_invalid_product_id() {
     }
 
     let response = response.unwrap();
-    assert!(response.status().is_client_error());
+    assert_eq!(response.status(), 400);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "validation_failed");
+    assert_eq!(body["instance"], "/products/invalid");
+    assert!(body["request_id"].is_string());
 }
 
 #[tokio::test]
@@ -431,7 +1917,7 @@ async fn test_endpoint_create_product_invalid_json() {
     
     // Test with invalid JSON
     let response = client
-        .post(&format!("{}/products", TEST_SERVER_URL))
+        .post(format!("{}/products", TEST_SERVER_URL))
         .header("content-type", "application/json")
         .body("{invalid json")
         .send()
@@ -444,15 +1930,60 @@ async fn test_endpoint_create_product_invalid_json() {
 
     let response = response.unwrap();
     assert_eq!(response.status(), 400);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], 400);
+    assert_eq!(body["code"], "validation_failed");
+    assert!(body["detail"].as_str().unwrap().starts_with("Invalid JSON body"));
+
+    // A body that isn't JSON at all
+    let response = client
+        .post(format!("{}/products", TEST_SERVER_URL))
+        .header("content-type", "text/plain")
+        .body("name=Shoe")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 415);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "unsupported_media_type");
+
+    // A query string that doesn't parse
+    let response = client
+        .get(format!("{}/products?limit=many", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
 }
 
 #[tokio::test]
 async fn test_endpoint_method_not_allowed() {
     let client = reqwest::Client::new();
     
-    // PATCH is not supported on /products
+    // PUT is not supported on /products
+    let response = client
+        .put(format!("{}/products", TEST_SERVER_URL))
+        .send()
+        .await;
+
+    if response.is_err() {
+        println!("Server not running, skipping endpoint tests");
+        return;
+    }
+
+    let response = response.unwrap();
+    assert_eq!(response.status(), 405);
+}
+
+#[tokio::test]
+async fn test_endpoint_wrong_method_is_problem() {
+    let client = reqwest::Client::new();
+
     let response = client
-        .patch(&format!("{}/products", TEST_SERVER_URL))
+        .put(format!("{}/products", TEST_SERVER_URL))
         .send()
         .await;
 
@@ -463,6 +1994,34 @@ async fn test_endpoint_method_not_allowed() {
 
     let response = response.unwrap();
     assert_eq!(response.status(), 405);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
+    let allow = response.headers()["allow"].to_str().unwrap().to_string();
+    assert!(allow.contains("GET") && allow.contains("POST"));
+    assert!(!allow.contains("PUT"));
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "method_not_allowed");
+
+    // Per-product routes and the variant routes behave the same way
+    let response = client
+        .post(format!("{}/products/{}", TEST_SERVER_URL, Uuid::new_v4()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 405);
+    let response = client
+        .put(format!("{}/variants", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 405);
+
+    // Unknown paths are still a 404
+    let response = client
+        .put(format!("{}/nonexistent", TEST_SERVER_URL))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
 }
 
 #[tokio::test]
@@ -470,7 +2029,7 @@ async fn test_endpoint_not_found_route() {
     let client = reqwest::Client::new();
     
     let response = client
-        .get(&format!("{}/nonexistent", TEST_SERVER_URL))
+        .get(format!("{}/nonexistent", TEST_SERVER_URL))
         .send()
         .await;
 
@@ -481,4 +2040,14 @@ async fn test_endpoint_not_found_route() {
 
     let response = response.unwrap();
     assert_eq!(response.status(), 404);
+    assert_eq!(response.headers()["content-type"], "application/problem+json");
+    let request_id = response.headers()["x-request-id"].to_str().unwrap().to_string();
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["type"], "urn:problem-type:not_found");
+    assert_eq!(body["title"], "Not Found");
+    assert_eq!(body["status"], 404);
+    assert_eq!(body["instance"], "/nonexistent");
+    assert_eq!(body["request_id"], request_id.as_str());
+    assert!(body["detail"].is_string());
 }
@@ -0,0 +1,95 @@
+use actix_web::body::to_bytes;
+use actix_web::http::StatusCode;
+use actix_web::ResponseError;
+use backend::traits::responses::{AppError, Problem, PROBLEM_CONTENT_TYPE};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde_json::Value;
+
+fn database_error(kind: DatabaseErrorKind, message: &str) -> DieselError {
+    DieselError::DatabaseError(kind, Box::new(message.to_string()))
+}
+
+#[test]
+fn test_app_error_codes_and_statuses() {
+    let cases = [
+        (AppError::Validation("bad".to_string()), 400, "validation_failed"),
+        (AppError::NotFound("missing".to_string()), 404, "not_found"),
+        (AppError::Conflict("taken".to_string()), 409, "conflict"),
+        (AppError::PreconditionFailed, 412, "precondition_failed"),
+        (AppError::Unprocessable("reused".to_string()), 422, "unprocessable"),
+        (AppError::Unavailable(anyhow::anyhow!("pool timed out")), 503, "service_unavailable"),
+        (AppError::Internal(anyhow::anyhow!("boom")), 500, "internal_error")
+    ];
+    for (err, status, code) in cases {
+        assert_eq!(err.status_code().as_u16(), status);
+        assert_eq!(err.code(), code);
+    }
+}
+
+#[test]
+fn test_database_errors_are_classified() {
+    let unique = AppError::from(database_error(DatabaseErrorKind::UniqueViolation, "duplicate key on products_pkey"));
+    assert_eq!(unique.code(), "conflict");
+
+    let foreign_key = AppError::from(database_error(DatabaseErrorKind::ForeignKeyViolation, "variant_id fkey"));
+    assert_eq!(foreign_key.code(), "conflict");
+
+    let check = AppError::from(database_error(DatabaseErrorKind::CheckViolation, "products_cost_check"));
+    assert_eq!(check.code(), "validation_failed");
+
+    let closed = AppError::from(database_error(DatabaseErrorKind::ClosedConnection, "server closed the connection"));
+    assert_eq!(closed.code(), "service_unavailable");
+
+    assert_eq!(AppError::from(DieselError::NotFound).code(), "not_found");
+    assert_eq!(AppError::from(DieselError::RollbackTransaction).code(), "internal_error");
+}
+
+#[test]
+fn test_anyhow_errors_keep_their_kind() {
+    let wrapped = anyhow::Error::from(AppError::Conflict("A variant named 'Size' already exists".to_string()));
+    assert_eq!(AppError::from(wrapped).code(), "conflict");
+
+    let diesel = anyhow::Error::from(database_error(DatabaseErrorKind::UniqueViolation, "duplicate key"))
+        .context("Inserting product");
+    assert_eq!(AppError::from(diesel).code(), "conflict");
+
+    assert_eq!(AppError::from(anyhow::anyhow!("unexpected")).code(), "internal_error");
+}
+
+#[actix_rt::test]
+async fn test_error_bodies_hide_internal_details() {
+    let err = AppError::from(database_error(DatabaseErrorKind::UniqueViolation, "duplicate key on products_pkey"));
+    let body: Value = serde_json::from_slice(&to_bytes(err.error_response().into_body()).await.unwrap()).unwrap();
+    assert_eq!(body["code"], "conflict");
+    assert!(!body["detail"].as_str().unwrap().contains("products_pkey"));
+
+    let err = AppError::Internal(anyhow::anyhow!("connection to 10.0.0.5 refused"));
+    let body: Value = serde_json::from_slice(&to_bytes(err.error_response().into_body()).await.unwrap()).unwrap();
+    assert_eq!(body, serde_json::json!({
+        "type": "urn:problem-type:internal_error",
+        "title": "Internal Server Error",
+        "status": 500,
+        "detail": "Internal server error",
+        "code": "internal_error"
+    }));
+}
+
+#[test]
+fn test_problem_responses() {
+    let response = AppError::NotFound("Product not found".to_string()).error_response();
+    assert_eq!(response.headers().get("content-type").unwrap(), PROBLEM_CONTENT_TYPE);
+
+    // The problem travels with the response so the middleware can complete it
+    let problem = response.extensions().get::<Problem>().cloned().unwrap();
+    assert_eq!(problem.status, 404);
+    assert_eq!(problem.title, "Not Found");
+    assert_eq!(problem.problem_type, "urn:problem-type:not_found");
+
+    let completed = problem.with_request("/products/1".to_string(), Some("abc".to_string()));
+    let value = serde_json::to_value(&completed).unwrap();
+    assert_eq!(value["instance"], "/products/1");
+    assert_eq!(value["request_id"], "abc");
+
+    assert_eq!(Problem::from_status(StatusCode::METHOD_NOT_ALLOWED, "").code, "method_not_allowed");
+    assert_eq!(Problem::from_status(StatusCode::BAD_GATEWAY, "").code, "internal_error");
+}
@@ -0,0 +1,112 @@
+use backend::models::{parse_attribute_filters, BulkUpdate, CostAdjustment, ProductFilters};
+use bigdecimal::BigDecimal;
+use serde_json::json;
+use std::str::FromStr;
+
+#[test]
+fn test_attribute_filters_group_values_by_variant() {
+    let attributes = parse_attribute_filters("attr.Size=42&attr.color=Black,red&attr.size=43&name=shoe").unwrap();
+
+    assert_eq!(attributes.len(), 2);
+    assert_eq!(attributes["size"], vec!["42", "43"]);
+    assert_eq!(attributes["color"], vec!["black", "red"]);
+}
+
+#[test]
+fn test_attribute_filters_decode_values() {
+    let attributes = parse_attribute_filters("attr.width=extra%20wide&attr.width=Extra+Wide").unwrap();
+    assert_eq!(attributes["width"], vec!["extra wide"]);
+}
+
+#[test]
+fn test_attribute_filters_reject_incomplete_pairs() {
+    assert!(parse_attribute_filters("attr.=42").is_err());
+    assert!(parse_attribute_filters("attr.size=").is_err());
+    assert!(parse_attribute_filters("attr.size=,").is_err());
+    assert!(parse_attribute_filters("limit=10").unwrap().is_empty());
+}
+
+#[test]
+fn test_bulk_selector_needs_a_filter_and_no_paging() {
+    assert!(ProductFilters::default().validate_selector().is_err());
+
+    let selector = ProductFilters { is_active: Some(true), ..Default::default() };
+    assert!(selector.validate_selector().is_ok());
+
+    let paged = ProductFilters { is_active: Some(true), limit: Some(10), ..Default::default() };
+    assert!(paged.validate_selector().unwrap_err().contains("limit"));
+
+    let with_deleted = ProductFilters { is_active: Some(true), include_deleted: Some(true), ..Default::default() };
+    assert!(with_deleted.validate_selector().is_err());
+
+    // Search modifiers without anything to search for would select every product
+    let modifiers_only = ProductFilters { fuzzy: Some(true), search_variants: Some(true), ..Default::default() };
+    assert!(modifiers_only.validate_selector().unwrap_err().contains("at least one filter"));
+
+    let blank_search = ProductFilters { q: Some(" ! ".to_string()), ..Default::default() };
+    assert!(blank_search.validate_selector().is_err());
+
+    let search = ProductFilters { q: Some("runner".to_string()), search_variants: Some(true), ..Default::default() };
+    assert!(search.validate_selector().is_ok());
+}
+
+#[test]
+fn test_name_filter_matches_wildcards_literally() {
+    let everything = ProductFilters { name: Some("%".to_string()), ..Default::default() };
+    assert_eq!(everything.name_pattern().as_deref(), Some("%\\%%"));
+
+    let single = ProductFilters { name: Some("a_b\\c".to_string()), ..Default::default() };
+    assert_eq!(single.name_pattern().as_deref(), Some("%a\\_b\\\\c%"));
+
+    assert_eq!(ProductFilters::default().name_pattern(), None);
+}
+
+#[test]
+fn test_bulk_update_parses_fields_and_cost_adjustment() {
+    let update: BulkUpdate = serde_json::from_value(json!({
+        "active": false,
+        "cost_adjustment": { "op": "percent", "by": "8" }
+    })).unwrap();
+
+    assert_eq!(update.updates.active, Some(false));
+    assert!(update.updates.cost.is_none());
+    assert_eq!(update.cost_adjustment, Some(CostAdjustment::Percent(BigDecimal::from(8))));
+    assert_eq!(
+        update.cost_adjustment.unwrap().factor_and_offset(),
+        (BigDecimal::from_str("1.08").unwrap(), BigDecimal::from(0))
+    );
+}
+
+#[test]
+fn test_bulk_update_rejects_unsafe_changes() {
+    let filters = ProductFilters { is_active: Some(true), ..Default::default() };
+    let update = |body| serde_json::from_value::<BulkUpdate>(body).unwrap();
+
+    assert!(update(json!({})).validate(&filters).is_err());
+    assert!(update(json!({ "active": false })).validate(&filters).is_ok());
+    assert!(update(json!({
+        "cost": { "amount": "10.00", "currency": "USD" },
+        "cost_adjustment": { "op": "multiply", "by": "2" }
+    })).validate(&filters).is_err());
+    assert!(update(json!({ "cost_adjustment": { "op": "multiply", "by": "-1" } })).validate(&filters).is_err());
+    assert!(update(json!({ "cost_adjustment": { "op": "percent", "by": "-150" } })).validate(&filters).is_err());
+
+    // A fixed amount only makes sense within one currency
+    let adding = update(json!({ "cost_adjustment": { "op": "add", "by": "1.50" } }));
+    assert!(adding.validate(&filters).is_err());
+    let in_usd = ProductFilters { currency: Some("USD".to_string()), ..Default::default() };
+    assert!(adding.validate(&in_usd).is_ok());
+}
+
+#[test]
+fn test_cost_bounds_need_a_currency() {
+    let across_currencies = ProductFilters { cost_ge: Some(BigDecimal::from(10)), ..Default::default() };
+    assert!(across_currencies.validate().unwrap_err().contains("currency"));
+
+    let within_one = ProductFilters {
+        cost_le: Some(BigDecimal::from(10)),
+        currency: Some("JPY".to_string()),
+        ..Default::default()
+    };
+    assert!(within_one.validate().is_ok());
+}
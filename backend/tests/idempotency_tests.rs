@@ -0,0 +1,79 @@
+// Integration tests for the IdempotencyService
+// These tests require a running PostgreSQL database with the schema set up
+
+use backend::services::IdempotencyService;
+use backend::models::{IdempotencyClaim, StoredResponse};
+use backend::config::{create_pool, get_settings, Database};
+use chrono::Duration;
+use uuid::Uuid;
+
+fn create_test_service(ttl: Duration) -> IdempotencyService {
+    create_leasing_service(ttl, Duration::minutes(5))
+}
+
+fn create_leasing_service(ttl: Duration, lease: Duration) -> IdempotencyService {
+    let settings = get_settings().unwrap();
+    let pool = create_pool(&settings);
+    IdempotencyService::new(Database::new(pool), ttl, lease)
+}
+
+fn stored_response() -> StoredResponse {
+    StoredResponse {
+        status: 201,
+        headers: vec![("content-type".to_string(), "application/json".to_string())],
+        body: b"{\"id\":1}".to_vec(),
+    }
+}
+
+#[tokio::test]
+async fn test_service_idempotency_key_lifecycle() {
+    let service = create_test_service(Duration::hours(1));
+    let key = Uuid::new_v4().to_string();
+
+    assert_eq!(service.claim(&key, "hash-a").await.unwrap(), IdempotencyClaim::Started);
+    // A retry while the first request is still running
+    assert_eq!(service.claim(&key, "hash-a").await.unwrap(), IdempotencyClaim::InProgress);
+
+    service.complete(&key, &stored_response()).await.unwrap();
+    assert_eq!(service.claim(&key, "hash-a").await.unwrap(), IdempotencyClaim::Replay(stored_response()));
+    assert_eq!(service.claim(&key, "hash-b").await.unwrap(), IdempotencyClaim::Mismatch);
+
+    // Completed keys stay put when released
+    service.release(&key).await.unwrap();
+    assert_eq!(service.claim(&key, "hash-a").await.unwrap(), IdempotencyClaim::Replay(stored_response()));
+}
+
+#[tokio::test]
+async fn test_service_idempotency_key_release_and_expiry() {
+    let service = create_test_service(Duration::hours(1));
+    let key = Uuid::new_v4().to_string();
+
+    // A released key can be claimed again, even for another request
+    assert_eq!(service.claim(&key, "hash-a").await.unwrap(), IdempotencyClaim::Started);
+    service.release(&key).await.unwrap();
+    assert_eq!(service.claim(&key, "hash-b").await.unwrap(), IdempotencyClaim::Started);
+
+    // Keys past their TTL are forgotten
+    let expiring = create_test_service(Duration::zero());
+    let key = Uuid::new_v4().to_string();
+    assert_eq!(expiring.claim(&key, "hash-a").await.unwrap(), IdempotencyClaim::Started);
+    expiring.complete(&key, &stored_response()).await.unwrap();
+    assert_eq!(expiring.claim(&key, "hash-b").await.unwrap(), IdempotencyClaim::Started);
+}
+
+#[tokio::test]
+async fn test_service_idempotency_key_lease_takeover() {
+    // A claim whose server died never completes, so its lease simply runs out
+    let crashed = create_leasing_service(Duration::hours(1), Duration::zero());
+    let key = Uuid::new_v4().to_string();
+    assert_eq!(crashed.claim(&key, "hash-a").await.unwrap(), IdempotencyClaim::Started);
+
+    // A retry takes the key over, and holds it for its own lease
+    let service = create_test_service(Duration::hours(1));
+    assert_eq!(service.claim(&key, "hash-a").await.unwrap(), IdempotencyClaim::Started);
+    assert_eq!(service.claim(&key, "hash-a").await.unwrap(), IdempotencyClaim::InProgress);
+
+    // Completed keys are never taken over, however old their lease
+    service.complete(&key, &stored_response()).await.unwrap();
+    assert_eq!(crashed.claim(&key, "hash-a").await.unwrap(), IdempotencyClaim::Replay(stored_response()));
+}
@@ -0,0 +1,86 @@
+use backend::models::{parse_import_csv, ImportRow, Money, Validate, MAX_VARIANT_NAME_LENGTH};
+use uuid::Uuid;
+
+fn valid_rows(csv: &str) -> Vec<(u64, ImportRow)> {
+    parse_import_csv(csv.as_bytes())
+        .unwrap()
+        .into_iter()
+        .map(|(line, row)| (line, row.unwrap()))
+        .collect()
+}
+
+#[test]
+fn test_import_maps_columns_and_variants() {
+    let id = Uuid::new_v4();
+    let rows = valid_rows(&format!(
+        "Name,Cost,currency,active,variant:Size,variant:Color,id\n\
+         Runner,120.00,USD,yes,41|42| 42 ,,\n\
+         Walker,9000,JPY,,43,Black,{}\n",
+        id
+    ));
+
+    assert_eq!(rows.len(), 2);
+    let (line, runner) = &rows[0];
+    assert_eq!(*line, 2);
+    assert_eq!(runner.name, "Runner");
+    assert_eq!(runner.cost, Money::new("120.00", "USD").unwrap());
+    assert_eq!(runner.active, Some(true));
+    assert_eq!(runner.variants, vec![("Size".to_string(), vec!["41".to_string(), "42".to_string()])]);
+    assert!(runner.id.is_none());
+
+    let (_, walker) = &rows[1];
+    assert_eq!(walker.id, Some(id));
+    assert_eq!(walker.active, None);
+    assert_eq!(walker.cost.amount_string(), "9000");
+    assert_eq!(walker.variants.len(), 2);
+}
+
+#[test]
+fn test_import_defaults_currency() {
+    let rows = valid_rows("name,cost\nSandal,15.5\n");
+    assert_eq!(rows[0].1.cost, Money::new("15.50", "USD").unwrap());
+}
+
+#[test]
+fn test_import_reports_row_errors_with_line_numbers() {
+    let rows = parse_import_csv(
+        "name,cost,active\n\
+         Good,10.00,true\n\
+         ,10.00,true\n\
+         \"Multi\nline\",abc,true\n\
+         Odd,1.00,maybe\n\
+         Short,1.00\n\
+         Also good,1.999,false\n".as_bytes()
+    ).unwrap();
+
+    let lines: Vec<u64> = rows.iter().map(|(line, _)| *line).collect();
+    assert_eq!(lines, vec![2, 3, 4, 6, 7, 8]);
+    assert!(rows[0].1.is_ok());
+    assert!(rows[1].1.as_ref().unwrap_err().contains("name"));
+    assert!(rows[2].1.as_ref().unwrap_err().contains("abc"));
+    assert!(rows[3].1.as_ref().unwrap_err().contains("maybe"));
+    assert!(rows[4].1.as_ref().unwrap_err().contains("Expected 3 cells"));
+    assert!(rows[5].1.as_ref().unwrap_err().contains("decimal places"));
+}
+
+#[test]
+fn test_import_rejects_bad_headers() {
+    assert!(parse_import_csv(b"name,price\nShoe,1.00\n").unwrap_err().contains("price"));
+    assert!(parse_import_csv(b"name,active\nShoe,true\n").unwrap_err().contains("cost"));
+    assert!(parse_import_csv(b"name,cost,Name\nShoe,1.00,Boot\n").is_err());
+    assert!(parse_import_csv(b"name,cost,variant:\nShoe,1.00,42\n").is_err());
+}
+
+#[test]
+fn test_import_validates_variant_names_from_the_header() {
+    let long_name = "x".repeat(MAX_VARIANT_NAME_LENGTH + 1);
+    let rows = valid_rows(&format!("name,cost,variant:{},variant:Size\nShoe,1.00,a,42\nBoot,1.00,,43\n", long_name));
+
+    let errors = rows[0].1.validated().unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].field, format!("variant:{}.name", long_name));
+    assert!(errors[0].message.contains("at most"));
+
+    // Rows that leave the column empty don't use the variant
+    assert!(rows[1].1.validated().is_ok());
+}
@@ -0,0 +1,33 @@
+use backend::models::Money;
+use serde_json::json;
+
+#[test]
+fn test_money_serializes_as_exact_decimal_string() {
+    let money = Money::new("39.9", "USD").unwrap();
+    let value = serde_json::to_value(&money).unwrap();
+    assert_eq!(value, json!({ "amount": "39.90", "currency": "USD" }));
+
+    let yen = Money::new("1500", "JPY").unwrap();
+    assert_eq!(serde_json::to_value(&yen).unwrap()["amount"], "1500");
+}
+
+#[test]
+fn test_money_round_trips() {
+    let parsed: Money = serde_json::from_value(json!({ "amount": "0.10", "currency": "KWD" })).unwrap();
+    assert_eq!(parsed.amount_string(), "0.100");
+
+    let reparsed: Money = serde_json::from_value(serde_json::to_value(&parsed).unwrap()).unwrap();
+    assert_eq!(reparsed, parsed);
+}
+
+#[test]
+fn test_money_rejects_inexact_input() {
+    // Floats are refused so no binary rounding sneaks in
+    assert!(serde_json::from_value::<Money>(json!({ "amount": 39.99, "currency": "USD" })).is_err());
+    // More decimal places than the currency allows
+    assert!(Money::new("39.999", "USD").is_err());
+    assert!(Money::new("10.5", "JPY").is_err());
+    // Currency codes must look like ISO-4217
+    assert!(Money::new("10.00", "usd").is_err());
+    assert!(Money::new("ten", "USD").is_err());
+}
@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+use backend::models::{Money, PatchFormat, ProductDocument, ProductPatch, Validate};
+use serde_json::json;
+
+fn document() -> ProductDocument {
+    ProductDocument {
+        name: "Runner".to_string(),
+        cost: Money::new("120.00", "USD").unwrap(),
+        active: true,
+        variants: BTreeMap::from([("Size".to_string(), vec!["41".to_string(), "42".to_string()])])
+    }
+}
+
+#[test]
+fn test_patch_formats_from_content_type() {
+    assert_eq!(PatchFormat::from_content_type("application/merge-patch+json"), Some(PatchFormat::Merge));
+    assert_eq!(PatchFormat::from_content_type("Application/JSON-Patch+JSON; charset=utf-8"), Some(PatchFormat::Json));
+    assert_eq!(PatchFormat::from_content_type("application/json"), None);
+    assert_eq!(PatchFormat::from_content_type(""), None);
+}
+
+#[test]
+fn test_merge_patch_touches_only_given_members() {
+    let patch = ProductPatch::parse(PatchFormat::Merge, br#"{"active": false, "variants": {"Color": ["Black"]}}"#).unwrap();
+    let patched: ProductDocument = serde_json::from_value(patch.apply(&document()).unwrap()).unwrap();
+    assert_eq!(patched.name, "Runner");
+    assert!(!patched.active);
+    assert_eq!(patched.variants.keys().collect::<Vec<_>>(), vec!["Color", "Size"]);
+
+    // Null removes a variant
+    let patch = ProductPatch::Merge(json!({ "variants": { "Size": null } }));
+    let patched: ProductDocument = serde_json::from_value(patch.apply(&document()).unwrap()).unwrap();
+    assert!(patched.variants.is_empty());
+
+    // but a removed required member no longer reads as a product
+    let patch = ProductPatch::Merge(json!({ "name": null }));
+    assert!(serde_json::from_value::<ProductDocument>(patch.apply(&document()).unwrap()).is_err());
+}
+
+#[test]
+fn test_json_patch_operations() {
+    let patch = ProductPatch::parse(
+        PatchFormat::Json,
+        br#"[{"op": "add", "path": "/variants/Size/-", "value": "43"}, {"op": "replace", "path": "/name", "value": "Trail"}]"#
+    ).unwrap();
+    let patched: ProductDocument = serde_json::from_value(patch.apply(&document()).unwrap()).unwrap();
+    assert_eq!(patched.name, "Trail");
+    assert_eq!(patched.variants["Size"], vec!["41", "42", "43"]);
+
+    let patch = ProductPatch::Json(serde_json::from_value(json!([{ "op": "test", "path": "/name", "value": "Trail" }])).unwrap());
+    assert!(patch.apply(&document()).is_err());
+
+    assert!(ProductPatch::parse(PatchFormat::Json, br#"{"op": "add"}"#).is_err());
+}
+
+#[test]
+fn test_patched_documents_are_validated() {
+    let patch = ProductPatch::Merge(json!({ "name": " ", "variants": { "Size": [], "Width": ["Wide", "Wide"] } }));
+    let patched: ProductDocument = serde_json::from_value(patch.apply(&document()).unwrap()).unwrap();
+    let fields: Vec<String> = patched.validated().unwrap_err().into_iter().map(|error| error.field).collect();
+    assert_eq!(fields, vec!["name", "variants.Size", "variants.Width[1]"]);
+}
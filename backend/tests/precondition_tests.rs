@@ -0,0 +1,34 @@
+use backend::models::{names_tag, version_etag, IfMatch};
+
+#[test]
+fn test_version_etags_are_strong_tags() {
+    assert_eq!(version_etag(3, None), "\"3\"");
+    assert_eq!(version_etag(3, Some("variants")), "\"3-variants\"");
+}
+
+#[test]
+fn test_if_match_accepts_any_representation_of_a_version() {
+    let if_match = IfMatch::parse("\"2\", \"3-variants\"");
+    assert_eq!(if_match, IfMatch::Versions(vec![2, 3]));
+    assert!(if_match.matches(3));
+    assert!(!if_match.matches(4));
+
+    assert!(IfMatch::parse(" * ").matches(42));
+}
+
+#[test]
+fn test_if_match_never_matches_weak_or_foreign_tags() {
+    assert!(!IfMatch::parse("W/\"3\"").matches(3));
+    assert!(!IfMatch::parse("\"abc\"").matches(3));
+    assert!(!IfMatch::parse("3").matches(3));
+    assert!(!IfMatch::parse("").matches(3));
+}
+
+#[test]
+fn test_if_none_match_uses_weak_comparison() {
+    assert!(names_tag("W/\"3\"", "\"3\""));
+    assert!(names_tag("\"2\", \"3\"", "\"3\""));
+    assert!(names_tag("*", "\"3\""));
+    assert!(!names_tag("\"3\"", "\"3-variants\""));
+    assert!(!names_tag("", "\"3\""));
+}
@@ -2,14 +2,21 @@
 // These tests require a running PostgreSQL database with the schema set up
 
 use backend::services::ProductService;
-use backend::models::{NewCompleteProduct, NewProduct, ProductFilters, ProductUpdates};
-use backend::config::{create_pool, get_settings};
+use backend::models::{
+    parse_import_csv, BulkMode, BulkUpdate, CostAdjustment, ExportFormat, IfMatch, ImportAction, Money, NewCompleteProduct, NewProduct, NewVariant, NewVariantValue, ProductDocument, ProductFilters, ProductPatch, ProductUpdates, SuggestQuery,
+    VariantRef
+};
+use backend::config::{create_pool, get_settings, Database};
+use backend::traits::responses::AppError;
+use chrono::{Duration, Utc};
+use serde_json::json;
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
 fn create_test_service() -> ProductService {
     let settings = get_settings().unwrap();
     let pool = create_pool(&settings);
-    ProductService::new(pool)
+    ProductService::new(Database::new(pool))
 }
 
 #[tokio::test]
@@ -20,23 +27,23 @@ async fn test_service_create_and_get_product() {
         product: NewProduct {
             id: None,
             name: "Test Product".to_string(),
-            cost: 29.99,
+            cost: Money::new("29.99", "USD").unwrap(),
             active: true,
         },
         variants: vec![],
     };
     
     // Create a product
-    let result = service.create_product(new_product);
+    let result = service.create_product(new_product, None).await;
     assert!(result.is_ok(), "Failed to create product: {:?}", result.err());
     
     let created_product = result.unwrap();
     assert_eq!(created_product.name, "Test Product");
-    assert_eq!(created_product.cost, 29.99);
+    assert_eq!(created_product.cost.amount_string(), "29.99");
     assert!(created_product.active);
     
     // Get the product by ID
-    let get_result = service.get_product_by_id(created_product.id);
+    let get_result = service.get_product_by_id(created_product.id).await;
     assert!(get_result.is_ok(), "Failed to get product: {:?}", get_result.err());
     
     let retrieved = get_result.unwrap();
@@ -46,7 +53,323 @@ async fn test_service_create_and_get_product() {
     assert_eq!(retrieved.name, "Test Product");
     
     // Clean up: delete the product
-    let _ = service.delete_product(created_product.id);
+    let _ = service.delete_product(created_product.id, None, None).await;
+}
+
+fn bulk_item(id: Uuid, name: &str, width: &str, allowed: &[&str]) -> NewCompleteProduct {
+    NewCompleteProduct {
+        product: NewProduct {
+            id: Some(id),
+            name: name.to_string(),
+            cost: Money::new("50.00", "USD").unwrap(),
+            active: true,
+        },
+        variants: vec![NewVariantValue {
+            variant: VariantRef::Name(NewVariant {
+                name: format!("Width {}", name),
+                allowed_values: Some(allowed.iter().map(|v| v.to_string()).collect()),
+            }),
+            values: vec![Some(width.to_string())],
+        }],
+    }
+}
+
+#[tokio::test]
+async fn test_service_bulk_create_products() {
+    let service = create_test_service();
+    let brand = format!("bulk{}", Uuid::new_v4().simple());
+    let ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+    let items = |bad: usize| -> Vec<NewCompleteProduct> {
+        ids.iter().enumerate().map(|(i, id)| {
+            let width = if i == bad { "Huge" } else { "Wide" };
+            bulk_item(*id, &format!("{} {}", brand, i), width, &["Narrow", "Wide"])
+        }).collect()
+    };
+    
+    // Atomic mode: one disallowed value rolls back the whole batch
+    let result = service.create_products(items(1), BulkMode::Atomic, None).await.unwrap();
+    assert_eq!(result.created, 0);
+    assert_eq!(result.failed, 3);
+    assert!(result.items[1].error.as_deref().unwrap().contains("Huge"));
+    assert!(result.items.iter().all(|item| item.id.is_none()));
+    for id in &ids {
+        assert!(service.get_product_by_id(*id).await.unwrap().is_none());
+    }
+    
+    // Per-item mode: the valid items are created in order and the bad one reported
+    let result = service.create_products(items(1), BulkMode::PerItem, Some("importer")).await.unwrap();
+    assert_eq!(result.created, 2);
+    assert_eq!(result.failed, 1);
+    assert_eq!(result.items.iter().map(|item| item.index).collect::<Vec<_>>(), vec![0, 1, 2]);
+    assert_eq!(result.items[0].id, Some(ids[0]));
+    assert!(result.items[1].id.is_none());
+    assert_eq!(result.items[2].id, Some(ids[2]));
+    
+    let created = service.get_complete_product_by_id(ids[2]).await.unwrap().unwrap();
+    assert_eq!(created.product.name, format!("{} 2", brand));
+    assert_eq!(created.variants.len(), 1);
+    let history = service.product_history(ids[2]).await.unwrap().unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].changed_by.as_deref(), Some("importer"));
+    
+    // An id that already exists fails on its own in per-item mode
+    let retry = service.create_products(items(usize::MAX), BulkMode::PerItem, None).await.unwrap();
+    assert_eq!(retry.created, 1);
+    assert_eq!(retry.items[1].id, Some(ids[1]));
+    // Without the database's own message, which names the constraint
+    let duplicate = retry.items[0].error.as_deref().unwrap();
+    assert_eq!(duplicate, "A resource with the same identifier already exists");
+    assert!(!duplicate.contains("products_pkey"));
+    
+    // ...and fails the whole batch in atomic mode
+    let fresh = Uuid::new_v4();
+    let mut batch = vec![bulk_item(fresh, &format!("{} fresh", brand), "Wide", &["Wide"])];
+    batch.push(bulk_item(ids[0], &format!("{} 0", brand), "Wide", &["Wide"]));
+    assert!(service.create_products(batch, BulkMode::Atomic, None).await.is_err());
+    assert!(service.get_product_by_id(fresh).await.unwrap().is_none());
+    
+    // Items that break the validation rules are reported with their field paths
+    let mut blank = bulk_item(Uuid::new_v4(), " ", "Wide", &["Wide"]);
+    blank.product.cost = Money::new("-1.00", "USD").unwrap();
+    let unsized_item = bulk_item(Uuid::new_v4(), &format!("{} unsized", brand), " ", &["Wide"]);
+    let result = service.create_products(vec![blank, unsized_item], BulkMode::PerItem, None).await.unwrap();
+    assert_eq!(result.failed, 2);
+    let fields = |index: usize| result.items[index].errors.iter().map(|e| e.field.clone()).collect::<Vec<_>>();
+    assert_eq!(fields(0), vec!["[0].product.name", "[0].product.cost"]);
+    assert_eq!(fields(1), vec!["[1].variants[0].values[0]"]);
+    assert_eq!(result.items[0].error.as_deref(), Some("2 fields are invalid"));
+    
+    // Clean up: delete the test products
+    for id in &ids {
+        let _ = service.delete_product(*id, None, None).await;
+    }
+}
+
+#[tokio::test]
+async fn test_service_bulk_update_and_delete_by_filter() {
+    let service = create_test_service();
+    let brand = format!("bulk{}", Uuid::new_v4().simple());
+    let mut ids = Vec::new();
+    for (suffix, amount, currency) in [("a", "10.00", "USD"), ("b", "4.99", "USD"), ("c", "1000", "JPY")] {
+        let product = service.create_product(NewCompleteProduct {
+            product: NewProduct {
+                id: None,
+                name: format!("{} {}", brand, suffix),
+                cost: Money::new(amount, currency).unwrap(),
+                active: true,
+            },
+            variants: vec![],
+        }, None).await.unwrap();
+        ids.push(product.id);
+    }
+    let selector = |currency: Option<&str>| ProductFilters {
+        name: Some(brand.clone()),
+        currency: currency.map(str::to_string),
+        ..Default::default()
+    };
+    let percent = |by: &str| BulkUpdate {
+        updates: ProductUpdates { name: None, cost: None, active: None },
+        cost_adjustment: Some(CostAdjustment::Percent(by.parse().unwrap())),
+    };
+    let cost_of = async |id: Uuid| service.get_product_by_id(id).await.unwrap().unwrap().cost.amount_string();
+    
+    // A dry run reports the matches without touching them
+    let dry = service.bulk_update_products(selector(None), percent("8"), true, 100, None).await.unwrap();
+    assert!(dry.dry_run);
+    assert_eq!(dry.matched, 3);
+    assert_eq!(dry.ids.len(), 3);
+    assert_eq!(cost_of(ids[0]).await, "10.00");
+    
+    // Over the limit, a dry run still counts but a real run is refused
+    let capped = service.bulk_update_products(selector(None), percent("8"), true, 2, None).await.unwrap();
+    assert_eq!(capped.matched, 3);
+    assert!(capped.ids.is_empty());
+    assert!(service.bulk_update_products(selector(None), percent("8"), false, 2, None).await.is_err());
+    assert_eq!(cost_of(ids[0]).await, "10.00");
+    
+    // Costs are rounded to the minor units of each product's currency
+    let raised = service.bulk_update_products(selector(None), percent("8"), false, 100, Some("pricing")).await.unwrap();
+    assert_eq!(raised.matched, 3);
+    assert_eq!(cost_of(ids[0]).await, "10.80");
+    assert_eq!(cost_of(ids[1]).await, "5.39");
+    assert_eq!(cost_of(ids[2]).await, "1080");
+    let history = service.product_history(ids[0]).await.unwrap().unwrap();
+    assert_eq!(history[0].action, "update");
+    assert_eq!(history[0].changed_by.as_deref(), Some("pricing"));
+    
+    // A change that would make a cost negative is rolled back as a whole
+    let lower = BulkUpdate {
+        updates: ProductUpdates { name: None, cost: None, active: None },
+        cost_adjustment: Some(CostAdjustment::Add("-6.00".parse().unwrap())),
+    };
+    assert!(service.bulk_update_products(selector(Some("USD")), lower, false, 100, None).await.is_err());
+    assert_eq!(cost_of(ids[0]).await, "10.80");
+    
+    // So is one that would raise a cost past the maximum, even past what the column can store
+    for factor in ["100000000", "100000000000000000000"] {
+        let raise = BulkUpdate {
+            updates: ProductUpdates { name: None, cost: None, active: None },
+            cost_adjustment: Some(CostAdjustment::Multiply(factor.parse().unwrap())),
+        };
+        let err = service.bulk_update_products(selector(None), raise, false, 100, None).await.unwrap_err();
+        assert_eq!(AppError::from(err).code(), "validation_failed");
+    }
+    assert_eq!(cost_of(ids[0]).await, "10.80");
+    
+    // Field updates apply to the matching products only
+    let cheap = ProductFilters { cost_le: Some("6".parse().unwrap()), ..selector(Some("USD")) };
+    let deactivate = BulkUpdate {
+        updates: ProductUpdates { name: None, cost: None, active: Some(false) },
+        cost_adjustment: None,
+    };
+    let deactivated = service.bulk_update_products(cheap, deactivate, false, 100, None).await.unwrap();
+    assert_eq!(deactivated.ids, vec![ids[1]]);
+    assert!(!service.get_product_by_id(ids[1]).await.unwrap().unwrap().active);
+    assert!(service.get_product_by_id(ids[0]).await.unwrap().unwrap().active);
+    
+    // Bulk delete tombstones the matches, so they can still be restored
+    let dry = service.bulk_delete_products(selector(None), true, 100, None).await.unwrap();
+    assert_eq!(dry.matched, 3);
+    assert!(service.get_product_by_id(ids[0]).await.unwrap().is_some());
+    let deleted = service.bulk_delete_products(selector(None), false, 100, None).await.unwrap();
+    assert_eq!(deleted.matched, 3);
+    for id in &ids {
+        assert!(service.get_product_by_id(*id).await.unwrap().is_none());
+    }
+    assert_eq!(service.product_history(ids[2]).await.unwrap().unwrap()[0].action, "delete");
+    assert!(service.restore_product(ids[2], None).await.unwrap().is_some());
+    
+    // Clean up: delete the restored product
+    let _ = service.delete_product(ids[2], None, None).await;
+}
+
+#[tokio::test]
+async fn test_service_import_products_from_csv() {
+    let service = create_test_service();
+    let brand = format!("import{}", Uuid::new_v4().simple());
+    let size = format!("Size {}", brand);
+    let csv = |rows: &[String]| format!("name,cost,currency,active,variant:{}\n{}\n", size, rows.join("\n"));
+    let first = csv(&[
+        format!("{} Runner,120.00,USD,true,41|42", brand),
+        format!("{} Walker,80.00,USD,,43", brand),
+        ",10.00,USD,true,".to_string(),
+        format!("{} Runner,99.00,USD,true,", brand),
+    ]);
+    
+    // A dry run reports every row but leaves nothing behind
+    let dry = service.import_products(first.as_bytes(), true, None).await.unwrap();
+    assert!(dry.dry_run);
+    assert_eq!((dry.created, dry.failed), (2, 2));
+    assert_eq!(dry.rows[2].line, 4);
+    assert!(dry.rows[3].error.as_deref().unwrap().contains("line 2"));
+    let by_brand = ProductFilters { name: Some(brand.clone()), ..Default::default() };
+    assert!(service.get_products(Some(by_brand.clone())).await.unwrap().items.is_empty());
+    
+    // The real import creates the valid rows with their variants
+    let report = service.import_products(first.as_bytes(), false, Some("merch")).await.unwrap();
+    assert_eq!((report.created, report.updated, report.failed), (2, 0, 2));
+    let runner_id = report.rows[0].id.unwrap();
+    let runner = service.get_complete_product_by_id(runner_id).await.unwrap().unwrap();
+    assert_eq!(runner.product.cost.amount_string(), "120.00");
+    assert_eq!(runner.variants[0].values.len(), 2);
+    
+    // Importing the same rows again changes nothing
+    let again = service.import_products(first.as_bytes(), false, None).await.unwrap();
+    assert_eq!((again.created, again.updated, again.unchanged), (0, 0, 2));
+    assert_eq!(service.product_history(runner_id).await.unwrap().unwrap().len(), 1);
+    
+    // Rows are matched by name, or by id when given
+    let second = format!(
+        "id,name,cost,active,variant:{}\n,{} Runner,125.00,false,42\n{},{} Renamed,80.00,,\n",
+        size, brand, report.rows[1].id.unwrap(), brand
+    );
+    let updated = service.import_products(second.as_bytes(), false, None).await.unwrap();
+    assert_eq!(updated.updated, 2);
+    assert!(updated.rows.iter().all(|row| row.action == Some(ImportAction::Updated)));
+    let runner = service.get_complete_product_by_id(runner_id).await.unwrap().unwrap();
+    assert_eq!(runner.product.cost.amount_string(), "125.00");
+    assert!(!runner.product.active);
+    assert_eq!(runner.variants[0].values.len(), 1);
+    assert_eq!(runner.variants[0].values[0].value.as_deref(), Some("42"));
+    let walker = service.get_product_by_id(report.rows[1].id.unwrap()).await.unwrap().unwrap();
+    assert_eq!(walker.name, format!("{} Renamed", brand));
+    
+    // A bad header rejects the whole file
+    assert!(service.import_products(b"name,price\nShoe,1.00\n", false, None).await.is_err());
+    
+    // Clean up: delete the imported products
+    let _ = service.delete_product(runner_id, None, None).await;
+    let _ = service.delete_product(walker.id, None, None).await;
+}
+
+#[tokio::test]
+async fn test_service_export_round_trips_through_import() {
+    let service = create_test_service();
+    let brand = format!("export{}", Uuid::new_v4().simple());
+    let mut created = Vec::new();
+    for (suffix, values) in [("a", vec!["41", "42"]), ("b", vec![]), ("c", vec!["43"])] {
+        let product = service.create_product(NewCompleteProduct {
+            product: NewProduct {
+                id: None,
+                name: format!("{}, {} \"quoted\"", brand, suffix),
+                cost: Money::new("70.00", "EUR").unwrap(),
+                active: true,
+            },
+            variants: if values.is_empty() { vec![] } else { vec![variant(&format!("Size {}", brand), &values)] },
+        }, None).await.unwrap();
+        created.push((product.id, product.name.clone(), values));
+    }
+    created.sort();
+    let filters = ProductFilters { name: Some(brand.clone()), ..Default::default() };
+    
+    // The header comes first; a product created after it is outside the export's snapshot
+    let mut chunks = service.export_products(filters.clone(), ExportFormat::Csv);
+    let mut csv = chunks.recv().await.unwrap().unwrap();
+    assert!(String::from_utf8_lossy(&csv).contains(&format!("Size {}", brand)));
+    let late = service.create_product(NewCompleteProduct {
+        product: NewProduct {
+            id: None,
+            name: format!("{} late", brand),
+            cost: Money::new("70.00", "EUR").unwrap(),
+            active: true,
+        },
+        variants: vec![],
+    }, None).await.unwrap();
+    while let Some(chunk) = chunks.recv().await {
+        csv.extend(chunk.unwrap());
+    }
+    
+    // The CSV reads back as the same rows, in id order, through the import parser
+    let rows = parse_import_csv(&csv).unwrap();
+    assert_eq!(rows.len(), 3);
+    for ((_, row), (id, name, values)) in rows.iter().zip(&created) {
+        let row = row.as_ref().unwrap();
+        assert_eq!(row.id, Some(*id));
+        assert_eq!(&row.name, name);
+        assert_eq!(row.cost, Money::new("70.00", "EUR").unwrap());
+        assert_eq!(row.active, Some(true));
+        let exported = row.variants.first().map(|(_, v)| v.clone()).unwrap_or_default();
+        assert_eq!(exported, values.iter().map(|v| v.to_string()).collect::<Vec<_>>());
+    }
+    
+    // NDJSON has no header and one complete product per line
+    let mut chunks = service.export_products(filters, ExportFormat::Ndjson);
+    let mut ndjson = chunks.recv().await.unwrap().unwrap();
+    assert!(ndjson.is_empty());
+    while let Some(chunk) = chunks.recv().await {
+        ndjson.extend(chunk.unwrap());
+    }
+    let ndjson = String::from_utf8(ndjson).unwrap();
+    let lines: Vec<serde_json::Value> = ndjson.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines.iter().filter(|line| line["id"] == late.id.to_string()).count(), 1);
+    assert!(lines[0]["variants"].is_array());
+    
+    // Clean up: delete the test products
+    for (id, _, _) in &created {
+        let _ = service.delete_product(*id, None, None).await;
+    }
+    let _ = service.delete_product(late.id, None, None).await;
 }
 
 #[tokio::test]
@@ -58,33 +381,76 @@ async fn test_service_update_product() {
         product: NewProduct {
             id: None,
             name: "Update Test Product".to_string(),
-            cost: 45.00,
+            cost: Money::new("45.00", "USD").unwrap(),
             active: true,
         },
         variants: vec![],
     };
     
-    let created = service.create_product(new_product).unwrap();
+    let created = service.create_product(new_product, None).await.unwrap();
     
-    // Update the product
-    let updates = ProductUpdates {
-        name: Some("Updated Product Name".to_string()),
-        cost: Some(55.00),
-        active: Some(false),
+    // Replace the product
+    let document = ProductDocument {
+        name: "Updated Product Name".to_string(),
+        cost: Money::new("55.00", "USD").unwrap(),
+        active: false,
+        variants: BTreeMap::new(),
     };
     
-    let update_result = service.update_product(created.id, updates);
+    let update_result = service.replace_product(created.id, document, None, None).await;
     assert!(update_result.is_ok(), "Failed to update product: {:?}", update_result.err());
     
     let updated = update_result.unwrap();
     assert!(updated.is_some());
-    let updated = updated.unwrap();
+    let updated = updated.unwrap().product;
     assert_eq!(updated.name, "Updated Product Name");
-    assert_eq!(updated.cost, 55.0);
+    assert_eq!(updated.cost.amount_string(), "55.00");
     assert!(!updated.active);
     
     // Clean up: delete the product
-    let _ = service.delete_product(created.id);
+    let _ = service.delete_product(created.id, None, None).await;
+}
+
+#[tokio::test]
+async fn test_service_conditional_update_and_delete() {
+    let service = create_test_service();
+    
+    let created = service.create_product(NewCompleteProduct {
+        product: NewProduct {
+            id: None,
+            name: "Versioned Test Product".to_string(),
+            cost: Money::new("45.00", "USD").unwrap(),
+            active: true,
+        },
+        variants: vec![],
+    }, None).await.unwrap();
+    assert_eq!(created.version, 1);
+    
+    let rename = |name: &str| ProductPatch::Merge(json!({ "name": name }));
+    
+    // Every update bumps the version
+    let first = service.patch_product(created.id, rename("First Edit"), Some(IfMatch::Versions(vec![1])), None).await
+        .unwrap()
+        .unwrap();
+    assert_eq!(first.product.version, 2);
+    
+    // A second admin still holding version 1 is turned away and nothing changes
+    let stale = service.patch_product(created.id, rename("Second Edit"), Some(IfMatch::Versions(vec![1])), None).await;
+    assert!(stale.is_err());
+    let current = service.get_product_by_id(created.id).await.unwrap().unwrap();
+    assert_eq!((current.name.as_str(), current.version), ("First Edit", 2));
+    
+    // Unconditional and wildcard writes still go through
+    let second = service.patch_product(created.id, rename("Second Edit"), Some(IfMatch::Any), None).await.unwrap().unwrap();
+    assert_eq!(second.product.version, 3);
+    
+    // Deletes honour the precondition too
+    assert!(service.delete_product(created.id, Some(IfMatch::Versions(vec![2])), None).await.is_err());
+    assert!(service.delete_product(created.id, Some(IfMatch::Versions(vec![3])), None).await.unwrap());
+    
+    // A missing product is a 404 rather than a failed precondition
+    assert!(service.patch_product(created.id, rename("Gone"), Some(IfMatch::Any), None).await.unwrap().is_none());
+    assert!(!service.delete_product(created.id, Some(IfMatch::Versions(vec![3])), None).await.unwrap());
 }
 
 #[tokio::test]
@@ -96,26 +462,194 @@ async fn test_service_delete_product() {
         product: NewProduct {
             id: None,
             name: "Delete Test Product".to_string(),
-            cost: 15.99,
+            cost: Money::new("15.99", "USD").unwrap(),
             active: true,
         },
         variants: vec![],
     };
     
-    let created = service.create_product(new_product).unwrap();
+    let created = service.create_product(new_product, None).await.unwrap();
     let product_id = created.id;
     
     // Delete the product
-    let delete_result = service.delete_product(product_id);
+    let delete_result = service.delete_product(product_id, None, None).await;
     assert!(delete_result.is_ok(), "Failed to delete product: {:?}", delete_result.err());
     assert!(delete_result.unwrap(), "Product should have been deleted");
     
     // Verify it's deleted
-    let get_result = service.get_product_by_id(product_id);
+    let get_result = service.get_product_by_id(product_id).await;
     assert!(get_result.is_ok());
     assert!(get_result.unwrap().is_none(), "Product should not exist after deletion");
 }
 
+#[tokio::test]
+async fn test_service_soft_delete_restore_and_purge() {
+    let service = create_test_service();
+    let name = format!("Soft Delete Test {}", Uuid::new_v4());
+    
+    let created = service.create_product(NewCompleteProduct {
+        product: NewProduct {
+            id: None,
+            name: name.clone(),
+            cost: Money::new("49.99", "USD").unwrap(),
+            active: true,
+        },
+        variants: vec![variant(&format!("Size {}", Uuid::new_v4().simple()), &["42", "43"])],
+    }, None).await.unwrap();
+    
+    // Deleted products disappear from every read and can't be changed
+    assert!(service.delete_product(created.id, None, None).await.unwrap());
+    assert!(service.get_product_by_id(created.id).await.unwrap().is_none());
+    assert!(service.get_complete_product_by_id(created.id).await.unwrap().is_none());
+    let filters = ProductFilters { name: Some(name.clone()), ..Default::default() };
+    assert!(service.get_products(Some(filters.clone())).await.unwrap().items.is_empty());
+    assert!(service.patch_product(
+        created.id,
+        ProductPatch::Merge(json!({ "name": "Edited While Deleted" })),
+        None,
+        None
+    ).await.unwrap().is_none());
+    assert!(!service.delete_product(created.id, None, None).await.unwrap());
+    
+    // ...unless explicitly asked for
+    let with_deleted = service.get_products(Some(ProductFilters {
+        include_deleted: Some(true),
+        ..filters.clone()
+    })).await.unwrap();
+    assert_eq!(with_deleted.items.len(), 1);
+    assert!(with_deleted.items[0].deleted_at.is_some());
+    
+    // Restoring brings the product back with its variant values
+    let restored = service.restore_product(created.id, None).await.unwrap().expect("product should be restorable");
+    assert!(restored.deleted_at.is_none());
+    assert!(restored.version > created.version);
+    let complete = service.get_complete_product_by_id(created.id).await.unwrap().unwrap();
+    assert_eq!(complete.variants[0].values.len(), 2);
+    assert_eq!(service.restore_product(created.id, None).await.unwrap().unwrap().version, restored.version);
+    
+    // Purging keeps tombstones inside the retention period and removes older ones
+    assert!(service.delete_product(created.id, None, None).await.unwrap());
+    let summary = service.purge_deleted_products(Duration::days(30)).await.unwrap();
+    assert!(summary.deleted_before < Utc::now());
+    let kept = service.get_products(Some(ProductFilters {
+        include_deleted: Some(true),
+        ..filters.clone()
+    })).await.unwrap();
+    assert_eq!(kept.items.len(), 1);
+    
+    assert!(service.purge_deleted_products(Duration::zero()).await.unwrap().purged >= 1);
+    assert!(service.restore_product(created.id, None).await.unwrap().is_none());
+    assert!(service.get_products(Some(ProductFilters {
+        include_deleted: Some(true),
+        ..filters
+    })).await.unwrap().items.is_empty());
+}
+
+#[tokio::test]
+async fn test_service_revision_history_and_revert() {
+    let service = create_test_service();
+    
+    let created = service.create_product(NewCompleteProduct {
+        product: NewProduct {
+            id: None,
+            name: "History Test Product".to_string(),
+            cost: Money::new("80.00", "USD").unwrap(),
+            active: true,
+        },
+        variants: vec![],
+    }, Some("alice")).await.unwrap();
+    
+    service.patch_product(
+        created.id,
+        ProductPatch::Merge(json!({ "cost": { "amount": "60.00", "currency": "USD" } })),
+        None,
+        Some("bob")
+    ).await.unwrap().unwrap();
+    service.patch_product(
+        created.id,
+        ProductPatch::Merge(json!({ "name": "History Test Product (Sale)", "active": false })),
+        None,
+        None
+    ).await.unwrap().unwrap();
+    
+    // Newest first, each with only the fields that changed
+    let history = service.product_history(created.id).await.unwrap().expect("product should exist");
+    let actions: Vec<&str> = history.iter().map(|r| r.action.as_str()).collect();
+    assert_eq!(actions, vec!["update", "update", "create"]);
+    assert_eq!(history[2].changed_by.as_deref(), Some("alice"));
+    assert_eq!(history[2].changes.len(), 5);
+    assert!(history[2].changes.iter().all(|c| c.from.is_none()));
+    
+    let price_change = &history[1];
+    assert_eq!(price_change.changed_by.as_deref(), Some("bob"));
+    assert_eq!(price_change.changes.len(), 1);
+    assert_eq!(price_change.changes[0].field, "cost");
+    assert_eq!(price_change.changes[0].from.as_ref().unwrap()["amount"], "80.00");
+    assert_eq!(price_change.changes[0].to["amount"], "60.00");
+    
+    let fields: Vec<&str> = history[0].changes.iter().map(|c| c.field.as_str()).collect();
+    assert_eq!(fields, vec!["name", "active"]);
+    
+    // Reverting to the first revision is itself recorded
+    let reverted = service.revert_product(created.id, history[2].revision, None, Some("carol")).await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reverted.name, "History Test Product");
+    assert_eq!(reverted.cost, Money::new("80.00", "USD").unwrap());
+    assert!(reverted.active);
+    
+    let history = service.product_history(created.id).await.unwrap().unwrap();
+    assert_eq!(history[0].action, "revert");
+    assert_eq!(history[0].revision, reverted.version);
+    assert_eq!(history[0].changes.len(), 3);
+    
+    // Unknown revisions and stale versions are rejected
+    let err = service.revert_product(created.id, 999, None, None).await.unwrap_err();
+    assert_eq!(AppError::from(err).code(), "not_found");
+    assert!(service.revert_product(created.id, 1, Some(IfMatch::Versions(vec![1])), None).await.is_err());
+    
+    // Deleting keeps the history readable
+    assert!(service.delete_product(created.id, None, None).await.unwrap());
+    let history = service.product_history(created.id).await.unwrap().unwrap();
+    assert_eq!(history[0].action, "delete");
+    assert_eq!(history[0].changes[0].field, "deleted");
+    assert!(service.revert_product(created.id, 1, None, None).await.unwrap().is_none());
+    assert!(service.product_history(Uuid::new_v4()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_service_purge_keeps_history() {
+    let service = create_test_service();
+    
+    let created = service.create_product(NewCompleteProduct {
+        product: NewProduct {
+            id: None,
+            name: format!("Purge History Test {}", Uuid::new_v4()),
+            cost: Money::new("15.00", "USD").unwrap(),
+            active: true,
+        },
+        variants: vec![variant(&format!("Size {}", Uuid::new_v4().simple()), &["S"])],
+    }, Some("alice")).await.unwrap();
+    assert!(service.delete_product(created.id, None, Some("bob")).await.unwrap());
+    let before_purge = service.get_products(None).await.unwrap().last_modified.unwrap();
+    assert!(service.purge_deleted_products(Duration::zero()).await.unwrap().purged >= 1);
+    assert!(service.get_product_by_id(created.id).await.unwrap().is_none());
+    
+    // Lists are last modified by the purge, though the row it removed is gone
+    assert!(service.get_products(None).await.unwrap().last_modified.unwrap() > before_purge);
+    
+    // The audit trail survives the purge and records it last
+    let history = service.product_history(created.id).await.unwrap().expect("history should outlive the purge");
+    let actions: Vec<&str> = history.iter().map(|r| r.action.as_str()).collect();
+    assert_eq!(actions, vec!["purge", "delete", "create"]);
+    assert_eq!(history[0].revision, created.version + 2);
+    assert!(history[0].changes.is_empty());
+    assert_eq!(history[1].changed_by.as_deref(), Some("bob"));
+    
+    // A purged product can't be reverted back into existence
+    assert!(service.revert_product(created.id, created.version, None, None).await.unwrap().is_none());
+}
+
 #[tokio::test]
 async fn test_service_get_products_with_filters() {
     let service = create_test_service();
@@ -126,7 +660,7 @@ async fn test_service_get_products_with_filters() {
             product: NewProduct {
                 id: None,
                 name: "Filter Test Product 1".to_string(),
-                cost: 10.0,
+                cost: Money::new("10.00", "USD").unwrap(),
                 active: true,
             },
             variants: vec![],
@@ -135,7 +669,7 @@ async fn test_service_get_products_with_filters() {
             product: NewProduct {
                 id: None,
                 name: "Filter Test Product 2".to_string(),
-                cost: 50.0,
+                cost: Money::new("50.00", "USD").unwrap(),
                 active: false,
             },
             variants: vec![],
@@ -144,7 +678,7 @@ async fn test_service_get_products_with_filters() {
     
     let mut created_ids = Vec::new();
     for product in products {
-        let created = service.create_product(product).unwrap();
+        let created = service.create_product(product, None).await.unwrap();
         created_ids.push(created.id);
     }
     
@@ -154,22 +688,25 @@ async fn test_service_get_products_with_filters() {
         cost_ge: None,
         cost_le: None,
         is_active: None,
+        ..Default::default()
     };
     
-    let filtered_result = service.get_products(Some(name_filter));
+    let filtered_result = service.get_products(Some(name_filter)).await;
     assert!(filtered_result.is_ok(), "Failed to get filtered products: {:?}", filtered_result.err());
-    let filtered_products = filtered_result.unwrap();
+    let filtered_products = filtered_result.unwrap().items;
     assert!(!filtered_products.is_empty(), "Should find products with name filter");
     
     // Test filtering by cost range
     let cost_filter = ProductFilters {
         name: None,
-        cost_ge: Some(40.0),
-        cost_le: Some(60.0),
+        cost_ge: Some("40.00".parse().unwrap()),
+        cost_le: Some("60.00".parse().unwrap()),
+        currency: Some("USD".to_string()),
         is_active: None,
+        ..Default::default()
     };
     
-    let cost_filtered_result = service.get_products(Some(cost_filter));
+    let cost_filtered_result = service.get_products(Some(cost_filter)).await;
     assert!(cost_filtered_result.is_ok());
     
     // Test filtering by active status
@@ -178,25 +715,533 @@ async fn test_service_get_products_with_filters() {
         cost_ge: None,
         cost_le: None,
         is_active: Some(true),
+        ..Default::default()
     };
     
-    let active_filtered_result = service.get_products(Some(active_filter));
+    let active_filtered_result = service.get_products(Some(active_filter)).await;
     assert!(active_filtered_result.is_ok());
     
     // Clean up: delete the test products
     for id in created_ids {
-        let _ = service.delete_product(id);
+        let _ = service.delete_product(id, None, None).await;
+    }
+}
+
+#[tokio::test]
+async fn test_service_get_products_paginated() {
+    let service = create_test_service();
+    let prefix = format!("Pagination Test {}", Uuid::new_v4());
+    
+    let mut created_ids = Vec::new();
+    for i in 0..3 {
+        let created = service.create_product(NewCompleteProduct {
+            product: NewProduct {
+                id: None,
+                name: format!("{} {}", prefix, i),
+                cost: Money::new("10.00", "USD").unwrap(),
+                active: true,
+            },
+            variants: vec![],
+        }, None).await.unwrap();
+        created_ids.push(created.id);
+    }
+    
+    // First page carries a cursor and the total count
+    let first_page = service.get_products(Some(ProductFilters {
+        name: Some(prefix.clone()),
+        limit: Some(2),
+        include_total: Some(true),
+        ..Default::default()
+    })).await.unwrap();
+    assert_eq!(first_page.items.len(), 2);
+    assert_eq!(first_page.total, Some(3));
+    assert!(first_page.next_cursor.is_some());
+    
+    // Second page picks up after the cursor and is the last one
+    let second_page = service.get_products(Some(ProductFilters {
+        name: Some(prefix.clone()),
+        limit: Some(2),
+        cursor: first_page.next_cursor.clone(),
+        ..Default::default()
+    })).await.unwrap();
+    assert_eq!(second_page.items.len(), 1);
+    assert!(second_page.next_cursor.is_none());
+    assert!(second_page.total.is_none());
+    
+    let mut seen: Vec<Uuid> = first_page.items.iter()
+        .chain(second_page.items.iter())
+        .map(|p| p.id)
+        .collect();
+    seen.sort();
+    created_ids.sort();
+    assert_eq!(seen, created_ids);
+    
+    // Clean up: delete the test products
+    for id in created_ids {
+        let _ = service.delete_product(id, None, None).await;
+    }
+}
+
+#[tokio::test]
+async fn test_service_get_products_sorted() {
+    let service = create_test_service();
+    let prefix = format!("Sort Test {}", Uuid::new_v4());
+    
+    let mut created_ids = Vec::new();
+    for (suffix, cost) in [("a", "30.00"), ("b", "10.00"), ("c", "30.00"), ("d", "20.00")] {
+        let created = service.create_product(NewCompleteProduct {
+            product: NewProduct {
+                id: None,
+                name: format!("{} {}", prefix, suffix),
+                cost: Money::new(cost, "USD").unwrap(),
+                active: true,
+            },
+            variants: vec![],
+        }, None).await.unwrap();
+        created_ids.push(created.id);
+    }
+    
+    // Walk the pages one row at a time so the cursor crosses the tied costs
+    let mut names = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = service.get_products(Some(ProductFilters {
+            name: Some(prefix.clone()),
+            sort: Some("cost:desc,name".to_string()),
+            limit: Some(1),
+            cursor: cursor.clone(),
+            ..Default::default()
+        })).await.unwrap();
+        names.extend(page.items.into_iter().map(|p| p.name));
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    
+    let expected: Vec<String> = ["a", "c", "d", "b"]
+        .iter()
+        .map(|suffix| format!("{} {}", prefix, suffix))
+        .collect();
+    assert_eq!(names, expected);
+    
+    // Unknown fields are rejected rather than ignored
+    let invalid = service.get_products(Some(ProductFilters {
+        sort: Some("colour".to_string()),
+        ..Default::default()
+    })).await;
+    assert!(invalid.is_err());
+    
+    // Clean up: delete the test products
+    for id in created_ids {
+        let _ = service.delete_product(id, None, None).await;
+    }
+}
+
+#[tokio::test]
+async fn test_service_timestamps_and_incremental_filters() {
+    let service = create_test_service();
+    let prefix = format!("Timestamp Test {}", Uuid::new_v4());
+    
+    let mut created = Vec::new();
+    for suffix in ["a", "b", "c"] {
+        created.push(service.create_product(NewCompleteProduct {
+            product: NewProduct {
+                id: None,
+                name: format!("{} {}", prefix, suffix),
+                cost: Money::new("15.00", "USD").unwrap(),
+                active: true,
+            },
+            variants: vec![],
+        }, None).await.unwrap());
+    }
+    assert!(created.iter().all(|p| p.created_at == p.updated_at));
+    assert!(created[0].created_at < created[2].created_at);
+    
+    // Newest arrivals first, walking the cursor across pages
+    let mut names = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = service.get_products(Some(ProductFilters {
+            name: Some(prefix.clone()),
+            sort: Some("created_at:desc".to_string()),
+            limit: Some(2),
+            cursor: cursor.clone(),
+            ..Default::default()
+        })).await.unwrap();
+        names.extend(page.items.into_iter().map(|p| p.name));
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    let expected: Vec<String> = ["c", "b", "a"].iter().map(|suffix| format!("{} {}", prefix, suffix)).collect();
+    assert_eq!(names, expected);
+    
+    // Updating moves updated_at but keeps created_at
+    let updated = service.patch_product(
+        created[0].id,
+        ProductPatch::Merge(json!({ "cost": { "amount": "12.00", "currency": "USD" } })),
+        None,
+        None
+    ).await.unwrap().unwrap().product;
+    assert_eq!(updated.created_at, created[0].created_at);
+    assert!(updated.updated_at > created[2].updated_at);
+    
+    let changed = service.get_products(Some(ProductFilters {
+        name: Some(prefix.clone()),
+        updated_after: Some(created[2].updated_at),
+        ..Default::default()
+    })).await.unwrap();
+    assert_eq!(changed.items.iter().map(|p| p.id).collect::<Vec<_>>(), vec![created[0].id]);
+    
+    let newer = service.get_products(Some(ProductFilters {
+        name: Some(prefix.clone()),
+        created_after: Some(created[0].created_at),
+        ..Default::default()
+    })).await.unwrap();
+    assert_eq!(newer.items.len(), 2);
+    assert!(newer.items.iter().all(|p| p.id != created[0].id));
+    
+    // Clean up: delete the test products
+    for product in created {
+        let _ = service.delete_product(product.id, None, None).await;
+    }
+}
+
+#[tokio::test]
+async fn test_service_exact_cost_filters() {
+    let service = create_test_service();
+    let prefix = format!("Money Test {}", Uuid::new_v4());
+    
+    let created = service.create_product(NewCompleteProduct {
+        product: NewProduct {
+            id: None,
+            name: prefix.clone(),
+            cost: Money::new("39.99", "EUR").unwrap(),
+            active: true,
+        },
+        variants: vec![],
+    }, None).await.unwrap();
+    assert_eq!(created.cost.amount_string(), "39.99");
+    assert_eq!(created.cost.currency, "EUR");
+    
+    // Both bounds on the exact price must still match the row
+    let exact = service.get_products(Some(ProductFilters {
+        name: Some(prefix.clone()),
+        cost_ge: Some("39.99".parse().unwrap()),
+        cost_le: Some("39.99".parse().unwrap()),
+        currency: Some("EUR".to_string()),
+        ..Default::default()
+    })).await.unwrap();
+    assert_eq!(exact.items.len(), 1);
+    
+    let above = service.get_products(Some(ProductFilters {
+        name: Some(prefix.clone()),
+        cost_ge: Some("39.991".parse().unwrap()),
+        currency: Some("EUR".to_string()),
+        ..Default::default()
+    })).await.unwrap();
+    assert!(above.items.is_empty());
+    
+    // Clean up: delete the product
+    let _ = service.delete_product(created.id, None, None).await;
+}
+
+fn variant(name: &str, values: &[&str]) -> NewVariantValue {
+    NewVariantValue {
+        variant: VariantRef::Name(NewVariant { name: name.to_string(), allowed_values: None }),
+        values: values.iter().map(|v| Some(v.to_string())).collect(),
+    }
+}
+
+#[tokio::test]
+async fn test_service_full_text_search() {
+    let service = create_test_service();
+    // A made-up brand keeps the search isolated from other test data
+    let brand = format!("zq{}", Uuid::new_v4().simple());
+    
+    let runner = service.create_product(NewCompleteProduct {
+        product: NewProduct {
+            id: None,
+            name: format!("{} Trail Runner", brand),
+            cost: Money::new("120.00", "USD").unwrap(),
+            active: true,
+        },
+        variants: vec![variant("Color", &["Red", "Blue"]), variant("Size", &["42", "43"])],
+    }, None).await.unwrap();
+    let walker = service.create_product(NewCompleteProduct {
+        product: NewProduct {
+            id: None,
+            name: format!("{} Trail Walker Trail", brand),
+            cost: Money::new("90.00", "USD").unwrap(),
+            active: true,
+        },
+        variants: vec![variant("Color", &["Black"]), variant("Size", &["42"])],
+    }, None).await.unwrap();
+    
+    let search = async |q: String, search_variants: bool| {
+        service.get_products(Some(ProductFilters {
+            q: Some(q),
+            search_variants: Some(search_variants),
+            ..Default::default()
+        })).await.unwrap().items.into_iter().map(|p| p.id).collect::<Vec<_>>()
+    };
+    
+    // Prefix matching on every term
+    assert_eq!(search(format!("{} run", brand), false).await, vec![runner.id]);
+    
+    // Ranked best match first: "trail" appears twice in the walker's name
+    assert_eq!(search(format!("{} trail", brand), false).await, vec![walker.id, runner.id]);
+    
+    // Ranked results page through the cursor in the same order
+    let first = service.get_products(Some(ProductFilters {
+        q: Some(format!("{} trail", brand)),
+        limit: Some(1),
+        ..Default::default()
+    })).await.unwrap();
+    let second = service.get_products(Some(ProductFilters {
+        q: Some(format!("{} trail", brand)),
+        limit: Some(1),
+        cursor: first.next_cursor.clone(),
+        ..Default::default()
+    })).await.unwrap();
+    assert_eq!(first.items[0].id, walker.id);
+    assert_eq!(second.items[0].id, runner.id);
+    assert!(second.next_cursor.is_none());
+    
+    // Variant values only count when asked for
+    assert!(search(format!("{} red 42", brand), false).await.is_empty());
+    assert_eq!(search(format!("{} red 42", brand), true).await, vec![runner.id]);
+    assert_eq!(search(format!("{} 42", brand), true).await.len(), 2);
+    
+    // Clean up: delete the test products
+    let _ = service.delete_product(runner.id, None, None).await;
+    let _ = service.delete_product(walker.id, None, None).await;
+}
+
+#[tokio::test]
+async fn test_service_fuzzy_search_and_suggestions() {
+    let service = create_test_service();
+    let token = format!("zq{}", Uuid::new_v4().simple());
+    
+    let created = service.create_product(NewCompleteProduct {
+        product: NewProduct {
+            id: None,
+            name: format!("Ultraboost Runner {}", token),
+            cost: Money::new("180.00", "USD").unwrap(),
+            active: true,
+        },
+        variants: vec![],
+    }, None).await.unwrap();
+    
+    // Misspelled autocomplete input still finds the product
+    let suggestions = service.suggest_products(SuggestQuery {
+        prefix: "ultrabost".to_string(),
+        limit: Some(50),
+    }).await.unwrap();
+    let suggestion = suggestions.iter().find(|s| s.id == created.id);
+    assert!(suggestion.is_some(), "Misspelled prefix should suggest the product");
+    assert!(suggestion.unwrap().score > 0.0);
+    
+    // Full-text search needs the right spelling, fuzzy mode does not
+    let fuzzy_filters = |fuzzy: bool| ProductFilters {
+        name: Some(token.clone()),
+        q: Some("ultrabost runer".to_string()),
+        fuzzy: Some(fuzzy),
+        ..Default::default()
+    };
+    assert!(service.get_products(Some(fuzzy_filters(false))).await.unwrap().items.is_empty());
+    let fuzzy = service.get_products(Some(fuzzy_filters(true))).await.unwrap();
+    assert_eq!(fuzzy.items.len(), 1);
+    assert_eq!(fuzzy.items[0].id, created.id);
+    
+    // Clean up: delete the product
+    let _ = service.delete_product(created.id, None, None).await;
+}
+
+#[tokio::test]
+async fn test_service_attribute_filters() {
+    let service = create_test_service();
+    let prefix = format!("Attribute Test {}", Uuid::new_v4());
+    let create = async |suffix: &str, variants: Vec<NewVariantValue>| {
+        service.create_product(NewCompleteProduct {
+            product: NewProduct {
+                id: None,
+                name: format!("{} {}", prefix, suffix),
+                cost: Money::new("80.00", "USD").unwrap(),
+                active: true,
+            },
+            variants,
+        }, None).await.unwrap()
+    };
+    
+    let black_42 = create("A", vec![variant("Size", &["41", "42"]), variant("Color", &["Black"])]).await;
+    let red_43 = create("B", vec![variant("Size", &["43"]), variant("Color", &["Red"])]).await;
+    let black_44 = create("C", vec![variant("Size", &["44"]), variant("Color", &["Black"])]).await;
+    
+    let names = async |attributes: &[(&str, &[&str])]| -> Vec<String> {
+        let filters = ProductFilters {
+            name: Some(prefix.clone()),
+            sort: Some("name".to_string()),
+            attributes: attributes
+                .iter()
+                .map(|(name, values)| (name.to_string(), values.iter().map(|v| v.to_string()).collect()))
+                .collect(),
+            ..Default::default()
+        };
+        service.get_products(Some(filters)).await.unwrap().items.into_iter().map(|p| p.name).collect()
+    };
+    
+    // Values of one attribute are ORed
+    assert_eq!(names(&[("size", &["42", "43"])]).await, vec![black_42.name.clone(), red_43.name.clone()]);
+    // Different attributes are ANDed, and matching ignores case
+    assert_eq!(names(&[("color", &["black"]), ("size", &["42", "44"])]).await, vec![black_42.name.clone(), black_44.name.clone()]);
+    assert_eq!(names(&[("color", &["red"]), ("size", &["42"])]).await, Vec::<String>::new());
+    assert!(names(&[("material", &["leather"])]).await.is_empty());
+    
+    // Clean up: delete the test products
+    for product in [black_42, red_43, black_44] {
+        let _ = service.delete_product(product.id, None, None).await;
     }
 }
 
+#[tokio::test]
+async fn test_service_product_facets() {
+    let service = create_test_service();
+    let prefix = format!("Facet Test {}", Uuid::new_v4());
+    let create = async |suffix: &str, cost: &str, active: bool, variants: Vec<NewVariantValue>| {
+        service.create_product(NewCompleteProduct {
+            product: NewProduct {
+                id: None,
+                name: format!("{} {}", prefix, suffix),
+                cost: Money::new(cost, "USD").unwrap(),
+                active,
+            },
+            variants,
+        }, None).await.unwrap()
+    };
+    
+    let products = [
+        create("A", "12.00", true, vec![variant("Size", &["42", "43"]), variant("Color", &["Black"])]).await,
+        create("B", "25.50", true, vec![variant("Size", &["42"]), variant("Color", &["Red"])]).await,
+        create("C", "49.99", false, vec![variant("Size", &["44"]), variant("Color", &["Black"])]).await,
+    ];
+    
+    let facets = async |filters: ProductFilters| {
+        let filters = ProductFilters {
+            name: Some(prefix.clone()),
+            include_facets: Some(true),
+            limit: Some(1),
+            ..filters
+        };
+        service.get_products(Some(filters)).await.unwrap().facets.expect("facets were requested")
+    };
+    let counts = |facets: &backend::models::Facets, name: &str| -> Vec<(String, i64)> {
+        facets.attributes
+            .iter()
+            .find(|a| a.name == name)
+            .map(|a| a.values.iter().map(|v| (v.value.clone(), v.count)).collect())
+            .unwrap_or_default()
+    };
+    let pairs = |expected: &[(&str, i64)]| -> Vec<(String, i64)> {
+        expected.iter().map(|(v, c)| (v.to_string(), *c)).collect()
+    };
+    
+    // Facets cover the whole filter set, not just the requested page
+    let all = facets(ProductFilters::default()).await;
+    assert_eq!(counts(&all, "Size"), pairs(&[("42", 2), ("43", 1), ("44", 1)]));
+    assert_eq!(counts(&all, "Color"), pairs(&[("Black", 2), ("Red", 1)]));
+    assert_eq!((all.active.active, all.active.inactive), (2, 1));
+    assert_eq!(all.cost.iter().map(|b| b.count).sum::<i64>(), 3);
+    assert!(all.cost.iter().all(|b| b.min.currency == "USD" && b.min.amount < b.max.amount));
+    
+    // Filtering on color narrows the sizes but keeps the other colors countable
+    let black = facets(ProductFilters {
+        attributes: [("color".to_string(), vec!["black".to_string()])].into_iter().collect(),
+        ..Default::default()
+    }).await;
+    assert_eq!(counts(&black, "Size"), pairs(&[("42", 1), ("43", 1), ("44", 1)]));
+    assert_eq!(counts(&black, "Color"), pairs(&[("Black", 2), ("Red", 1)]));
+    assert_eq!((black.active.active, black.active.inactive), (1, 1));
+    
+    // The active filter leaves the active counts alone
+    let active_only = facets(ProductFilters { is_active: Some(true), ..Default::default() }).await;
+    assert_eq!((active_only.active.active, active_only.active.inactive), (2, 1));
+    assert_eq!(active_only.cost.iter().map(|b| b.count).sum::<i64>(), 2);
+    
+    // Facets are only computed on request
+    let plain = ProductFilters { name: Some(prefix.clone()), ..Default::default() };
+    assert!(service.get_products(Some(plain)).await.unwrap().facets.is_none());
+    
+    // Clean up: delete the test products
+    for product in products {
+        let _ = service.delete_product(product.id, None, None).await;
+    }
+}
+
+#[tokio::test]
+async fn test_service_get_complete_products() {
+    let service = create_test_service();
+    let prefix = format!("Complete Test {}", Uuid::new_v4());
+    
+    let with_variants = service.create_product(NewCompleteProduct {
+        product: NewProduct {
+            id: None,
+            name: format!("{} A", prefix),
+            cost: Money::new("75.00", "USD").unwrap(),
+            active: true,
+        },
+        variants: vec![variant("Size", &["43", "42"]), variant("Color", &["Red"])],
+    }, None).await.unwrap();
+    let without_variants = service.create_product(NewCompleteProduct {
+        product: NewProduct {
+            id: None,
+            name: format!("{} B", prefix),
+            cost: Money::new("25.00", "USD").unwrap(),
+            active: true,
+        },
+        variants: vec![],
+    }, None).await.unwrap();
+    
+    // Single product read groups values under each variant
+    let complete = service.get_complete_product_by_id(with_variants.id).await.unwrap().unwrap();
+    assert_eq!(complete.product.id, with_variants.id);
+    let groups: Vec<(String, Vec<Option<String>>)> = complete.variants.iter()
+        .map(|g| (g.variant.name.clone(), g.values.iter().map(|v| v.value.clone()).collect()))
+        .collect();
+    assert_eq!(groups, vec![
+        ("Color".to_string(), vec![Some("Red".to_string())]),
+        ("Size".to_string(), vec![Some("42".to_string()), Some("43".to_string())]),
+    ]);
+    
+    // List read keeps the page order and attaches variants per product
+    let page = service.get_complete_products(Some(ProductFilters {
+        name: Some(prefix.clone()),
+        sort: Some("name".to_string()),
+        ..Default::default()
+    })).await.unwrap();
+    assert_eq!(page.items.len(), 2);
+    assert_eq!(page.items[0].product.id, with_variants.id);
+    assert_eq!(page.items[0].variants.len(), 2);
+    assert_eq!(page.items[1].product.id, without_variants.id);
+    assert!(page.items[1].variants.is_empty());
+    
+    assert!(service.get_complete_product_by_id(Uuid::new_v4()).await.unwrap().is_none());
+    
+    // Clean up: delete the test products
+    let _ = service.delete_product(with_variants.id, None, None).await;
+    let _ = service.delete_product(without_variants.id, None, None).await;
+}
+
 #[tokio::test]
 async fn test_service_get_products_no_filter() {
     let service = create_test_service();
     
-    let result = service.get_products(None);
+    let result = service.get_products(None).await;
     assert!(result.is_ok(), "Failed to get all products: {:?}", result.err());
     
-    let products = result.unwrap();
+    let products = result.unwrap().items;
     // We don't assert on the count since other tests might have created products
     // Just verify the call succeeds and returns a vector
     println!("Found {} products", products.len());
@@ -208,7 +1253,7 @@ async fn test_service_get_nonexistent_product() {
     
     // Use a random UUID that doesn't exist
     let non_existent_id = Uuid::new_v4();
-    let result = service.get_product_by_id(non_existent_id);
+    let result = service.get_product_by_id(non_existent_id).await;
     assert!(result.is_ok());
     assert!(result.unwrap().is_none());
 }
@@ -217,15 +1262,16 @@ async fn test_service_get_nonexistent_product() {
 async fn test_service_update_nonexistent_product() {
     let service = create_test_service();
     
-    let updates = ProductUpdates {
-        name: Some("This won't work".to_string()),
-        cost: Some(100.0),
-        active: Some(true),
+    let document = ProductDocument {
+        name: "This won't work".to_string(),
+        cost: Money::new("100.00", "USD").unwrap(),
+        active: true,
+        variants: BTreeMap::new(),
     };
     
     // Use a random UUID that doesn't exist
     let non_existent_id = Uuid::new_v4();
-    let result = service.update_product(non_existent_id, updates);
+    let result = service.replace_product(non_existent_id, document, None, None).await;
     assert!(result.is_ok());
     assert!(result.unwrap().is_none());
 }
@@ -236,7 +1282,7 @@ async fn test_service_delete_nonexistent_product() {
     
     // Use a random UUID that doesn't exist
     let non_existent_id = Uuid::new_v4();
-    let result = service.delete_product(non_existent_id);
+    let result = service.delete_product(non_existent_id, None, None).await;
     assert!(result.is_ok());
     assert!(!result.unwrap()); // Should return false for non-existent product
 }
@@ -1,12 +1,14 @@
 use backend::services::ProductService;
-use backend::models::{NewCompleteProduct, NewProduct, ProductFilters, ProductUpdates};
-use backend::config::{create_pool, get_settings};
+use backend::models::{Money, NewCompleteProduct, NewProduct, ProductDocument, ProductFilters, ProductPatch};
+use backend::config::{create_pool, get_settings, Database};
+use serde_json::json;
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
 fn create_test_service() -> ProductService {
     let settings = get_settings().unwrap();
     let pool = create_pool(&settings);
-    ProductService::new(pool)
+    ProductService::new(Database::new(pool))
 }
 
 #[tokio::test]
@@ -17,18 +19,18 @@ async fn test_service_create_product() {
         product: NewProduct {
             id: None,
             name: "Service Test Product".to_string(),
-            cost: 15.99,
+            cost: Money::new("15.99", "USD").unwrap(),
             active: true,
         },
         variants: vec![],
     };
     
-    let result = service.create_product(new_product);
+    let result = service.create_product(new_product, None).await;
     assert!(result.is_ok());
     
     let created_product = result.unwrap();
     assert_eq!(created_product.name, "Service Test Product");
-    assert_eq!(created_product.cost, 15.99);
+    assert_eq!(created_product.cost.amount_string(), "15.99");
     assert!(created_product.active);
 }
 
@@ -37,18 +39,20 @@ async fn test_service_get_products() {
     let service = create_test_service();
     
     // Test without filters
-    let result = service.get_products(None);
+    let result = service.get_products(None).await;
     assert!(result.is_ok());
     
     // Test with filters
     let filters = ProductFilters {
         name: Some("test".to_string()),
-        cost_ge: Some(10.0),
-        cost_le: Some(50.0),
+        cost_ge: Some("10.00".parse().unwrap()),
+        cost_le: Some("50.00".parse().unwrap()),
+        currency: Some("USD".to_string()),
         is_active: Some(true),
+        ..Default::default()
     };
     
-    let result = service.get_products(Some(filters));
+    let result = service.get_products(Some(filters)).await;
     assert!(result.is_ok());
 }
 
@@ -56,7 +60,7 @@ async fn test_service_get_products() {
 async fn test_service_get_product_by_id_not_found() {
     let service = create_test_service();
     
-    let result = service.get_product_by_id(Uuid::new_v4());
+    let result = service.get_product_by_id(Uuid::new_v4()).await;
     assert!(result.is_ok());
     assert!(result.unwrap().is_none());
 }
@@ -65,13 +69,9 @@ async fn test_service_get_product_by_id_not_found() {
 async fn test_service_update_product_not_found() {
     let service = create_test_service();
     
-    let updates = ProductUpdates {
-        name: Some("Updated Name".to_string()),
-        cost: Some(99.99),
-        active: Some(false),
-    };
+    let patch = ProductPatch::Merge(json!({ "name": "Updated Name", "active": false }));
     
-    let result = service.update_product(Uuid::new_v4(), updates);
+    let result = service.patch_product(Uuid::new_v4(), patch, None, None).await;
     assert!(result.is_ok());
     assert!(result.unwrap().is_none());
 }
@@ -80,7 +80,7 @@ async fn test_service_update_product_not_found() {
 async fn test_service_delete_product_not_found() {
     let service = create_test_service();
     
-    let result = service.delete_product(Uuid::new_v4());
+    let result = service.delete_product(Uuid::new_v4(), None, None).await;
     assert!(result.is_ok());
     assert!(!result.unwrap()); // Should return false for non-existent product
 }
@@ -94,40 +94,41 @@ async fn test_service_full_flow() {
         product: NewProduct {
             id: None,
             name: "Service Flow Test".to_string(),
-            cost: 45.00,
+            cost: Money::new("45.00", "USD").unwrap(),
             active: true,
         },
         variants: vec![],
     };
     
-    let created = service.create_product(new_product).unwrap();
+    let created = service.create_product(new_product, None).await.unwrap();
     let product_id = created.id;
     
     // 2. Get the product by ID
-    let retrieved = service.get_product_by_id(product_id).unwrap();
+    let retrieved = service.get_product_by_id(product_id).await.unwrap();
     assert!(retrieved.is_some());
     let retrieved = retrieved.unwrap();
     assert_eq!(retrieved.name, "Service Flow Test");
     
-    // 3. Update the product
-    let updates = ProductUpdates {
-        name: Some("Updated Service Flow Test".to_string()),
-        cost: Some(55.00),
-        active: Some(false),
+    // 3. Replace the product
+    let document = ProductDocument {
+        name: "Updated Service Flow Test".to_string(),
+        cost: Money::new("55.00", "USD").unwrap(),
+        active: false,
+        variants: BTreeMap::new(),
     };
     
-    let updated = service.update_product(product_id, updates).unwrap();
+    let updated = service.replace_product(product_id, document, None, None).await.unwrap();
     assert!(updated.is_some());
-    let updated = updated.unwrap();
+    let updated = updated.unwrap().product;
     assert_eq!(updated.name, "Updated Service Flow Test");
-    assert_eq!(updated.cost, 55.0);
+    assert_eq!(updated.cost.amount_string(), "55.00");
     assert!(!updated.active);
     
     // 4. Delete the product
-    let deleted = service.delete_product(product_id).unwrap();
+    let deleted = service.delete_product(product_id, None, None).await.unwrap();
     assert!(deleted);
     
     // 5. Verify deletion
-    let not_found = service.get_product_by_id(product_id).unwrap();
+    let not_found = service.get_product_by_id(product_id).await.unwrap();
     assert!(not_found.is_none());
 }
@@ -0,0 +1,104 @@
+use backend::models::{
+    FieldError, Money, NewCompleteProduct, NewProduct, NewVariant, NewVariantValue, ProductUpdates, Validate,
+    VariantRef, VariantUpdate, MAX_PRODUCT_NAME_LENGTH
+};
+use uuid::Uuid;
+
+fn fields(errors: Vec<FieldError>) -> Vec<String> {
+    errors.into_iter().map(|error| error.field).collect()
+}
+
+fn named(name: &str, values: &[Option<&str>]) -> NewVariantValue {
+    NewVariantValue {
+        variant: VariantRef::Name(NewVariant { name: name.to_string(), allowed_values: None }),
+        values: values.iter().map(|value| value.map(String::from)).collect()
+    }
+}
+
+fn product(name: &str, amount: &str) -> NewProduct {
+    NewProduct { id: None, name: name.to_string(), cost: Money::new(amount, "USD").unwrap(), active: true }
+}
+
+#[test]
+fn test_valid_product_passes() {
+    let item = NewCompleteProduct {
+        product: product("Runner", "120.00"),
+        variants: vec![named("Size", &[Some("41"), Some("42")]), named("Color", &[Some("Black")])]
+    };
+    assert!(item.validated().is_ok());
+}
+
+#[test]
+fn test_every_failing_field_is_listed() {
+    let item = NewCompleteProduct {
+        product: product("   ", "-1.00"),
+        variants: vec![
+            named("Size", &[Some("41")]),
+            named("Color", &[]),
+            named("Width", &[Some(" "), None, Some("Wide"), Some("Wide")]),
+            named(" size ", &[Some("42")])
+        ]
+    };
+
+    let errors = item.validated().unwrap_err();
+    assert_eq!(fields(errors), vec![
+        "product.name",
+        "product.cost",
+        "variants[1].values",
+        "variants[2].values[0]",
+        "variants[2].values[1]",
+        "variants[2].values[3]",
+        "variants[3].variant"
+    ]);
+}
+
+#[test]
+fn test_length_and_cost_limits() {
+    let long_name = "x".repeat(MAX_PRODUCT_NAME_LENGTH + 1);
+    let errors = product(&long_name, "1000000000.01").validated().unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].message.contains(&MAX_PRODUCT_NAME_LENGTH.to_string()));
+    assert_eq!(errors[1].field, "cost");
+
+    // Characters are counted rather than bytes
+    assert!(product(&"ü".repeat(MAX_PRODUCT_NAME_LENGTH), "1.00").validated().is_ok());
+}
+
+#[test]
+fn test_updates_only_check_given_fields() {
+    let updates = ProductUpdates { name: None, cost: None, active: Some(false) };
+    assert!(updates.validated().is_ok());
+
+    let updates = ProductUpdates { name: Some(String::new()), cost: Some(Money::new("-1", "USD").unwrap()), active: None };
+    assert_eq!(fields(updates.validated().unwrap_err()), vec!["name", "cost"]);
+}
+
+#[test]
+fn test_variant_ids_listed_twice() {
+    let id = Uuid::new_v4();
+    let by_id = |values: &[&str]| NewVariantValue {
+        variant: VariantRef::Id { id },
+        values: values.iter().map(|value| Some(value.to_string())).collect()
+    };
+    let item = NewCompleteProduct { product: product("Runner", "1.00"), variants: vec![by_id(&["41"]), by_id(&["42"])] };
+    assert_eq!(fields(item.validated().unwrap_err()), vec!["variants[1].variant"]);
+}
+
+#[test]
+fn test_variant_definitions() {
+    let variant = NewVariant { name: "Size".to_string(), allowed_values: Some(vec!["41".to_string(), "".to_string()]) };
+    assert_eq!(fields(variant.validated().unwrap_err()), vec!["allowed_values[1]"]);
+}
+
+#[test]
+fn test_variant_update_tells_null_from_absent() {
+    let kept: VariantUpdate = serde_json::from_str(r#"{"name": "Size"}"#).unwrap();
+    assert_eq!(kept.allowed_values, None);
+
+    let cleared: VariantUpdate = serde_json::from_str(r#"{"allowed_values": null}"#).unwrap();
+    assert_eq!(cleared.allowed_values, Some(None));
+    assert!(cleared.validated().is_ok());
+
+    let restricted: VariantUpdate = serde_json::from_str(r#"{"allowed_values": ["41", ""]}"#).unwrap();
+    assert_eq!(fields(restricted.validated().unwrap_err()), vec!["allowed_values[1]"]);
+}
@@ -0,0 +1,392 @@
+// Integration tests for the VariantService
+// These tests require a running PostgreSQL database with the schema set up
+
+use backend::services::{ProductService, VariantService};
+use backend::models::{
+    Money, NewCompleteProduct, NewProduct, NewProductVariantValue, NewVariant, NewVariantValue, ProductPatch,
+    ProductVariantUpdates, VariantRef, VariantUpdate
+};
+use backend::config::{create_pool, get_settings, Database};
+use backend::traits::responses::AppError;
+use diesel::connection::SimpleConnection;
+use uuid::Uuid;
+
+fn create_test_services() -> (ProductService, VariantService) {
+    let settings = get_settings().unwrap();
+    let db = Database::new(create_pool(&settings));
+    (ProductService::new(db.clone()), VariantService::new(db))
+}
+
+async fn create_test_product(service: &ProductService, variants: Vec<NewVariantValue>) -> Uuid {
+    service.create_product(NewCompleteProduct {
+        product: NewProduct {
+            id: None,
+            name: "Variant Test Product".to_string(),
+            cost: Money::new("19.99", "USD").unwrap(),
+            active: true,
+        },
+        variants,
+    }, None).await.unwrap().id
+}
+
+// Variant definitions are global, so each test works with its own names
+fn unique_name(prefix: &str) -> String {
+    format!("{} {}", prefix, Uuid::new_v4().simple())
+}
+
+fn named(name: &str, allowed_values: Option<&[&str]>) -> VariantRef {
+    VariantRef::Name(NewVariant {
+        name: name.to_string(),
+        allowed_values: allowed_values.map(|values| values.iter().map(|v| v.to_string()).collect()),
+    })
+}
+
+fn values(values: &[&str]) -> Vec<Option<String>> {
+    values.iter().map(|v| Some(v.to_string())).collect()
+}
+
+#[tokio::test]
+async fn test_service_product_variant_crud() {
+    let (products, service) = create_test_services();
+    let product_id = create_test_product(&products, vec![]).await;
+    let size = unique_name("Size");
+
+    let version = products.get_product_by_id(product_id).await.unwrap().unwrap().version;
+
+    // Add a variant with two values
+    let added = service.add_product_variant(product_id, NewVariantValue {
+        variant: named(&size, None),
+        values: values(&["42", "43"]),
+    }, None).await.unwrap().expect("product should exist");
+    assert_eq!(added.variant.name, size);
+    assert_eq!(added.values.len(), 2);
+    let variant_id = added.variant.id;
+
+    // Variant changes count as changes to the product
+    let touched = products.get_product_by_id(product_id).await.unwrap().unwrap();
+    assert_eq!(touched.version, version + 1);
+
+    // Add, update and remove a single value
+    let value = service.add_variant_value(product_id, variant_id, NewProductVariantValue {
+        value: "44".to_string()
+    }, None).await.unwrap().expect("product and variant should exist");
+    assert_eq!(value.value.as_deref(), Some("44"));
+
+    let updated = service.update_variant_value(product_id, variant_id, value.id, ProductVariantUpdates {
+        value: "45".to_string(),
+    }, None).await.unwrap().expect("value should exist");
+    assert_eq!(updated.value.as_deref(), Some("45"));
+
+    // A value the product already has for the variant is a conflict, whether added or updated to
+    let err = service.add_variant_value(product_id, variant_id, NewProductVariantValue {
+        value: "42".to_string()
+    }, None).await.unwrap_err();
+    assert_eq!(AppError::from(err).code(), "conflict");
+    let err = service.update_variant_value(product_id, variant_id, value.id, ProductVariantUpdates {
+        value: "43".to_string(),
+    }, None).await.unwrap_err();
+    assert_eq!(AppError::from(err).code(), "conflict");
+
+    let listed = service.list_product_variants(product_id).await.unwrap().unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].values.len(), 3);
+
+    assert!(service.remove_variant_value(product_id, variant_id, value.id, None).await.unwrap());
+    assert!(!service.remove_variant_value(product_id, variant_id, value.id, None).await.unwrap());
+
+    // Rename the shared variant definition
+    let renamed_to = unique_name("Shoe Size");
+    let renamed = service.update_variant(variant_id, VariantUpdate {
+        name: Some(renamed_to.clone()),
+        allowed_values: None,
+    }, None).await.unwrap().expect("variant should exist");
+    assert_eq!(renamed.name, renamed_to);
+    assert_eq!(service.get_variant(variant_id).await.unwrap().unwrap().name, renamed_to);
+
+    // Removing the variant from the product keeps the shared definition
+    assert!(service.remove_product_variant(product_id, variant_id, None).await.unwrap());
+    assert!(service.list_product_variants(product_id).await.unwrap().unwrap().is_empty());
+    assert!(service.get_variant(variant_id).await.unwrap().is_some());
+
+    // Clean up: delete the test product and variant
+    let _ = products.delete_product(product_id, None, None).await;
+    let _ = service.delete_variant(variant_id, None).await;
+}
+
+#[tokio::test]
+async fn test_service_variants_are_shared() {
+    let (products, service) = create_test_services();
+    let color = unique_name("Color");
+
+    let first = create_test_product(&products, vec![NewVariantValue {
+        variant: named(&color, None),
+        values: values(&["Red"]),
+    }]).await;
+    // Same name in another case and with stray whitespace resolves to the same definition
+    let second = create_test_product(&products, vec![NewVariantValue {
+        variant: named(&format!("  {}  ", color.to_uppercase()), None),
+        values: values(&["Blue"]),
+    }]).await;
+
+    let first_variants = service.list_product_variants(first).await.unwrap().unwrap();
+    let second_variants = service.list_product_variants(second).await.unwrap().unwrap();
+    let variant_id = first_variants[0].variant.id;
+    assert_eq!(second_variants[0].variant.id, variant_id);
+
+    // Reference the definition by id
+    let third = create_test_product(&products, vec![NewVariantValue {
+        variant: VariantRef::Id { id: variant_id },
+        values: values(&["Green"]),
+    }]).await;
+    let third_variants = service.list_product_variants(third).await.unwrap().unwrap();
+    assert_eq!(third_variants[0].variant.id, variant_id);
+    assert_eq!(third_variants[0].variant.name, color);
+
+    let matching: Vec<_> = service.list_variants().await.unwrap().into_iter().filter(|v| v.name == color).collect();
+    assert_eq!(matching.len(), 1);
+
+    // Unknown ids and duplicate definitions are rejected
+    let unknown = NewVariantValue { variant: VariantRef::Id { id: Uuid::new_v4() }, values: vec![] };
+    assert!(service.add_product_variant(first, unknown, None).await.is_err());
+    assert!(service.create_variant(NewVariant { name: color.clone(), allowed_values: None }).await.is_err());
+    assert!(service.create_variant(NewVariant { name: color.to_lowercase(), allowed_values: None }).await.is_err());
+
+    // Clean up: delete the test products and variant
+    for product_id in [first, second, third] {
+        let _ = products.delete_product(product_id, None, None).await;
+    }
+    let _ = service.delete_variant(variant_id, None).await;
+}
+
+#[tokio::test]
+async fn test_service_variant_allowed_values() {
+    let (products, service) = create_test_services();
+    let width = unique_name("Width");
+
+    let variant = service.create_variant(NewVariant {
+        name: width.clone(),
+        allowed_values: Some(vec!["Narrow".to_string(), "Wide".to_string()]),
+    }).await.unwrap();
+    assert_eq!(variant.allowed_values.as_deref().map(<[String]>::len), Some(2));
+
+    // A value outside the list fails the whole product creation
+    let result = products.create_product(NewCompleteProduct {
+        product: NewProduct {
+            id: None,
+            name: "Disallowed Width Product".to_string(),
+            cost: Money::new("19.99", "USD").unwrap(),
+            active: true,
+        },
+        variants: vec![NewVariantValue { variant: named(&width, None), values: values(&["Medium"]) }],
+    }, None).await;
+    assert!(result.is_err());
+
+    let product_id = create_test_product(&products, vec![NewVariantValue {
+        variant: named(&width, None),
+        values: values(&["Narrow"]),
+    }]).await;
+
+    assert!(service.add_variant_value(product_id, variant.id, NewProductVariantValue {
+        value: "Medium".to_string()
+    }, None).await.is_err());
+    let wide = service.add_variant_value(product_id, variant.id, NewProductVariantValue {
+        value: "Wide".to_string()
+    }, None).await.unwrap().unwrap();
+    assert!(service.update_variant_value(product_id, variant.id, wide.id, ProductVariantUpdates {
+        value: "Medium".to_string(),
+    }, None).await.is_err());
+
+    // The allowed values can't shrink below the values already in use
+    assert!(service.update_variant(variant.id, VariantUpdate {
+        name: None,
+        allowed_values: Some(Some(vec!["Narrow".to_string()])),
+    }, None).await.is_err());
+    let widened = service.update_variant(variant.id, VariantUpdate {
+        name: None,
+        allowed_values: Some(Some(vec!["Narrow".to_string(), "Medium".to_string(), "Wide".to_string()])),
+    }, None).await.unwrap().unwrap();
+    assert!(widened.allows("Medium"));
+
+    // Leaving the allowed values out keeps them, and null lifts the restriction again
+    let renamed = service.update_variant(variant.id, VariantUpdate {
+        name: Some(unique_name("Width")),
+        allowed_values: None,
+    }, None).await.unwrap().unwrap();
+    assert_eq!(renamed.allowed_values, widened.allowed_values);
+    let unrestricted = service.update_variant(variant.id, VariantUpdate {
+        name: None,
+        allowed_values: Some(None),
+    }, None).await.unwrap().unwrap();
+    assert!(unrestricted.allowed_values.is_none());
+    assert!(service.get_variant(variant.id).await.unwrap().unwrap().allows("Extra Wide"));
+
+    // Clean up: delete the test product and variant
+    let _ = products.delete_product(product_id, None, None).await;
+    let _ = service.delete_variant(variant.id, None).await;
+}
+
+#[tokio::test]
+async fn test_service_variants_of_missing_product() {
+    let (_, service) = create_test_services();
+    let missing = Uuid::new_v4();
+
+    assert!(service.list_product_variants(missing).await.unwrap().is_none());
+    assert!(service.add_product_variant(missing, NewVariantValue {
+        variant: named("Color", None),
+        values: vec![],
+    }, None).await.unwrap().is_none());
+    assert!(service.add_variant_value(missing, Uuid::new_v4(), NewProductVariantValue {
+        value: "Red".to_string()
+    }, None).await.unwrap().is_none());
+    assert!(!service.remove_product_variant(missing, Uuid::new_v4(), None).await.unwrap());
+    assert!(!service.delete_variant(Uuid::new_v4(), None).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_service_variants_of_deleted_product() {
+    let (products, service) = create_test_services();
+    let color = unique_name("Color");
+    let product_id = create_test_product(&products, vec![NewVariantValue {
+        variant: named(&color, None),
+        values: values(&["Red"]),
+    }]).await;
+    let listed = service.list_product_variants(product_id).await.unwrap().unwrap();
+    let variant_id = listed[0].variant.id;
+    let value_id = listed[0].values[0].id;
+
+    // A tombstoned product's variants can no longer be changed
+    assert!(products.delete_product(product_id, None, None).await.unwrap());
+    assert!(service.update_variant_value(product_id, variant_id, value_id, ProductVariantUpdates {
+        value: "Blue".to_string(),
+    }, None).await.unwrap().is_none());
+    assert!(!service.remove_variant_value(product_id, variant_id, value_id, None).await.unwrap());
+    assert!(!service.remove_product_variant(product_id, variant_id, None).await.unwrap());
+
+    // Renaming the shared definition doesn't write to the tombstone, and deleting it
+    // is refused while the tombstone still carries its values
+    let deleted_version = products.product_history(product_id).await.unwrap().unwrap()[0].revision;
+    let renamed = unique_name("Colour");
+    service.update_variant(variant_id, VariantUpdate { name: Some(renamed.clone()), allowed_values: None }, None)
+        .await
+        .unwrap()
+        .unwrap();
+    let history = products.product_history(product_id).await.unwrap().unwrap();
+    assert_eq!(history[0].action, "delete");
+    assert_eq!(history[0].revision, deleted_version);
+    let err = service.delete_variant(variant_id, None).await.unwrap_err();
+    assert_eq!(AppError::from(err).code(), "conflict");
+
+    // Restoring the product brings its variants back untouched
+    let restored = products.restore_product(product_id, None).await.unwrap().unwrap();
+    assert_eq!(restored.version, deleted_version + 1);
+    let listed = service.list_product_variants(product_id).await.unwrap().unwrap();
+    assert_eq!(listed[0].variant.name, renamed);
+    assert_eq!(listed[0].values[0].value.as_deref(), Some("Red"));
+
+    // Clean up: delete the test product and variant
+    let _ = products.delete_product(product_id, None, None).await;
+    let _ = service.delete_variant(variant_id, None).await;
+}
+
+#[tokio::test]
+async fn test_service_variant_changes_are_recorded() {
+    let (products, service) = create_test_services();
+    let color = unique_name("Color");
+    let product_id = create_test_product(&products, vec![NewVariantValue {
+        variant: named(&color, None),
+        values: values(&["Red"]),
+    }]).await;
+    let variant_id = service.list_product_variants(product_id).await.unwrap().unwrap()[0].variant.id;
+
+    // Every variant change is a revision of the product, diffed like its other fields
+    service.add_variant_value(product_id, variant_id, NewProductVariantValue {
+        value: "Blue".to_string()
+    }, Some("dana")).await.unwrap().unwrap();
+    let history = products.product_history(product_id).await.unwrap().unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].revision, products.get_product_by_id(product_id).await.unwrap().unwrap().version);
+    assert_eq!(history[0].changed_by.as_deref(), Some("dana"));
+    assert_eq!(history[0].changes.len(), 1);
+    assert_eq!(history[0].changes[0].field, "variants");
+    assert_eq!(history[0].changes[0].from.as_ref().unwrap()[0]["values"], serde_json::json!(["Red"]));
+    assert_eq!(history[0].changes[0].to[0]["values"], serde_json::json!(["Blue", "Red"]));
+
+    // The current revision can be reverted to, and an earlier one brings its variants back
+    assert!(products.revert_product(product_id, history[0].revision, None, None).await.unwrap().is_some());
+    products.revert_product(product_id, history[1].revision, None, None).await.unwrap().unwrap();
+    let listed = service.list_product_variants(product_id).await.unwrap().unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].values.len(), 1);
+    assert_eq!(listed[0].values[0].value.as_deref(), Some("Red"));
+
+    // Deleting the definition is recorded on the product without it
+    assert!(service.delete_variant(variant_id, Some("dana")).await.unwrap());
+    let history = products.product_history(product_id).await.unwrap().unwrap();
+    assert_eq!(history[0].changes[0].field, "variants");
+    assert_eq!(history[0].changes[0].to, serde_json::json!([]));
+
+    // Reverting past the deletion creates the definition again
+    products.revert_product(product_id, history[1].revision, None, None).await.unwrap().unwrap();
+    let listed = service.list_product_variants(product_id).await.unwrap().unwrap();
+    assert_eq!(listed[0].variant.name, color);
+
+    // A patch that only touches variants records just that
+    products.patch_product(
+        product_id,
+        ProductPatch::Merge(serde_json::json!({ "variants": { color.as_str(): ["Green"] } })),
+        None,
+        None
+    ).await.unwrap().unwrap();
+    let history = products.product_history(product_id).await.unwrap().unwrap();
+    assert_eq!(history[0].changes.len(), 1);
+    assert_eq!(history[0].changes[0].to[0]["values"], serde_json::json!(["Green"]));
+
+    // Clean up: delete the test product and variant
+    let _ = products.delete_product(product_id, None, None).await;
+    let _ = service.delete_variant(listed[0].variant.id, None).await;
+}
+
+#[tokio::test]
+async fn test_service_variant_writes_lock_the_product() {
+    let settings = get_settings().unwrap();
+    let db = Database::new(create_pool(&settings));
+    let (products, service) = (ProductService::new(db.clone()), VariantService::new(db.clone()));
+    let color = unique_name("Color");
+    let product_id = create_test_product(&products, vec![NewVariantValue {
+        variant: named(&color, None),
+        values: values(&["Red"]),
+    }]).await;
+    let variant_id = service.list_product_variants(product_id).await.unwrap().unwrap()[0].variant.id;
+
+    // Another writer holds the product row and deletes the product before letting go
+    let holder = tokio::spawn({
+        let db = db.clone();
+        async move {
+            db.run(move |conn| {
+                conn.batch_execute(&format!(
+                    "BEGIN; \
+                     SELECT id FROM products WHERE id = '{id}' FOR UPDATE; \
+                     SELECT pg_sleep(0.3); \
+                     UPDATE products SET deleted_at = now(), version = version + 1 WHERE id = '{id}'; \
+                     COMMIT;",
+                    id = product_id
+                ))?;
+                Ok(())
+            }).await
+        }
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    // The variant write waits for the lock and then sees the product is gone
+    assert!(service.add_variant_value(product_id, variant_id, NewProductVariantValue {
+        value: "Blue".to_string()
+    }, None).await.unwrap().is_none());
+    holder.await.unwrap().unwrap();
+
+    products.restore_product(product_id, None).await.unwrap().unwrap();
+    let listed = service.list_product_variants(product_id).await.unwrap().unwrap();
+    assert_eq!(listed[0].values.len(), 1);
+
+    // Clean up: delete the test product
+    let _ = products.delete_product(product_id, None, None).await;
+}
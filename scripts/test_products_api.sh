@@ -57,7 +57,7 @@ test_create_simple_product() {
         -d '{
             "product": {
                 "name": "Test Shoe",
-                "cost": 99.99,
+                "cost": { "amount": "99.99", "currency": "USD" },
                 "active": true
             },
             "variants": []
@@ -81,7 +81,7 @@ test_create_product_with_variants() {
         -d '{
             "product": {
                 "name": "Running Shoe",
-                "cost": 129.99,
+                "cost": { "amount": "129.99", "currency": "USD" },
                 "active": true
             },
             "variants": [
@@ -172,7 +172,7 @@ test_filter_products_by_name() {
 test_filter_products_by_cost() {
     echo -e "${BLUE}Test 7: Filter Products by Cost${NC}"
     
-    local response=$(curl -s -X GET "$PRODUCTS_URL?cost_ge=100&cost_le=150")
+    local response=$(curl -s -X GET "$PRODUCTS_URL?cost_ge=100&cost_le=150&currency=USD")
     
     if echo "$response" | grep -q '"Running Shoe"' && echo "$response" | grep -q '\['; then
         print_test_result "Filter Products by Cost Range" "PASS" "$response"
@@ -207,11 +207,11 @@ test_update_product() {
         -H "Content-Type: application/json" \
         -d '{
             "name": "Updated Test Shoe",
-            "cost": 149.99,
+            "cost": { "amount": "149.99", "currency": "USD" },
             "active": false
         }')
     
-    if echo "$response" | grep -q '"name":"Updated Test Shoe"' && echo "$response" | grep -q '"cost":149.99'; then
+    if echo "$response" | grep -q '"name":"Updated Test Shoe"' && echo "$response" | grep -q '"amount":"149.99"'; then
         print_test_result "Update Product" "PASS" "$response"
     else
         print_test_result "Update Product" "FAIL" "$response"